@@ -0,0 +1,73 @@
+use std::{env, fmt::Write, fs, path::Path, process::Command};
+
+const BLOCK_PROPERTIES: &str = "data/block_properties.txt";
+
+fn main() {
+    embed_git_hash();
+    generate_block_properties();
+}
+
+/// Embeds the commit being built, so `--version` can tell builds of the same release apart.
+fn embed_git_hash() {
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map_or_else(|| "unknown".to_string(), |hash| hash.trim().to_string());
+    println!("cargo:rustc-env=DISPA_GIT_HASH={hash}");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
+
+/// Turns the block property table into a static slice, so the compiler doesn't parse it at runtime.
+fn generate_block_properties() {
+    println!("cargo:rerun-if-changed={BLOCK_PROPERTIES}");
+    let table = fs::read_to_string(BLOCK_PROPERTIES).expect("the block property table is readable");
+    let mut blocks = String::new();
+    for (index, line) in table.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (ids, properties) = line.split_once(" : ").unwrap_or_else(|| {
+            panic!(
+                "{BLOCK_PROPERTIES}:{}: expected '<ids> : <properties>'",
+                index + 1
+            )
+        });
+        let properties = properties
+            .split_whitespace()
+            .map(|property| {
+                let (key, values) = property.split_once('=').unwrap_or_else(|| {
+                    panic!("{BLOCK_PROPERTIES}:{}: expected 'key=values'", index + 1)
+                });
+                format!("({key:?}, &[{}])", property_values(values).join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        for id in ids.split_whitespace() {
+            let _ = writeln!(blocks, "    ({id:?}, &[{properties}]),");
+        }
+    }
+    let out_dir = env::var("OUT_DIR").expect("cargo sets OUT_DIR");
+    fs::write(
+        Path::new(&out_dir).join("block_properties.rs"),
+        format!("static BLOCKS: &[(&str, Properties)] = &[\n{blocks}];\n"),
+    )
+    .expect("the generated table is writable");
+}
+
+/// Expands `a|b` into its values and `0..7` into every whole number in the range, each quoted.
+fn property_values(values: &str) -> Vec<String> {
+    if let Some((low, high)) = values.split_once("..") {
+        if let (Ok(low), Ok(high)) = (low.parse::<u32>(), high.parse::<u32>()) {
+            return (low..=high).map(|value| format!("\"{value}\"")).collect();
+        }
+    }
+    values
+        .split('|')
+        .map(|value| format!("{value:?}"))
+        .collect()
+}
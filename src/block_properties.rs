@@ -0,0 +1,44 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+/// Each property of a block, with every value it accepts.
+pub type Properties = &'static [(&'static str, &'static [&'static str])];
+
+// Generated by build.rs from `data/block_properties.txt`.
+include!(concat!(env!("OUT_DIR"), "/block_properties.rs"));
+
+const VANILLA_NAMESPACE: &str = "minecraft:";
+
+/// Looks up the properties of a vanilla block, building the lookup table on first use.
+/// Returns `None` for blocks the table doesn't list, such as modded blocks, which can't be checked.
+pub fn properties(id: &str) -> Option<Properties> {
+    static TABLE: OnceLock<HashMap<&'static str, Properties>> = OnceLock::new();
+    let path = match id.split_once(':') {
+        Some(_) => id.strip_prefix(VANILLA_NAMESPACE)?,
+        None => id,
+    };
+    TABLE
+        .get_or_init(|| BLOCKS.iter().copied().collect())
+        .get(path)
+        .copied()
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vanilla_blocks_are_found_with_or_without_namespace() {
+        let log = properties("minecraft:oak_log").unwrap();
+        assert_eq!(log, properties("oak_log").unwrap());
+        assert_eq!(log, [("axis", ["x", "y", "z"].as_slice())]);
+        assert!(properties("mymod:oak_log").is_none());
+        assert!(properties("stone").is_none());
+    }
+
+    #[test]
+    fn ranges_expand_to_every_value() {
+        let wheat = properties("wheat").unwrap();
+        assert_eq!(wheat[0].1, ["0", "1", "2", "3", "4", "5", "6", "7"]);
+    }
+}
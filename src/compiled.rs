@@ -1,6 +1,23 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{Display, Write},
+};
+
 use itertools::Itertools;
+use log::{debug, trace};
+use serde::Serialize;
 
-use crate::statements::{Program, Statement};
+use crate::{
+    config::{Config, Retrigger, TargetVersion},
+    easing::Curve,
+    errors::{CompileError, CompileWarning, CompileWarningType},
+    objects::{
+        AngleUnit, Axis, Entity, Orientation, Position, Rotation, Scale, TagOperation, TextColor,
+        TextComponent, Translation,
+    },
+    statements::{self, Program, Statement, Vector},
+    verify,
+};
 
 #[allow(clippy::module_name_repetitions)]
 pub struct CompiledFile {
@@ -8,194 +25,1528 @@ pub struct CompiledFile {
     pub object_name: String,
     pub animation_name: String,
     pub contents: String,
+    /// The number of non-empty statements the file was compiled from.
+    pub statement_count: usize,
+    /// How many ticks the animation runs for before it resets.
+    pub length: u32,
+    pub warnings: Vec<CompileWarning>,
+    /// The namespace the file declared with `namespace`, if any.
+    pub namespace: Option<String>,
+    /// The output file name the file declared with `output`, if any.
+    pub output_name: Option<String>,
+    /// The interpolations running on each entity, by entity name, in source order.
+    pub timelines: Timelines,
+    /// Every event in the animation described on its own line, in tick order.
+    pub report: String,
+    /// The animation played backwards, when the file asks for it with `reverse`.
+    pub reverse: Option<Box<Self>>,
+    /// The animations defined by later `object` sections of the file, each compiled on its own.
+    pub sections: Vec<Self>,
+    /// How many commands of each kind the file compiled to, for `--stats`.
+    pub command_counts: CommandCounts,
+    /// The commands `verify` found malformed, each reported at the statement that generated it.
+    pub malformed: Vec<CompileError>,
+}
+
+pub type Timelines = BTreeMap<String, Vec<Interpolation>>;
+/// Command counts by the kind of statement they were generated for, in alphabetical order.
+pub type CommandCounts = BTreeMap<&'static str, usize>;
+
+/// The transformation an interpolation animates, or a `hold` keeping the entity still.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransformKind {
+    Translation,
+    Rotation,
+    Scale,
+    Hold,
+}
+impl TransformKind {
+    /// Interpolations of different kinds on the same entity are interpolated separately by the game,
+    /// so only those of the same kind can cut each other off. A hold conflicts with any interpolation.
+    fn conflicts_with(self, other: Self) -> bool {
+        match (self, other) {
+            (Self::Hold, Self::Hold) => false,
+            (Self::Hold, _) | (_, Self::Hold) => true,
+            _ => self == other,
+        }
+    }
+}
+impl Display for TransformKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Translation => "translation",
+            Self::Rotation => "rotation",
+            Self::Scale => "scale",
+            Self::Hold => "hold",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A transformation interpolating over `duration` ticks from `start`, compiled from the statement at `position`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Interpolation {
+    pub start: u32,
+    pub duration: u32,
+    pub kind: TransformKind,
+    pub position: Position,
+}
+impl Interpolation {
+    const fn end(&self) -> u32 {
+        self.start.saturating_add(self.duration)
+    }
+    const fn overlaps(&self, other: &Self) -> bool {
+        self.start < other.end() && other.start < self.end()
+    }
 }
-struct ProgramData {
+/// The state carried from one statement to the next while compiling a program.
+pub struct CompileContext {
+    /// The name of the source file, which the object and animation are named after by default.
+    file_name: String,
     object_name: String,
     animation_name: String,
     delay: u32,
+    namespace: Option<String>,
+    output_name: Option<String>,
+    /// The scoreboard objectives holding each animation's timer and whether it is playing.
+    timer_objective: String,
+    flags_objective: String,
+    /// The last tick a command is scheduled for, which can lie past `delay` when a rotation is split into keyframes,
+    /// or the last tick an entity is held until.
+    end: u32,
+    gated: bool,
+    /// The last text and color given to each entity, so a `color` can re-emit its current text.
+    texts: HashMap<String, (Option<TextComponent>, Option<TextColor>)>,
+    /// The last translation given to each entity, which a translation with per-axis durations starts from.
+    translations: HashMap<String, Translation>,
+    /// The last scale given to each entity, so a `mirror` can flip it.
+    scales: HashMap<String, Scale>,
+    /// The pivot each entity's rotations turn about when they don't give their own.
+    anchors: HashMap<String, Translation>,
+    /// The macro function repeated `data merge` commands are run through, when `macros` is enabled.
+    merge_macro: Option<String>,
+    /// The ticks a `waitfor` pauses the timer on, each with the condition that lets it carry on.
+    pauses: Vec<(u32, String)>,
+    /// The version whose syntax the commands are written in.
+    target_version: TargetVersion,
+    /// Whether the animation starts over when it ends, instead of stopping.
+    looping: bool,
 }
-impl ProgramData {
-    fn new(file_name: &str) -> Self {
+impl CompileContext {
+    fn new(file_name: &str, config: &Config) -> Self {
         Self {
+            file_name: file_name.to_string(),
             object_name: file_name.to_string(),
             animation_name: file_name.to_string(),
             delay: 0,
+            namespace: None,
+            output_name: None,
+            timer_objective: config.timer_objective.clone(),
+            flags_objective: config.flags_objective.clone(),
+            end: 0,
+            gated: config.single_file.is_some(),
+            texts: HashMap::new(),
+            translations: HashMap::new(),
+            scales: HashMap::new(),
+            anchors: HashMap::new(),
+            merge_macro: config.macros.then(|| {
+                crate::resource_location(
+                    &config.namespace,
+                    &merge_macro_path(config),
+                    &config.output_extension,
+                )
+            }),
+            pauses: Vec::new(),
+            target_version: config.target_version,
+            looping: false,
+        }
+    }
+    /// Applies a statement that only changes the state carried to later statements, like `wait`.
+    fn direct(&mut self, directive: &Statement) {
+        match directive {
+            Statement::ObjectName(object, animation) => {
+                self.object_name
+                    .clone_from(object.as_ref().unwrap_or(&self.file_name));
+                self.animation_name
+                    .clone_from(animation.as_ref().unwrap_or(&self.file_name));
+            }
+            Statement::Namespace(namespace) => self.namespace = Some(namespace.clone()),
+            Statement::Output(name) => self.output_name = Some(name.clone()),
+            Statement::Wait(duration) => self.delay += duration,
+            // The pause is on the next tick, so the statements before it only run once.
+            Statement::WaitFor(condition) => {
+                self.delay += 1;
+                self.pauses.push((self.delay, condition.clone()));
+            }
+            Statement::Loop(looping) => self.looping = *looping,
+            _ => {}
+        }
+    }
+    /// Guards `command` behind the animation's `flags` check when it will be merged into a single file,
+    /// since the merged file runs every tick instead of only while the animation is playing.
+    fn gate(&self, command: String) -> String {
+        if self.gated {
+            command
+                .lines()
+                .map(|line| {
+                    format!(
+                        "execute if score ${}-{} {} matches 1.. run {line}",
+                        self.object_name, self.animation_name, self.flags_objective
+                    )
+                })
+                .join("\n")
+        } else {
+            command
+        }
+    }
+    /// The tick the animation resets on, after its last wait and any keyframes scheduled past it.
+    fn length(&self) -> u32 {
+        self.delay.max(self.end)
+    }
+    /// A looping animation restarts its interpolations as soon as it resets, so it lasts until the last
+    /// of them finishes.
+    fn finish_loop(&mut self, timelines: &Timelines) {
+        if self.looping {
+            let last_end = timelines.values().flatten().map(Interpolation::end).max();
+            self.end = self.end.max(last_end.unwrap_or_default());
         }
     }
-    #[allow(clippy::needless_pass_by_value)]
     fn execute_string(&self, entity_name: &str, command: String) -> String {
+        self.execute_string_at(entity_name, self.delay, command)
+    }
+    #[allow(clippy::needless_pass_by_value)]
+    fn execute_string_at(&self, entity_name: &str, tick: u32, command: String) -> String {
         format!(
-            "execute as @e[tag={0},tag={entity_name}] if score ${0}-{1} timer matches {2} run {command}",
-            self.object_name, self.animation_name, self.delay
+            "execute as @e[tag={},tag={entity_name}] {} run {command}",
+            self.object_name,
+            self.timer_check(tick)
         )
     }
     #[allow(clippy::needless_pass_by_value)]
     fn execute_at_string(&self, entity_name: &str, command: String) -> String {
         format!(
-            "execute as @e[tag={0},tag={entity_name}] at @s if score ${0}-{1} timer matches {2} run {command}",
-            self.object_name, self.animation_name, self.delay
+            "execute as @e[tag={},tag={entity_name}] at @s {} run {command}",
+            self.object_name,
+            self.timer_check(self.delay)
         )
     }
+    /// Checks that the timer is on `tick`, and that a `waitfor` pausing on it has passed.
+    fn timer_check(&self, tick: u32) -> String {
+        let check = format!(
+            "if score ${}-{} {} matches {tick}",
+            self.object_name, self.animation_name, self.timer_objective
+        );
+        match self.pauses.iter().find(|(pause, _)| *pause == tick) {
+            Some((_, condition)) => format!("{check} if score {condition}"),
+            None => check,
+        }
+    }
 }
-pub fn program(program: Program, file_name: &str, file_path: &str) -> CompiledFile {
-    let mut data = ProgramData::new(file_name);
+#[must_use]
+pub fn program(
+    program: Program,
+    file_name: &str,
+    file_path: &str,
+    config: &Config,
+) -> CompiledFile {
+    let mut data = CompileContext::new(file_name, config);
+    let statement_count = program
+        .statements
+        .iter()
+        .filter(|&statement| *statement != Statement::Empty && !statement.is_comment())
+        .count();
+    // Only exact repeats of the previous command are dropped. Their tick is part of the command,
+    // so a command repeated on a later tick is kept.
+    let mut previous: Option<String> = None;
+    let mut timelines = Timelines::new();
+    let mut report = Vec::new();
+    let mut command_counts = CommandCounts::new();
+    let mut length_warnings = Vec::new();
+    let mut malformed = Vec::new();
+    let mut labelled_tick = None;
     let program_contents = program
         .statements
         .into_iter()
-        .filter_map(|statement| match statement {
-            Statement::ObjectName(object, animation) => {
-                data.object_name = object;
-                data.animation_name = animation;
-                None
+        .zip(program.sources)
+        .filter_map(|(statement, (position, source))| {
+            // A kept comment is copied as it is, since a gate would make it a command.
+            if statement.is_comment() {
+                return comment_line(config, &statement);
             }
-            Statement::Wait(duration) => {
-                data.delay += duration;
-                None
+            let (offset, statement) = statement.split_offset();
+            data.delay += offset;
+            let tick = data.delay;
+            let interpolation = statement.interpolation(data.delay, position);
+            if let Some(description) = statement.describe() {
+                report.push(format!("tick {}: {description}", data.delay));
             }
-            Statement::Empty => None,
-
-            Statement::Translate(entity, translation, duration) => {
-                let compiled_transformation = translation.compile();
-                Some(transformation(
-                    &data,
-                    entity.name(),
-                    duration,
-                    &compiled_transformation,
-                ))
+            let command = statement.compile(&mut data);
+            if statement != Statement::Empty {
+                trace!(
+                    "{file_path}:{}: '{source}' on tick {}: {}",
+                    position.line,
+                    data.delay,
+                    command.as_deref().unwrap_or("no command")
+                );
             }
-            Statement::Rotate(entity, rotation, duration) => Some(transformation(
-                &data,
-                entity.name(),
-                duration,
-                &rotation.compile(),
-            )),
-            Statement::Scale(entity, scale, duration) => Some(transformation(
-                &data,
-                entity.name(),
-                duration,
-                &scale.compile(),
-            )),
-            Statement::Spawn(source, entity_type, new) => {
-                Some(spawn(&data, &entity_type, new.name(), source.name()))
+            let gated = command.clone().map(|command| data.gate(command));
+            // An offset statement can run after the last wait, so the reset has to wait for it.
+            data.end = data.end.max(data.delay);
+            data.delay -= offset;
+            if command.is_some() && config.dedupe && previous == command {
+                debug!(
+                    "{file_path}:{}: '{source}' repeats the previous command, so it is dropped",
+                    position.line
+                );
+                return None;
             }
-            Statement::Item(entity, item_definition) => {
-                Some(item(&data, entity.name(), &item_definition))
+            // A hold compiles to no command, but still takes up its entity's timeline.
+            if let Some((entity, interpolation)) = interpolation {
+                timelines.entry(entity).or_default().push(interpolation);
             }
-            Statement::Block(entity, block_state) => {
-                Some(block(&data, entity.name(), &block_state.compile()))
+            previous = Some(command?);
+            let command = gated?;
+            if let Some(warning) = command_length_warning(file_path, position, &command, config) {
+                length_warnings.push(warning);
             }
-            Statement::Text(entity, text_string) => Some(text(&data, entity.name(), &text_string)),
-            Statement::Teleport(entity, x, y, z) => Some(teleport(&data, entity.name(), x, y, z)),
-            Statement::Raw(command, delayed) => Some(raw(&data, &command, delayed)),
+            if config.verify {
+                malformed.extend(verify::check(file_path, position, &source, &command));
+            }
+            *command_counts.entry(statement.category()).or_default() += command.lines().count();
+            Some(annotated(
+                config,
+                command,
+                (position, &source),
+                tick,
+                &mut labelled_tick,
+            ))
         })
         .join("\n");
 
+    data.finish_loop(&timelines);
+    let reset = reset(&data);
+    let increment = increment(&data);
+    for (category, lines) in [("reset", &reset), ("increment", &increment)] {
+        *command_counts.entry(category).or_default() +=
+            lines.lines().filter(|line| !line.is_empty()).count();
+    }
+    let program_contents = factor_merges(&data, program_contents);
+    let contents = file_contents(config, &program_contents, &reset, &increment);
+
     CompiledFile {
         path: file_path.to_string(),
-        object_name: data.object_name.to_string(),
-        animation_name: data.animation_name.to_string(),
-        contents: format!(
-            "{}\n{}\n{}\n{}",
-            disclaimer(),
-            program_contents,
-            reset(&data),
-            increment(&data),
+        length: data.length(),
+        object_name: data.object_name,
+        animation_name: data.animation_name,
+        contents,
+        statement_count,
+        warnings: program
+            .warnings
+            .into_iter()
+            .chain(overlap_warnings(file_path, &timelines))
+            .chain(order_warnings(file_path, &timelines))
+            .chain(length_warnings)
+            .collect(),
+        namespace: data.namespace,
+        output_name: data.output_name,
+        timelines,
+        report: report.join("\n"),
+        reverse: None,
+        sections: Vec::new(),
+        command_counts,
+        malformed,
+    }
+}
+
+/// Puts the config's comments about a statement before its command: its source line when verbose,
+/// and the tick it starts on when pretty and it is the first statement on that tick.
+fn annotated(
+    config: &Config,
+    command: String,
+    (position, source): (Position, &str),
+    tick: u32,
+    labelled_tick: &mut Option<u32>,
+) -> String {
+    let command = if config.verbose {
+        format!("# {}: {source}\n{command}", position.line)
+    } else {
+        command
+    };
+    // A statement's keyframes on later ticks stay under the tick the statement starts on.
+    if config.pretty && *labelled_tick != Some(tick) {
+        let gap = if labelled_tick.is_some() { "\n" } else { "" };
+        *labelled_tick = Some(tick);
+        return format!("{gap}# tick {tick}\n{command}");
+    }
+    command
+}
+
+/// Puts the animation's commands before the lines that reset and advance its timer, laid out as the
+/// config asks.
+fn file_contents(config: &Config, commands: &str, reset: &str, increment: &str) -> String {
+    if config.minify {
+        [commands, reset, increment]
+            .iter()
+            .flat_map(|part| part.lines())
+            .filter(|line| !line.is_empty())
+            .join("\n")
+    } else if config.pretty {
+        format!(
+            "{commands}\n\n# reset once the animation ends\n{}\n\n# advance the timer\n{increment}",
+            reset.trim()
+        )
+    } else {
+        format!("{commands}\n{reset}\n{increment}")
+    }
+}
+
+/// Warns about a statement whose longest command is over the configured `max_command_length`.
+fn command_length_warning(
+    file_path: &str,
+    position: Position,
+    command: &str,
+    config: &Config,
+) -> Option<CompileWarning> {
+    let max_length = config.max_command_length?;
+    let length = command
+        .lines()
+        .map(str::len)
+        .max()
+        .filter(|&length| length > max_length)?;
+    Some(CompileWarning::in_file(
+        file_path,
+        position,
+        CompileWarningType::CommandTooLong(length, max_length),
+    ))
+}
+
+/// The line a `#!` comment is copied into the output as, or a region marker when the config keeps them.
+fn comment_line(config: &Config, comment: &Statement) -> Option<String> {
+    match comment {
+        Statement::Comment(comment) if comment.is_empty() => Some("#".to_string()),
+        Statement::Comment(comment) => Some(format!("# {comment}")),
+        Statement::Region(name) if config.keep_regions => {
+            Some(format!("#region {name}").trim_end().to_string())
+        }
+        Statement::EndRegion if config.keep_regions => Some("#endregion".to_string()),
+        _ => None,
+    }
+}
+
+/// Warns about every interpolation that starts while an earlier conflicting one on the same entity
+/// is still running, since the game restarts the interpolation from wherever the earlier one had got to.
+fn overlap_warnings(file_path: &str, timelines: &Timelines) -> Vec<CompileWarning> {
+    timelines
+        .iter()
+        .flat_map(|(entity, interpolations)| {
+            interpolations
+                .iter()
+                .enumerate()
+                .filter_map(move |(index, later)| {
+                    let earlier = interpolations[..index].iter().find(|earlier| {
+                        earlier.kind.conflicts_with(later.kind) && earlier.overlaps(later)
+                    })?;
+                    Some(CompileWarning::in_file(
+                        file_path,
+                        later.position,
+                        CompileWarningType::OverlappingInterpolation(
+                            later.kind,
+                            entity,
+                            earlier.kind,
+                            earlier.position,
+                        ),
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// Warns about every interpolation that a `+<ticks>` offset or `start` makes finish before an earlier
+/// conflicting one in the file has even started, so the entity plays them in the opposite order to the
+/// file's. Interpolations that overlap are already warned about, and other entities are never compared.
+fn order_warnings(file_path: &str, timelines: &Timelines) -> Vec<CompileWarning> {
+    timelines
+        .iter()
+        .flat_map(|(entity, interpolations)| {
+            interpolations
+                .iter()
+                .enumerate()
+                .filter_map(move |(index, later)| {
+                    // An instant change on the same tick as the earlier one still plays in file order.
+                    let earlier = interpolations[..index].iter().find(|earlier| {
+                        earlier.kind.conflicts_with(later.kind)
+                            && later.end() <= earlier.start
+                            && later.start < earlier.start
+                    })?;
+                    Some(CompileWarning::in_file(
+                        file_path,
+                        later.position,
+                        CompileWarningType::OutOfOrderInterpolation(
+                            later.kind,
+                            entity,
+                            earlier.kind,
+                            earlier.position,
+                        ),
+                    ))
+                })
+        })
+        .collect()
+}
+
+impl Statement {
+    /// The entity this statement interpolates and when, if it is a transformation.
+    /// `delay` is the tick the statement is reached on, before any `start` it waits for.
+    fn interpolation(&self, delay: u32, position: Position) -> Option<(String, Interpolation)> {
+        let (entity, kind, duration, start) = match self {
+            Self::Translate(entity, _, duration, start) => {
+                (entity, TransformKind::Translation, *duration, *start)
+            }
+            Self::Rotate(entity, _, duration, start) => {
+                (entity, TransformKind::Rotation, *duration, *start)
+            }
+            Self::Scale(entity, _, duration, start) => {
+                (entity, TransformKind::Scale, *duration, *start)
+            }
+            Self::Hold(entity, ticks) => (entity, TransformKind::Hold, *ticks, 0),
+            Self::AxisDurations(_, statement) | Self::Eased(_, statement) => {
+                return statement.interpolation(delay, position)
+            }
+            _ => return None,
+        };
+        let interpolation = Interpolation {
+            start: delay.saturating_add(start),
+            duration,
+            kind,
+            position,
+        };
+        Some((entity.name().to_string(), interpolation))
+    }
+
+    /// The kind of command the statement compiles to, for `--stats`.
+    fn category(&self) -> &'static str {
+        match self {
+            Self::Translate(..) | Self::Rotate(..) | Self::Scale(..) | Self::Mirror(..) => {
+                "transform"
+            }
+            Self::Spawn(..) => "spawn",
+            Self::Item(..) => "item",
+            Self::Block(..) => "block",
+            Self::Text(..) | Self::Color(..) => "text",
+            Self::Teleport(..) => "teleport",
+            Self::Tag(..) => "tag",
+            Self::Raw(..) => "raw",
+            Self::Offset(_, statement)
+            | Self::AxisDurations(_, statement)
+            | Self::Eased(_, statement) => statement.category(),
+            Self::Frames(_, frames) => frames.first().map_or("other", Self::category),
+            Self::ObjectName(..)
+            | Self::Namespace(_)
+            | Self::Output(_)
+            | Self::Wait(_)
+            | Self::WaitFor(_)
+            | Self::Hold(..)
+            | Self::Anchor(..)
+            | Self::Entity(_)
+            | Self::With(_)
+            | Self::Reverse
+            | Self::Loop(_)
+            | Self::Comment(_)
+            | Self::Region(_)
+            | Self::EndRegion
+            | Self::Empty => "other",
+        }
+    }
+
+    /// Describes what the statement does, for the timeline report. Statements that only change the
+    /// compiler's state, like `wait`, have nothing to describe.
+    pub fn describe(&self) -> Option<String> {
+        let description = match self {
+            Self::ObjectName(..)
+            | Self::Namespace(_)
+            | Self::Output(_)
+            | Self::Wait(_)
+            | Self::Entity(_)
+            | Self::With(_)
+            | Self::Reverse
+            | Self::Loop(_)
+            | Self::Comment(_)
+            | Self::Region(_)
+            | Self::EndRegion
+            | Self::Empty => return None,
+            Self::Translate(entity, translation, duration, start) => format!(
+                "translate {} -> ({}, {}, {}){}",
+                entity.name(),
+                translation.x,
+                translation.y,
+                translation.z,
+                describe_timing(*duration, *start)
+            ),
+            Self::Rotate(entity, rotation, duration, start) => {
+                let orientation = match rotation.orientation {
+                    Orientation::AxisAngle { axis, angle, unit } => {
+                        let unit = match unit {
+                            AngleUnit::Degrees => "deg",
+                            AngleUnit::Radians => "rad",
+                        };
+                        format!(
+                            "by {angle}{unit} about [{}, {}, {}]",
+                            axis[0], axis[1], axis[2]
+                        )
+                    }
+                    Orientation::Quaternion([x, y, z, w]) => format!("to [{x}, {y}, {z}, {w}]"),
+                };
+                let pivot = rotation.pivot.map_or_else(String::new, |pivot| {
+                    format!(" around ({}, {}, {})", pivot.x, pivot.y, pivot.z)
+                });
+                format!(
+                    "rotate {} {orientation}{pivot}{}",
+                    entity.name(),
+                    describe_timing(*duration, *start)
+                )
+            }
+            Self::Scale(entity, scale, duration, start) => format!(
+                "scale {} -> ({}, {}, {}){}",
+                entity.name(),
+                scale.x,
+                scale.y,
+                scale.z,
+                describe_timing(*duration, *start)
+            ),
+            Self::Spawn(source, entity_type, new, offset, mounted) => {
+                let placement = match (offset, mounted) {
+                    (_, true) => " mounted".to_string(),
+                    (Some((x, y, z)), false) => format!(" at ({x}, {y}, {z})"),
+                    (None, false) => String::new(),
+                };
+                format!(
+                    "spawn {entity_type} {} from {}{placement}",
+                    new.name(),
+                    source.name()
+                )
+            }
+            Self::Item(entity, item) => format!("item {} -> {item}", entity.name()),
+            Self::Block(entity, block_state) => format!("block {} -> {block_state}", entity.name()),
+            Self::Text(entity, text) => format!("text {} -> {}", entity.name(), text.compile()),
+            Self::Teleport(entity, x, y, z) => {
+                format!("teleport {} -> ({x}, {y}, {z})", entity.name())
+            }
+            Self::Tag(entity, operation, tag) => {
+                format!("tag {} {} {tag}", entity.name(), operation.compile())
+            }
+            Self::Color(entity, color) => format!("color {} -> {}", entity.name(), color.compile()),
+            Self::Mirror(entity, axis) => format!("mirror {} along {axis}", entity.name()),
+            Self::Raw(command, _) => format!("command {command}"),
+            Self::Hold(entity, ticks) => format!("hold {} for {ticks}t", entity.name()),
+            Self::WaitFor(condition) => format!("wait for {condition}"),
+            Self::Anchor(entity, anchor) => format!(
+                "anchor {} at ({}, {}, {})",
+                entity.name(),
+                anchor.x,
+                anchor.y,
+                anchor.z
+            ),
+            Self::Offset(_, statement) => return statement.describe(),
+            Self::AxisDurations([x, y, z], statement) => {
+                format!("{}, {x}t/{y}t/{z}t per axis", statement.describe()?)
+            }
+            Self::Eased(easing, statement) => {
+                format!("{}, eased with {}", statement.describe()?, easing.name)
+            }
+            Self::Frames(interval, frames) => format!(
+                "{}, then {} more every {interval}t",
+                frames.first()?.describe()?,
+                frames.len() - 1
+            ),
+        };
+        Some(description)
+    }
+
+    /// Compiles this statement, updating `data` with any state it carries to later statements.
+    /// Statements that only change that state, like `wait`, compile to no command.
+    pub fn compile(&self, data: &mut CompileContext) -> Option<String> {
+        match self {
+            Self::ObjectName(..)
+            | Self::Namespace(_)
+            | Self::Output(_)
+            | Self::Wait(_)
+            | Self::WaitFor(_)
+            | Self::Loop(_) => {
+                data.direct(self);
+                None
+            }
+            // Comments are copied by `program` instead, ungated.
+            Self::Entity(_)
+            | Self::With(_)
+            | Self::Reverse
+            | Self::Comment(_)
+            | Self::Region(_)
+            | Self::EndRegion
+            | Self::Empty => None,
+            Self::Translate(entity, translation, duration, start) => {
+                data.translations
+                    .insert(entity.name().to_string(), *translation);
+                Some(transformation(
+                    data,
+                    &entity.selector(),
+                    *duration,
+                    *start,
+                    &translation.compile(),
+                ))
+            }
+            Self::Rotate(entity, rotation, duration, start) => {
+                let rotation = Rotation {
+                    pivot: rotation
+                        .pivot
+                        .or_else(|| data.anchors.get(entity.name()).copied()),
+                    ..*rotation
+                };
+                Some(rotate(
+                    data,
+                    &entity.selector(),
+                    &rotation,
+                    *duration,
+                    *start,
+                ))
+            }
+            Self::Anchor(entity, anchor) => {
+                data.anchors.insert(entity.name().to_string(), *anchor);
+                None
+            }
+            // `program` adds the offset to the delay before compiling, since the command is gated on it.
+            Self::Offset(_, statement) => statement.compile(data),
+            Self::AxisDurations(durations, statement) => {
+                axis_transformation(data, statement, *durations)
+            }
+            Self::Eased(easing, statement) => eased_transformation(data, statement, &easing.curve),
+            Self::Frames(interval, frames) => Some(frames_statement(data, *interval, frames)),
+            Self::Scale(entity, scale, duration, start) => {
+                data.scales.insert(entity.name().to_string(), *scale);
+                Some(transformation(
+                    data,
+                    &entity.selector(),
+                    *duration,
+                    *start,
+                    &scale.compile(),
+                ))
+            }
+            Self::Mirror(entity, axis) => Some(mirror(data, entity, *axis)),
+            Self::Spawn(source, entity_type, new, offset, mounted) => {
+                let summon = spawn(data, entity_type, new.name(), &source.selector(), *offset);
+                Some(if *mounted {
+                    format!("{summon}\n{}", mount(data, new.name(), &source.selector()))
+                } else {
+                    summon
+                })
+            }
+            Self::Item(entity, definition) => Some(item(data, &entity.selector(), definition)),
+            Self::Block(entity, block_state) => {
+                Some(block(data, &entity.selector(), &block_state.compile()))
+            }
+            Self::Text(entity, text_component) => {
+                Some(text_statement(data, entity, text_component))
+            }
+            Self::Color(entity, color) => color_statement(data, entity, color),
+            Self::Teleport(entity, x, y, z) => Some(teleport(data, &entity.selector(), *x, *y, *z)),
+            Self::Tag(entity, operation, name) => {
+                Some(tag(data, &entity.selector(), *operation, name))
+            }
+            Self::Raw(command, delayed) => Some(raw(data, command, *delayed)),
+            Self::Hold(_, ticks) => {
+                data.end = data.end.max(data.delay.saturating_add(*ticks));
+                None
+            }
+        }
+    }
+}
+
+/// The crate version, substituted for `{version}` in the header.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// The commit this was built from, or `unknown` when it wasn't built from a git checkout.
+pub const GIT_HASH: &str = env!("DISPA_GIT_HASH");
+
+fn describe_timing(duration: u32, start: u32) -> String {
+    if start == 0 {
+        format!(" over {duration}t")
+    } else {
+        format!(" over {duration}t, starting {start}t later")
+    }
+}
+
+/// Puts the configured header at the top of a generated file as a comment.
+/// An unset or empty header, or minified output, leaves `contents` as it is.
+#[must_use]
+pub fn with_header(config: &Config, contents: &str) -> String {
+    match config.header.as_deref() {
+        Some(header) if !header.is_empty() && !config.minify => {
+            let header = header
+                .replace("{version}", VERSION)
+                .lines()
+                .map(|line| format!("# {line}"))
+                .join("\n");
+            format!("{header}\n{contents}")
+        }
+        _ => contents.to_string(),
+    }
+}
+
+/// The first line of the header, up to its `{version}`, which marks a file as generated by any version.
+/// There is no marker when there is no header to tell generated files apart by.
+#[must_use]
+pub fn generated_marker(config: &Config) -> Option<String> {
+    let first = config.header.as_deref()?.lines().next()?;
+    let marker = first.split("{version}").next().unwrap_or(first).trim_end();
+    (!marker.is_empty()).then(|| format!("# {marker}"))
+}
+
+/// The `_start` function of an animation, which restarts its timer and sets its flags.
+///
+/// When retriggering is blocked, the timer is only reset while the animation isn't playing. It is set
+/// before `flags`, since setting `flags` first would make the animation look like it was already playing.
+#[must_use]
+pub fn start_function(config: &Config, object_name: &str, animation_name: &str) -> String {
+    let Config {
+        timer_objective,
+        flags_objective,
+        ..
+    } = config;
+    let start = match config.retrigger {
+        Retrigger::Restart => format!(
+            "scoreboard players set ${object_name}-{animation_name} {flags_objective} 1\n\
+            scoreboard players set ${object_name}-{animation_name} {timer_objective} 0"
         ),
+        Retrigger::Block => format!(
+            "execute unless score ${object_name}-{animation_name} {flags_objective} matches 1.. run scoreboard players set ${object_name}-{animation_name} {timer_objective} 0\n\
+            scoreboard players set ${object_name}-{animation_name} {flags_objective} 1"
+        ),
+    };
+    if config.group_tick_function {
+        format!("{start}\nscoreboard players set #{object_name} {flags_objective} 1")
+    } else {
+        start
     }
 }
 
-pub fn disclaimer() -> String {
-    "# File generated using DiSPA".to_string()
+#[must_use]
+pub fn stop_function(config: &Config, object_name: &str, animation_name: &str) -> String {
+    let Config {
+        timer_objective,
+        flags_objective,
+        ..
+    } = config;
+    format!(
+        "# Stops playback only; display entities keep their current transformation.\n\
+        scoreboard players set ${object_name}-{animation_name} {flags_objective} 0\n\
+        scoreboard players set ${object_name}-{animation_name} {timer_objective} -1"
+    )
 }
 
+#[must_use]
 pub fn tick_function_line(
+    config: &Config,
     object_name: &str,
     animation_name: &str,
-    namespace: &str,
-    path: &str,
+    location: &str,
+) -> String {
+    let flags_objective = &config.flags_objective;
+    format!("execute if score ${object_name}-{animation_name} {flags_objective} matches 1.. run function {location}")
+}
+
+/// Runs an object's dispatch function while the object is marked as playing, when tick lines are grouped.
+/// The `#` holder can't collide with an animation's, which always start with `$`.
+#[must_use]
+pub fn object_tick_line(config: &Config, object_name: &str, location: &str) -> String {
+    let flags_objective = &config.flags_objective;
+    format!("execute if score #{object_name} {flags_objective} matches 1.. run function {location}")
+}
+
+/// Ends an object's dispatch function by unmarking the object once none of its animations are playing,
+/// so the tick function stops calling it.
+pub fn object_dispatch_end<'a>(
+    config: &Config,
+    object_name: &str,
+    animation_names: impl IntoIterator<Item = &'a str>,
 ) -> String {
-    format!("execute if score ${object_name}-{animation_name} flags matches 1.. run function {namespace}:{path}")
+    let flags_objective = &config.flags_objective;
+    let conditions = animation_names
+        .into_iter()
+        .fold(String::new(), |mut acc, animation_name| {
+            let _ = write!(
+                acc,
+                "unless score ${object_name}-{animation_name} {flags_objective} matches 1.. "
+            );
+            acc
+        });
+    format!("execute {conditions}run scoreboard players set #{object_name} {flags_objective} 0")
+}
+
+const MERGE_COMMAND_PREFIX: &str = "data merge entity @s ";
+
+/// How many `data merge` commands an entity needs before they are run through the merge macro. Below
+/// this, the macro call costs more than repeating the selector saves.
+const MACRO_REPEATS: usize = 3;
+
+/// A `data merge` line of the animation, split into the parts the merge macro is called with.
+struct MergeLine<'a> {
+    /// Whatever runs the line, like the `flags` check of a merged file.
+    gate: &'a str,
+    /// The entity as it goes in a selector, with any predicate.
+    entity_name: &'a str,
+    /// The timer check, after `matches`.
+    check: &'a str,
+    nbt: &'a str,
+}
+impl<'a> MergeLine<'a> {
+    /// Splits a line `execute_string_at` wrote, which `gate` may have wrapped. Any other line is `None`.
+    fn parse(data: &CompileContext, line: &'a str) -> Option<Self> {
+        let selector = format!("execute as @e[tag={},tag=", data.object_name);
+        let gate = format!(
+            "execute if score ${}-{} {} matches 1.. run ",
+            data.object_name, data.animation_name, data.flags_objective
+        );
+        let (gate, rest) = line
+            .strip_prefix(&gate)
+            .map_or(("", line), |rest| (&line[..gate.len()], rest));
+        let rest = rest.strip_prefix(&selector)?;
+        let timer = format!(
+            "] if score ${}-{} {} matches ",
+            data.object_name, data.animation_name, data.timer_objective
+        );
+        let (entity_name, rest) = rest.split_once(&timer)?;
+        let (check, nbt) = rest.split_once(&format!(" run {MERGE_COMMAND_PREFIX}"))?;
+        Some(Self {
+            gate,
+            entity_name,
+            check,
+            nbt,
+        })
+    }
+}
+
+/// Runs the `data merge` commands of every entity with at least `MACRO_REPEATS` of them through the
+/// merge macro, so their selector is only written once, in the macro. The score is still checked
+/// first, so the macro is only called on the tick the merge runs.
+fn factor_merges(data: &CompileContext, contents: String) -> String {
+    let Some(merge_macro) = &data.merge_macro else {
+        return contents;
+    };
+    let counts = contents
+        .lines()
+        .filter_map(|line| MergeLine::parse(data, line))
+        .map(|merge| merge.entity_name)
+        .counts();
+    contents
+        .lines()
+        .map(|line| match MergeLine::parse(data, line) {
+            Some(merge) if counts[merge.entity_name] >= MACRO_REPEATS => {
+                // A predicate can quote its values, so the selector is escaped to stay one SNBT string.
+                let entity_name = merge
+                    .entity_name
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"");
+                format!(
+                    "{}execute if score ${}-{} {} matches {} run function {merge_macro} {{object:\"{}\",entity:\"{entity_name}\",nbt:{}}}",
+                    merge.gate,
+                    data.object_name,
+                    data.animation_name,
+                    data.timer_objective,
+                    merge.check,
+                    data.object_name,
+                    merge.nbt
+                )
+            }
+            _ => line.to_string(),
+        })
+        .join("\n")
+}
+
+/// Where the shared `data merge` macro function is written, next to the tick function.
+#[must_use]
+pub fn merge_macro_path(config: &Config) -> String {
+    crate::companion_path(&config.tick_function, "merge", &config.output_extension)
+}
+
+/// The macro function repeated `data merge` commands are run through when `macros` is enabled. Its
+/// `nbt` argument is a compound, which the macro substitutes as SNBT.
+#[must_use]
+pub fn merge_macro() -> String {
+    format!("$execute as @e[tag=$(object),tag=$(entity)] run {MERGE_COMMAND_PREFIX}$(nbt)")
+}
+
+/// Creates the objectives every animation is timed with. Adding an objective that already exists only
+/// fails that one command, so the function is safe to run on every load.
+#[must_use]
+pub fn load_function(config: &Config) -> String {
+    format!(
+        "scoreboard objectives add {} dummy\n\
+        scoreboard objectives add {} dummy",
+        config.timer_objective, config.flags_objective
+    )
+}
+
+/// The merged function gates each command on its own animation's `flags`, so it runs unconditionally.
+#[must_use]
+pub fn single_file_tick_line(location: &str) -> String {
+    format!("function {location}")
 }
 
-fn increment(data: &ProgramData) -> String {
+/// A `waitfor` holds the timer on its tick, so the timer only moves past it once its condition passes.
+/// Interpolations started before the pause keep playing, since the game times them on its own. Both
+/// increments check the timer as it was before either ran, so reaching a pause whose condition already
+/// passes doesn't also step past it on the same tick, skipping the commands scheduled on it.
+fn increment(data: &CompileContext) -> String {
     let object_name = &data.object_name;
     let animation_name = &data.animation_name;
-    format!("scoreboard players add ${object_name}-{animation_name} timer 1")
+    let timer_objective = &data.timer_objective;
+    let timer = format!("${object_name}-{animation_name} {timer_objective}");
+    let add = format!("scoreboard players add {timer} 1");
+    if data.pauses.is_empty() {
+        return data.gate(add);
+    }
+    let previous = format!("${object_name}-{animation_name}.previous {timer_objective}");
+    let unpaused = data
+        .pauses
+        .iter()
+        .map(|(tick, _)| format!("unless score {previous} matches {tick} "))
+        .join("");
+    let resumed = data.pauses.iter().map(|(tick, condition)| {
+        format!("execute if score {previous} matches {tick} if score {condition} run {add}")
+    });
+    data.gate(
+        [
+            format!("scoreboard players operation {previous} = {timer}"),
+            format!("execute {unpaused}run {add}"),
+        ]
+        .into_iter()
+        .chain(resumed)
+        .join("\n"),
+    )
 }
 
-fn reset(data: &ProgramData) -> String {
-    let ProgramData {
+/// The reset lines are never gated: they only match once the timer has passed the end of the animation,
+/// and the timer must still be cleared after the first line has already unset `flags`. A looping
+/// animation keeps `flags` set, so clearing the timer starts it over on the next tick.
+fn reset(data: &CompileContext) -> String {
+    let CompileContext {
         object_name,
         animation_name,
-        delay,
+        timer_objective,
+        flags_objective,
+        ..
     } = data;
+    let delay = data.length();
+    let stop = if data.looping {
+        String::new()
+    } else {
+        format!("execute if score ${object_name}-{animation_name} {timer_objective} matches {delay}.. run scoreboard players set ${object_name}-{animation_name} {flags_objective} 0\n")
+    };
     format!(
         "\n\
-        execute if score ${object_name}-{animation_name} timer matches {delay}.. run scoreboard players set ${object_name}-{animation_name} flags 0\n\
-        execute if score ${object_name}-{animation_name} timer matches {delay}.. run scoreboard players set ${object_name}-{animation_name} timer -1\n\
+        {stop}\
+        execute if score ${object_name}-{animation_name} {timer_objective} matches {delay}.. run scoreboard players set ${object_name}-{animation_name} {timer_objective} -1\n\
         "
     )
 }
 
-fn raw(data: &ProgramData, command: &str, delayed: bool) -> String {
+fn raw(data: &CompileContext, command: &str, delayed: bool) -> String {
     if delayed {
-        format!(
-            "execute if score ${0}-{1} timer matches {2} run {3}",
-            data.object_name, data.animation_name, data.delay, command
-        )
+        format!("execute {} run {command}", data.timer_check(data.delay))
     } else {
         command.to_string()
     }
 }
 
 fn transformation(
-    data: &ProgramData,
+    data: &CompileContext,
     entity_name: &str,
     duration: u32,
+    start: u32,
     transformation: &str,
 ) -> String {
     data.execute_string(
         entity_name,
-        format!("data merge entity @s {{start_interpolation:0,interpolation_duration:{duration},transformation:{{{transformation}}}}}")
+        transformation_command(duration, start, transformation),
     )
 }
 
+fn transformation_command(duration: u32, start: u32, transformation: &str) -> String {
+    format!("data merge entity @s {{start_interpolation:{start},interpolation_duration:{duration},transformation:{{{transformation}}}}}")
+}
+
+/// Spreads the rotation's keyframes evenly across `duration`, each interpolating over its own share of it.
+/// `interpolation_start` shifts every keyframe by that many ticks rather than delaying each one's
+/// interpolation, which would make each keyframe overlap the next.
+fn rotate(
+    data: &mut CompileContext,
+    entity_name: &str,
+    rotation: &Rotation,
+    duration: u32,
+    interpolation_start: u32,
+) -> String {
+    let keyframes = rotation.keyframes();
+    let steps = u32::try_from(keyframes.len()).unwrap_or(u32::MAX);
+    keyframes
+        .iter()
+        .zip(0..)
+        .map(|(keyframe, step)| {
+            let start = duration * step / steps;
+            let end = duration * (step + 1) / steps;
+            let tick = data.delay + interpolation_start + start;
+            data.end = data.end.max(tick);
+            data.execute_string_at(
+                entity_name,
+                tick,
+                transformation_command(end - start, 0, &keyframe.compile()),
+            )
+        })
+        .join("\n")
+}
+
+/// Where a translation or scale takes its entity from and to, and how to write a point between them.
+struct Endpoints {
+    /// The entity as it goes in a selector, with any predicate.
+    entity_name: String,
+    from: Vector,
+    to: Vector,
+    duration: u32,
+    interpolation_start: u32,
+    compile: fn(Vector) -> String,
+}
+
+/// Reads the endpoints of a translation or scale, starting from the entity's last translation or scale
+/// and recording this one as its latest. Returns `None` for any other statement.
+fn endpoints(data: &mut CompileContext, statement: &Statement) -> Option<Endpoints> {
+    match statement {
+        Statement::Translate(entity, translation, duration, start) => {
+            let from = data
+                .translations
+                .insert(entity.name().to_string(), *translation)
+                .unwrap_or(Translation::new((0.0, 0.0, 0.0)));
+            Some(Endpoints {
+                entity_name: entity.selector(),
+                from: (from.x, from.y, from.z),
+                to: (translation.x, translation.y, translation.z),
+                duration: *duration,
+                interpolation_start: *start,
+                compile: |point| Translation::new(point).compile(),
+            })
+        }
+        Statement::Scale(entity, scale, duration, start) => {
+            let from = data
+                .scales
+                .insert(entity.name().to_string(), *scale)
+                .unwrap_or(Scale::IDENTITY);
+            Some(Endpoints {
+                entity_name: entity.selector(),
+                from: (from.x, from.y, from.z),
+                to: (scale.x, scale.y, scale.z),
+                duration: *duration,
+                interpolation_start: *start,
+                compile: |point| Scale::new(point).compile(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Compiles a translation or scale whose axes each have their own duration, starting from the entity's
+/// last translation or scale.
+fn axis_transformation(
+    data: &mut CompileContext,
+    statement: &Statement,
+    durations: [u32; 3],
+) -> Option<String> {
+    let Some(endpoints) = endpoints(data, statement) else {
+        return statement.compile(data);
+    };
+    let axes = AxisKeyframes {
+        from: endpoints.from,
+        to: endpoints.to,
+        durations,
+    };
+    Some(axis_keyframes(
+        data,
+        &endpoints.entity_name,
+        &axes,
+        endpoints.interpolation_start,
+        endpoints.compile,
+    ))
+}
+
+/// How many keyframes an eased transformation is split into, at most one a tick.
+const EASING_KEYFRAMES: u32 = 10;
+
+/// Follows the curve with a chain of linear keyframes spread evenly across the duration, each taking
+/// the entity to where the curve is by the next one. A `start` shifts every keyframe, as it does for a
+/// rotation's.
+#[allow(clippy::cast_precision_loss)]
+fn eased_transformation(
+    data: &mut CompileContext,
+    statement: &Statement,
+    curve: &Curve,
+) -> Option<String> {
+    let Some(endpoints) = endpoints(data, statement) else {
+        return statement.compile(data);
+    };
+    let duration = endpoints.duration;
+    let steps = duration.clamp(1, EASING_KEYFRAMES);
+    let (from, to) = (endpoints.from, endpoints.to);
+    let command = (1..=steps)
+        .map(|step| {
+            let start = duration * (step - 1) / steps;
+            let end = duration * step / steps;
+            let progress = if duration == 0 {
+                1.0
+            } else {
+                curve.at(end as f32 / duration as f32)
+            };
+            let point = (
+                (to.0 - from.0).mul_add(progress, from.0),
+                (to.1 - from.1).mul_add(progress, from.1),
+                (to.2 - from.2).mul_add(progress, from.2),
+            );
+            let tick = data.delay + endpoints.interpolation_start + start;
+            data.end = data.end.max(tick);
+            data.execute_string_at(
+                &endpoints.entity_name,
+                tick,
+                transformation_command(end - start, 0, &(endpoints.compile)(point)),
+            )
+        })
+        .join("\n");
+    Some(command)
+}
+
+/// A transformation from one value to another, where each axis takes its own number of ticks.
+struct AxisKeyframes {
+    from: Vector,
+    to: Vector,
+    durations: [u32; 3],
+}
+impl AxisKeyframes {
+    /// Where every axis is `tick` ticks in, with the ones that have finished at their target.
+    #[allow(clippy::cast_precision_loss)]
+    fn at(&self, tick: u32) -> Vector {
+        let axis = |from: f32, to: f32, duration: u32| {
+            if tick >= duration {
+                to
+            } else {
+                (to - from).mul_add(tick as f32 / duration as f32, from)
+            }
+        };
+        let [x, y, z] = self.durations;
+        (
+            axis(self.from.0, self.to.0, x),
+            axis(self.from.1, self.to.1, y),
+            axis(self.from.2, self.to.2, z),
+        )
+    }
+}
+
+/// An entity only has one interpolation duration, so a keyframe starts each time an axis finishes,
+/// taking every axis to where it is by the next one. Interpolation is linear, so this plays out the
+/// same as a command on every tick. Axes with no duration reach their target with the first keyframe.
+/// `interpolation_start` shifts every keyframe, as it does for a rotation's.
+fn axis_keyframes(
+    data: &mut CompileContext,
+    entity_name: &str,
+    axes: &AxisKeyframes,
+    interpolation_start: u32,
+    compile: fn(Vector) -> String,
+) -> String {
+    let mut ends: Vec<u32> = axes
+        .durations
+        .into_iter()
+        .filter(|&duration| duration > 0)
+        .collect();
+    ends.sort_unstable();
+    ends.dedup();
+    let mut start = 0;
+    ends.into_iter()
+        .map(|end| {
+            let tick = data.delay + interpolation_start + start;
+            data.end = data.end.max(tick);
+            let command = data.execute_string_at(
+                entity_name,
+                tick,
+                transformation_command(end - start, 0, &compile(axes.at(end))),
+            );
+            start = end;
+            command
+        })
+        .join("\n")
+}
+
+/// Compiles each frame `interval` ticks after the one before, keeping the animation running until the last.
+fn frames_statement(data: &mut CompileContext, interval: u32, frames: &[Statement]) -> String {
+    let delay = data.delay;
+    let commands = frames
+        .iter()
+        .zip(0..)
+        .filter_map(|(frame, step)| {
+            data.delay = delay + interval * step;
+            frame.compile(data)
+        })
+        .join("\n");
+    data.delay = delay;
+    data.end = data
+        .end
+        .max(delay + statements::frames_length(interval, frames));
+    commands
+}
+
+/// Flips the entity's last known scale along `axis`, snapping instead of interpolating.
+fn mirror(data: &mut CompileContext, entity: &Entity, axis: Axis) -> String {
+    let scale = data
+        .scales
+        .entry(entity.name().to_string())
+        .or_insert(Scale::IDENTITY);
+    *scale = scale.mirrored(axis);
+    let compiled_scale = scale.compile();
+    transformation(data, &entity.selector(), 0, 0, &compiled_scale)
+}
+
 fn spawn(
-    data: &ProgramData,
+    data: &CompileContext,
     entity_type: &str,
     new_entity_name: &str,
     source_entity_name: &str,
+    offset: Option<Vector>,
 ) -> String {
+    let position = offset.map_or_else(
+        || "~ ~ ~".to_string(),
+        |(x, y, z)| format!("~{x} ~{y} ~{z}"),
+    );
     data.execute_at_string(
         source_entity_name,
         format!(
-            "summon {entity_type} ~ ~ ~ {{Tags:[\"{}\",\"{new_entity_name}\"]}}",
+            "summon {entity_type} {position} {{Tags:[\"{}\",\"{new_entity_name}\"]}}",
+            data.object_name
+        ),
+    )
+}
+
+/// Mounts the new entity on the nearest source, which is the one it was just summoned at.
+/// Riding only carries the entity along with the source's position, such as its teleports, and the
+/// client can draw it a tick behind. The source's transformations are visual and aren't inherited.
+fn mount(data: &CompileContext, new_entity_name: &str, source_entity_name: &str) -> String {
+    data.execute_at_string(
+        new_entity_name,
+        format!(
+            "ride @s mount @e[tag={},tag={source_entity_name},limit=1,sort=nearest]",
             data.object_name
         ),
     )
 }
 
-fn item(data: &ProgramData, entity_name: &str, item: &str) -> String {
+fn item(data: &CompileContext, entity_name: &str, item: &str) -> String {
+    if data.target_version.item_components() {
+        return data.execute_string(
+            entity_name,
+            format!("item replace entity @s contents with {item}"),
+        );
+    }
+    // Before components, the item is NBT, its id needs a namespace, and its count must be given.
+    let (id, tag) = item
+        .find('{')
+        .map_or((item, None), |index| (&item[..index], Some(&item[index..])));
+    let namespace = if id.contains(':') { "" } else { "minecraft:" };
+    let tag = tag.map_or_else(String::new, |tag| format!(",tag:{tag}"));
     data.execute_string(
         entity_name,
-        format!("item replace entity @s contents with {item}"),
+        format!("data merge entity @s {{item:{{id:\"{namespace}{id}\",Count:1b{tag}}}}}"),
     )
 }
 
-fn block(data: &ProgramData, entity_name: &str, block_state: &str) -> String {
+fn block(data: &CompileContext, entity_name: &str, block_state: &str) -> String {
     data.execute_string(
         entity_name,
         format!("data merge entity @s {{block_state:{{{block_state}}}}}"),
     )
 }
 
-fn text(data: &ProgramData, entity_name: &str, text: &str) -> String {
+/// Sets an entity's text, applying any color previously given to it with `color`.
+fn text_statement(
+    data: &mut CompileContext,
+    entity: &Entity,
+    text_component: &TextComponent,
+) -> String {
+    let (current, color) = data.texts.entry(entity.name().to_string()).or_default();
+    let colored = color.as_ref().map_or_else(
+        || text_component.clone(),
+        |color| text_component.with_color(color),
+    );
+    *current = Some(text_component.clone());
+    text(data, &entity.selector(), &colored)
+}
+
+/// Recolors an entity's current text, and remembers the color for any text it is given later.
+/// Without a current text there is nothing to recolor, so no command is emitted.
+fn color_statement(
+    data: &mut CompileContext,
+    entity: &Entity,
+    color: &TextColor,
+) -> Option<String> {
+    let (current, current_color) = data.texts.entry(entity.name().to_string()).or_default();
+    let colored = current.as_ref().map(|current| current.with_color(color));
+    *current_color = Some(color.clone());
+    colored.map(|colored| text(data, &entity.selector(), &colored))
+}
+
+fn text(data: &CompileContext, entity_name: &str, text: &TextComponent) -> String {
+    // A JSON component is already valid SNBT, so only the versions that take a JSON string need it
+    // escaped for the single-quoted string it is embedded in.
+    let component = if data.target_version.text_components() {
+        text.compile()
+    } else {
+        let escaped = text.compile().replace('\\', "\\\\").replace('\'', "\\'");
+        format!("'{escaped}'")
+    };
     data.execute_string(
         entity_name,
-        format!("data merge entity @s {{text:'{text}'}}"),
+        format!("data merge entity @s {{text:{component}}}"),
     )
 }
 
-fn teleport(data: &ProgramData, entity_name: &str, x: f32, y: f32, z: f32) -> String {
+fn teleport(data: &CompileContext, entity_name: &str, x: f32, y: f32, z: f32) -> String {
     data.execute_at_string(entity_name, format!("tp @s ~{x} ~{y} ~{z}"))
 }
+
+fn tag(data: &CompileContext, entity_name: &str, operation: TagOperation, tag: &str) -> String {
+    data.execute_string(entity_name, format!("tag @s {} {tag}", operation.compile()))
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::{AngleUnit, Regexes};
+
+    fn data() -> CompileContext {
+        CompileContext {
+            file_name: "anim".to_string(),
+            object_name: "obj".to_string(),
+            animation_name: "anim".to_string(),
+            delay: 0,
+            namespace: None,
+            output_name: None,
+            timer_objective: "timer".to_string(),
+            flags_objective: "flags".to_string(),
+            end: 0,
+            gated: false,
+            texts: HashMap::new(),
+            translations: HashMap::new(),
+            scales: HashMap::new(),
+            anchors: HashMap::new(),
+            merge_macro: None,
+            pauses: Vec::new(),
+            target_version: TargetVersion::default(),
+            looping: false,
+        }
+    }
+
+    /// Reverses the SNBT single-quoted string escapes.
+    fn unescape_snbt(string: &str) -> String {
+        let mut unescaped = String::new();
+        let mut chars = string.chars();
+        while let Some(character) = chars.next() {
+            if character == '\\' {
+                unescaped.extend(chars.next());
+            } else {
+                assert_ne!(character, '\'', "unescaped quote in {string}");
+                unescaped.push(character);
+            }
+        }
+        unescaped
+    }
+
+    #[test]
+    fn text_with_quote_and_newline_is_valid_snbt() {
+        let original = "It's a\nnew line";
+        let command = text(&data(), "t", &TextComponent::Plain(original.to_string()));
+        let snbt = command
+            .split_once("{text:'")
+            .and_then(|(_, rest)| rest.strip_suffix("'}"))
+            .unwrap();
+        let component: String = serde_json::from_str(&unescape_snbt(snbt)).unwrap();
+        assert_eq!(component, original);
+    }
+
+    #[test]
+    fn plain_text_is_unchanged() {
+        let plain = TextComponent::Plain("This is some text".to_string());
+        assert!(text(&data(), "t", &plain).ends_with(r#"{text:'"This is some text"'}"#));
+    }
+
+    #[test]
+    fn color_recolors_current_and_later_text() {
+        let with_color = TextComponent::Plain("Hi".to_string())
+            .with_color(&TextColor::Named("red"))
+            .compile();
+        assert_eq!(with_color, r#"{"color":"red","text":"Hi"}"#);
+        let json = TextComponent::Json(r#"{"text":"Hi","bold":true}"#.to_string())
+            .with_color(&TextColor::Hex(0x00FF_8800))
+            .compile();
+        assert_eq!(json, r##"{"bold":true,"color":"#FF8800","text":"Hi"}"##);
+        let array = TextComponent::Json(r#"["a","b"]"#.to_string())
+            .with_color(&TextColor::Named("gold"))
+            .compile();
+        assert_eq!(array, r#"{"color":"gold","extra":[["a","b"]],"text":""}"#);
+    }
+
+    #[test]
+    fn json_text_is_emitted_verbatim() {
+        let json = r#"{"text":"Hi","color":"red"}"#;
+        let component = TextComponent::Json(json.to_string());
+        assert!(text(&data(), "t", &component).ends_with(&format!("{{text:'{json}'}}")));
+    }
+
+    #[test]
+    fn multi_turn_rotation_spreads_keyframes_across_duration() {
+        let mut data = data();
+        let rotation = Rotation::new([0.0, 1.0, 0.0], 720.0, AngleUnit::Degrees, None);
+        let commands = rotate(&mut data, "test", &rotation, 40, 0);
+        let lines: Vec<&str> = commands.lines().collect();
+        assert_eq!(lines.len(), 8);
+        for (step, line) in lines.iter().enumerate() {
+            assert!(line.contains(&format!("timer matches {} run", step * 5)));
+            assert!(line.contains("interpolation_duration:5,"));
+        }
+        assert!(lines[7].contains(&rotation.compile()));
+        assert_eq!(data.end, 35);
+    }
+
+    #[test]
+    fn delayed_rotation_shifts_every_keyframe() {
+        let mut data = data();
+        let rotation = Rotation::new([0.0, 1.0, 0.0], 720.0, AngleUnit::Degrees, None);
+        let commands = rotate(&mut data, "test", &rotation, 40, 5);
+        let lines: Vec<&str> = commands.lines().collect();
+        assert_eq!(lines.len(), 8);
+        for (step, line) in lines.iter().enumerate() {
+            assert!(line.contains(&format!("timer matches {} run", step * 5 + 5)));
+            assert!(line.contains("{start_interpolation:0,interpolation_duration:5,"));
+        }
+        assert_eq!(data.end, 40);
+    }
+
+    #[test]
+    fn single_turn_rotation_stays_single_command() {
+        let mut data = data();
+        let rotation = Rotation::new([0.0, 1.0, 0.0], 360.0, AngleUnit::Degrees, None);
+        let commands = rotate(&mut data, "test", &rotation, 40, 0);
+        assert_eq!(commands.lines().count(), 1);
+        assert!(commands.contains("interpolation_duration:40,"));
+    }
+
+    #[test]
+    fn mirror_flips_the_last_scale() {
+        let mut data = data();
+        let test = Entity::new("test", &Regexes::new(false).unwrap().name).unwrap();
+        assert!(mirror(&mut data, &test, Axis::X).contains("scale: [-1f,1f,1f]"));
+        data.scales
+            .insert("test".to_string(), Scale::new((2.0, 3.0, 4.0)));
+        assert!(mirror(&mut data, &test, Axis::Y).contains("scale: [2f,-3f,4f]"));
+        assert!(mirror(&mut data, &test, Axis::Y).contains("scale: [2f,3f,4f]"));
+    }
+}
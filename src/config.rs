@@ -1,29 +1,709 @@
+use anyhow::ensure;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::{collections::BTreeMap, fmt::Display, fs, num::NonZeroUsize, path::Path};
 
+use crate::{easing::Curve, errors::GenericError, objects::AngleUnit};
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SourceFolders {
+    Single(String),
+    Multiple(Vec<String>),
+}
+impl SourceFolders {
+    pub fn roots(&self) -> impl Iterator<Item = &str> {
+        match self {
+            Self::Single(root) => std::slice::from_ref(root).iter(),
+            Self::Multiple(roots) => roots.iter(),
+        }
+        .map(String::as_str)
+    }
+}
+
+/// What an animation's `_start` function does when the animation is already playing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Retrigger {
+    /// Starts the animation over from its first tick.
+    #[default]
+    Restart,
+    /// Lets the running animation finish, ignoring the start.
+    Block,
+}
+
+/// The Minecraft version the generated commands are written for, named after the first release with
+/// each syntax the compiler emits.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TargetVersion {
+    /// 1.20 to 1.20.4, where an item is set with NBT and text is a JSON string.
+    #[serde(rename = "1.20")]
+    V1_20,
+    /// 1.20.5 to 1.21.4, where an item takes components and text is still a JSON string.
+    #[default]
+    #[serde(rename = "1.20.5")]
+    V1_20_5,
+    /// 1.21.5 and later, where text is a component rather than a JSON string.
+    #[serde(rename = "1.21.5")]
+    V1_21_5,
+}
+impl TargetVersion {
+    /// Whether items take components like `stone[custom_name='"Rock"']`, rather than NBT.
+    #[must_use]
+    pub const fn item_components(self) -> bool {
+        !matches!(self, Self::V1_20)
+    }
+    /// Whether a text display's text is a component, rather than a JSON string.
+    #[must_use]
+    pub const fn text_components(self) -> bool {
+        matches!(self, Self::V1_21_5)
+    }
+}
+impl Display for TargetVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let version = match self {
+            Self::V1_20 => "1.20",
+            Self::V1_20_5 => "1.20.5",
+            Self::V1_21_5 => "1.21.5",
+        };
+        write!(f, "{version}")
+    }
+}
+
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Serialize, Deserialize)]
 pub struct Config {
-    pub source_folder: String,
+    pub source_folder: SourceFolders,
     pub target_folder: String,
     pub tick_function: String,
     pub namespace: String,
+    #[serde(default)]
+    pub angle_unit: AngleUnit,
+    /// The Minecraft version to write commands for, `"1.20"`, `"1.20.5"` or `"1.21.5"`, where their
+    /// syntax has changed.
+    #[serde(default)]
+    pub target_version: TargetVersion,
+    #[serde(default = "default_source_extension")]
+    pub source_extension: String,
+    #[serde(default = "default_output_extension")]
+    pub output_extension: String,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Extra entity types `spawn` accepts besides the display entities, e.g. modded entities.
+    #[serde(default)]
+    pub entity_types: Vec<String>,
+    /// Checks the properties in a `block` statement's state against the vanilla blocks' own. Blocks the
+    /// bundled table doesn't list, like modded blocks, are never rejected.
+    #[serde(default)]
+    pub validate_block_states: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub single_file: Option<String>,
+    /// Where to write a function creating the scoreboard objectives the animations use. `null` leaves it out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_function: Option<String>,
+    /// The `minecraft:load` function tag to add the load function to, e.g. `./data/minecraft/tags/function/load.json`.
+    /// Values already in the tag are kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_tag: Option<String>,
+    /// The comment put at the top of every generated file. `null` or `""` leaves it out.
+    #[serde(default = "default_header")]
+    pub header: Option<String>,
+    /// Sorts the tick function's lines so they don't depend on filesystem order. Disable to keep source order.
+    #[serde(default = "default_sort_tick_function")]
+    pub sort_tick_function: bool,
+    /// How many ticks a second lasts when a time is given in seconds, e.g. `wait 2s`, for servers running
+    /// at a custom tick rate. Changing it doesn't retime anything already given in ticks.
+    #[serde(default = "default_ticks_per_second")]
+    pub ticks_per_second: u32,
+    /// Extra curves a translation or scale can name as its easing, like `{"bounce": {"table": [0, 1.2, 1]}}`
+    /// or `{"snappy": {"bezier": [0.2, 0, 0, 1]}}`. A curve here replaces a built-in one of the same name.
+    #[serde(default)]
+    pub easings: BTreeMap<String, Curve>,
+    /// Warns about animations that run for longer than this many ticks. `null` turns the warning off.
+    #[serde(default = "default_max_ticks")]
+    pub max_ticks: Option<u32>,
+    /// Warns about generated commands longer than this many characters, which the game may refuse to run.
+    /// `null` turns the warning off.
+    #[serde(default = "default_max_command_length")]
+    pub max_command_length: Option<usize>,
+    /// The scoreboard objective counting each animation's ticks, for packs that already use `timer`.
+    #[serde(default = "default_timer_objective")]
+    pub timer_objective: String,
+    /// The scoreboard objective marking which animations are playing, for packs that already use `flags`.
+    #[serde(default = "default_flags_objective")]
+    pub flags_objective: String,
+    #[serde(default)]
+    pub retrigger: Retrigger,
+    /// Rejects statements naming an entity that the file never declares with `entity <name>`, spawns,
+    /// or adds as a tag, to catch misspelled names.
+    #[serde(default)]
+    pub strict_entities: bool,
+    /// Lets entity, object and animation names contain dots, so entities can be named hierarchically
+    /// like `arm.left.hand`.
+    #[serde(default)]
+    pub dotted_names: bool,
+    /// Calls each object's animations from a function of its own, which the tick function only runs
+    /// while one of them is playing. This saves a check per animation on every tick for objects with
+    /// many animations.
+    #[serde(default)]
+    pub group_tick_function: bool,
+    /// Runs the `data merge` commands of each entity with at least three of them through one shared
+    /// macro function instead of repeating their selector on every line, which shrinks large
+    /// animations. Macro functions need Minecraft 1.20.2 or newer.
+    #[serde(default)]
+    pub macros: bool,
+    /// Drops a generated command that exactly repeats the one before it.
+    #[serde(default = "default_dedupe")]
+    pub dedupe: bool,
+    /// Writes a readable list of each file's events, by tick, to `<output>.timeline.txt`.
+    #[serde(default)]
+    pub report: bool,
+    /// Writes each file's interpolations to `<output>_timeline.json`, for tools that visualize animations.
+    #[serde(default)]
+    pub timelines: bool,
+    /// Copies `#region` and `#endregion` markers into the output as comments, so editors can fold the
+    /// generated functions the same way as their sources.
+    #[serde(default)]
+    pub keep_regions: bool,
+    /// Writes `dispa_manifest.json` in the working directory, listing every animation with its object,
+    /// namespace, function and length, for tools built around the pack.
+    #[serde(default)]
+    pub manifest: bool,
+    #[serde(default)]
+    pub minify: bool,
+    /// Groups each animation's commands under a `# tick <n>` comment for the tick they start on, and
+    /// labels the lines that end the animation and advance its timer.
+    #[serde(default)]
+    pub pretty: bool,
+    #[serde(default)]
+    pub verbose: bool,
+    /// Checks every generated command for mistakes like unbalanced brackets or unclosed quotes, failing
+    /// the file that generated one. `--verify` turns it on for a single run.
+    #[serde(default)]
+    pub verify: bool,
+    /// How many files are compiled at once, which `--jobs` overrides. `null` uses every core. Files are
+    /// always generated in the same order, so this only changes how fast they compile, never the output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<NonZeroUsize>,
 }
 
-const CONFIG_PATH: &str = "./dspa_config.json";
-pub fn read() -> anyhow::Result<Config> {
-    let config_contents = fs::read_to_string(CONFIG_PATH).unwrap_or_else(|_| initialize_file());
-    Ok(serde_json::from_str::<Config>(&config_contents)?)
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            source_folder: SourceFolders::Single("./src".to_string()),
+            target_folder: "./objects".to_string(),
+            tick_function: "./tick.mcfunction".to_string(),
+            namespace: "de".to_string(),
+            angle_unit: AngleUnit::default(),
+            target_version: TargetVersion::default(),
+            source_extension: default_source_extension(),
+            output_extension: default_output_extension(),
+            exclude: Vec::new(),
+            entity_types: Vec::new(),
+            validate_block_states: false,
+            single_file: None,
+            load_function: None,
+            load_tag: None,
+            header: default_header(),
+            sort_tick_function: default_sort_tick_function(),
+            ticks_per_second: default_ticks_per_second(),
+            easings: BTreeMap::new(),
+            max_ticks: default_max_ticks(),
+            max_command_length: default_max_command_length(),
+            timer_objective: default_timer_objective(),
+            flags_objective: default_flags_objective(),
+            retrigger: Retrigger::default(),
+            strict_entities: false,
+            dotted_names: false,
+            group_tick_function: false,
+            macros: false,
+            dedupe: default_dedupe(),
+            report: false,
+            timelines: false,
+            keep_regions: false,
+            manifest: false,
+            minify: false,
+            pretty: false,
+            verbose: false,
+            verify: false,
+            jobs: None,
+        }
+    }
 }
 
-const CONFIG_DEFAULTS: &str = r#"
-{
-    "source_folder": "./src",
-    "target_folder": "./objects",
-    "tick_function": "./tick.mcfunction",
-    "namespace": "de"
+impl Config {
+    /// Checks the folders and names the config points at before anything is compiled, creating the
+    /// target folder and the tick function's folder if they don't exist yet.
+    ///
+    /// # Errors
+    /// If a source folder is missing, the target folder or tick function's folder can't be created,
+    /// or the namespace isn't a valid resource location namespace, this returns a
+    /// `GenericError::InvalidConfigField` naming the field and its value.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for root in self.source_folder.roots() {
+            ensure!(
+                Path::new(root).is_dir(),
+                GenericError::InvalidConfigField(
+                    "source_folder",
+                    root.to_string(),
+                    "No such folder exists.".to_string()
+                )
+            );
+        }
+        fs::create_dir_all(&self.target_folder).map_err(|err| {
+            GenericError::InvalidConfigField(
+                "target_folder",
+                self.target_folder.clone(),
+                format!("The folder can't be created: {err}"),
+            )
+        })?;
+        ensure!(
+            is_valid_namespace(&self.namespace),
+            GenericError::InvalidConfigField(
+                "namespace",
+                self.namespace.clone(),
+                "Expected only lowercase letters, digits, '_', '-' and '.'.".to_string()
+            )
+        );
+        for (name, curve) in &self.easings {
+            if let Some(problem) = curve.problem() {
+                return Err(GenericError::InvalidConfigField(
+                    "easings",
+                    name.clone(),
+                    problem.to_string(),
+                )
+                .into());
+            }
+        }
+        // A bare file name has an empty parent, which is the working directory.
+        if let Some(tick_folder) = Path::new(&self.tick_function)
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(tick_folder).map_err(|err| {
+                GenericError::InvalidConfigField(
+                    "tick_function",
+                    self.tick_function.clone(),
+                    format!(
+                        "The folder '{}' can't be created: {err}",
+                        tick_folder.display()
+                    ),
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `namespace` is a valid resource location namespace, e.g. the `de` of `de:anim`.
+#[must_use]
+pub fn is_valid_namespace(namespace: &str) -> bool {
+    !namespace.is_empty()
+        && namespace
+            .chars()
+            .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '_' | '-' | '.'))
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn default_header() -> Option<String> {
+    Some("File generated using DiSPA v{version}".to_string())
+}
+const fn default_ticks_per_second() -> u32 {
+    20
+}
+fn default_timer_objective() -> String {
+    "timer".to_string()
+}
+fn default_flags_objective() -> String {
+    "flags".to_string()
+}
+/// Ten minutes at 20 ticks per second.
+#[allow(clippy::unnecessary_wraps)]
+const fn default_max_ticks() -> Option<u32> {
+    Some(12_000)
+}
+/// The longest command a command block accepts.
+#[allow(clippy::unnecessary_wraps)]
+const fn default_max_command_length() -> Option<usize> {
+    Some(32_767)
+}
+const fn default_sort_tick_function() -> bool {
+    true
+}
+const fn default_dedupe() -> bool {
+    true
+}
+fn default_source_extension() -> String {
+    "dspa".to_string()
+}
+fn default_output_extension() -> String {
+    "mcfunction".to_string()
+}
+
+const JSON_CONFIG_PATH: &str = "./dspa_config.json";
+const TOML_CONFIG_PATH: &str = "./dspa_config.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigFormat {
+    Json,
+    Toml,
 }
-"#;
-fn initialize_file() -> String {
-    fs::write("./dspa_config.json", CONFIG_DEFAULTS).expect("config path should be valid.");
-    CONFIG_DEFAULTS.into()
+impl ConfigFormat {
+    /// Picks the format from the file extension, treating anything other than `.toml` as JSON.
+    fn of(path: &str) -> Self {
+        if Path::new(path).extension().is_some_and(|e| e == "toml") {
+            Self::Toml
+        } else {
+            Self::Json
+        }
+    }
+    fn parse(self, contents: &str) -> Result<Config, String> {
+        match self {
+            Self::Json => {
+                serde_json::from_str(&without_comments(contents)).map_err(|err| err.to_string())
+            }
+            Self::Toml => toml::from_str(contents).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// Blanks out the `//` and `/* */` comments and trailing commas of a JSON config, so it can be
+/// annotated. Everything blanked becomes spaces, keeping newlines, so parse errors still point at
+/// the right line and column.
+fn without_comments(contents: &str) -> String {
+    let mut output: Vec<char> = Vec::with_capacity(contents.len());
+    let mut chars = contents.chars().peekable();
+    let mut quoted = false;
+    let mut escaped = false;
+    while let Some(character) = chars.next() {
+        if quoted {
+            match character {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => quoted = false,
+                _ => {}
+            }
+            output.push(character);
+            continue;
+        }
+        match (character, chars.peek()) {
+            ('"', _) => quoted = true,
+            ('/', Some('/')) => {
+                output.push(' ');
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    output.push(' ');
+                    chars.next();
+                }
+                continue;
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                output.extend("  ".chars());
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    output.push(if next == '\n' { '\n' } else { ' ' });
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+                continue;
+            }
+            ('}' | ']', _) => {
+                if let Some(last) = output.iter_mut().rev().find(|c| !c.is_whitespace()) {
+                    if *last == ',' {
+                        *last = ' ';
+                    }
+                }
+            }
+            _ => {}
+        }
+        output.push(character);
+    }
+    output.into_iter().collect()
+}
+
+/// Reads the config from `explicit_path` if one was given, and otherwise from whichever of
+/// `dspa_config.json` and `dspa_config.toml` exists. A default JSON config is created if neither does.
+///
+/// # Errors
+/// If both default files exist without an explicit path, or the config is invalid,
+/// this returns a `GenericError::InvalidConfig`.
+pub fn read(explicit_path: Option<&str>) -> anyhow::Result<Config> {
+    let path = match explicit_path {
+        Some(path) => path,
+        None => default_path()?,
+    };
+    let format = ConfigFormat::of(path);
+    let config = match fs::read_to_string(path) {
+        Ok(config_contents) => format
+            .parse(&config_contents)
+            .map_err(|err| GenericError::InvalidConfig(path.to_string(), err))?,
+        Err(_) => initialize_file(path, format)?,
+    };
+    ensure!(
+        !(config.minify && config.verbose),
+        GenericError::InvalidConfig(
+            path.to_string(),
+            "'minify' and 'verbose' cannot both be enabled.".to_string()
+        )
+    );
+    ensure!(
+        !(config.minify && config.pretty),
+        GenericError::InvalidConfig(
+            path.to_string(),
+            "'minify' and 'pretty' cannot both be enabled.".to_string()
+        )
+    );
+    ensure!(
+        config.ticks_per_second > 0,
+        GenericError::InvalidConfig(
+            path.to_string(),
+            "'ticks_per_second' must be at least 1.".to_string()
+        )
+    );
+    Ok(config)
+}
+
+/// Reads the config like `read`, but falls back to the default config instead of creating a file,
+/// for commands that don't need a project to be set up.
+///
+/// # Errors
+/// If the config exists but can't be read, this returns a `GenericError::InvalidConfig`.
+pub fn read_if_present(explicit_path: Option<&str>) -> anyhow::Result<Config> {
+    let path = match explicit_path {
+        Some(path) => path,
+        None => default_path()?,
+    };
+    if Path::new(path).exists() {
+        read(Some(path))
+    } else {
+        Ok(Config::default())
+    }
+}
+
+/// Writes the default config for a new project to `explicit_path`, or to the default config path, and
+/// returns it.
+///
+/// # Errors
+/// If the config already exists and `force` isn't set, this returns a `GenericError::ConfigExists`.
+pub fn initialize(explicit_path: Option<&str>, force: bool) -> anyhow::Result<Config> {
+    let path = match explicit_path {
+        Some(path) => path,
+        None => default_path()?,
+    };
+    ensure!(
+        force || !Path::new(path).exists(),
+        GenericError::ConfigExists(path.to_string())
+    );
+    initialize_file(path, ConfigFormat::of(path))
+}
+
+fn default_path() -> anyhow::Result<&'static str> {
+    let json = Path::new(JSON_CONFIG_PATH).exists();
+    let toml = Path::new(TOML_CONFIG_PATH).exists();
+    ensure!(
+        !(json && toml),
+        GenericError::InvalidConfig(
+            JSON_CONFIG_PATH.to_string(),
+            format!("'{TOML_CONFIG_PATH}' also exists. Remove one, or choose one with '--config <path>'.")
+        )
+    );
+    Ok(if toml {
+        TOML_CONFIG_PATH
+    } else {
+        JSON_CONFIG_PATH
+    })
+}
+
+/// Writes the default config to `path` and returns it.
+///
+/// # Errors
+/// If the file cannot be written, this returns a `GenericError::FileNotExist`.
+fn initialize_file(path: &str, format: ConfigFormat) -> anyhow::Result<Config> {
+    let config = Config::default();
+    let contents = match format {
+        ConfigFormat::Json => serde_json::to_string_pretty(&config)?,
+        ConfigFormat::Toml => toml::to_string(&config)?,
+    };
+    fs::write(path, contents).map_err(|_| GenericError::FileNotExist(path.to_string()))?;
+    Ok(config)
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiled;
+
+    #[test]
+    fn json_configs_can_have_comments_and_trailing_commas() {
+        let config = ConfigFormat::Json
+            .parse(
+                r#"{
+                    // Where the sources live.
+                    "source_folder": "./animations",
+                    "target_folder": "./objects", /* the pack's functions */
+                    "tick_function": "./tick.mcfunction",
+                    "namespace": "de",
+                    "header": "// kept, since it's in a string /* too */",
+                    "exclude": ["**/draft/**",],
+                }"#,
+            )
+            .unwrap();
+        assert_eq!(
+            config.source_folder.roots().collect::<Vec<_>>(),
+            ["./animations"]
+        );
+        assert_eq!(
+            config.header.as_deref(),
+            Some("// kept, since it's in a string /* too */")
+        );
+        assert_eq!(config.exclude, ["**/draft/**"]);
+
+        let Err(error) = ConfigFormat::Json.parse("{\n  // a comment\n  \"namespace\": 1,\n}")
+        else {
+            panic!("A number namespace should be rejected.");
+        };
+        assert!(error.contains("line 3"), "{error}");
+    }
+
+    #[test]
+    fn source_folder_accepts_string_or_array() {
+        let single: SourceFolders = serde_json::from_str(r#""./src""#).unwrap();
+        assert_eq!(single.roots().collect::<Vec<_>>(), ["./src"]);
+        let multiple: SourceFolders = serde_json::from_str(r#"["./shared", "./world"]"#).unwrap();
+        assert_eq!(
+            multiple.roots().collect::<Vec<_>>(),
+            ["./shared", "./world"]
+        );
+    }
+
+    #[test]
+    fn toml_config_matches_json_config() {
+        let json = ConfigFormat::Json
+            .parse(&serde_json::to_string_pretty(&Config::default()).unwrap())
+            .unwrap();
+        let toml = ConfigFormat::Toml
+            .parse(&toml::to_string(&json).unwrap())
+            .unwrap();
+        assert_eq!(
+            serde_json::to_string(&json).unwrap(),
+            serde_json::to_string(&toml).unwrap()
+        );
+    }
+
+    #[test]
+    fn target_versions_are_named_after_releases() {
+        let version: TargetVersion = serde_json::from_str(r#""1.21.5""#).unwrap();
+        assert_eq!(version, TargetVersion::V1_21_5);
+        assert_eq!(version.to_string(), "1.21.5");
+        assert!(serde_json::from_str::<TargetVersion>(r#""1.19""#).is_err());
+        assert_eq!(Config::default().target_version, TargetVersion::V1_20_5);
+    }
+
+    #[test]
+    fn format_follows_extension() {
+        assert_eq!(ConfigFormat::of("./dspa_config.toml"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::of("./dspa_config.json"), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::of("./custom"), ConfigFormat::Json);
+    }
+
+    #[test]
+    fn header_defaults_to_disclaimer_and_can_be_removed() {
+        let mut config = Config::default();
+        assert_eq!(
+            compiled::with_header(&config, "say hi"),
+            format!(
+                "# File generated using DiSPA v{}\nsay hi",
+                compiled::VERSION
+            )
+        );
+        config.header = Some("Line one\nLine two".to_string());
+        assert_eq!(
+            compiled::with_header(&config, "say hi"),
+            "# Line one\n# Line two\nsay hi"
+        );
+        config.header = Some(String::new());
+        assert_eq!(compiled::with_header(&config, "say hi"), "say hi");
+        let config: Config =
+            serde_json::from_str(r#"{"source_folder":"s","target_folder":"t","tick_function":"f","namespace":"n","header":null}"#)
+                .unwrap();
+        assert_eq!(config.header, None);
+    }
+
+    #[test]
+    fn objectives_default_to_timer_and_flags() {
+        let config: Config = serde_json::from_str(
+            r#"{"source_folder":"s","target_folder":"t","tick_function":"f","namespace":"n"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.timer_objective, "timer");
+        assert_eq!(config.flags_objective, "flags");
+    }
+
+    #[test]
+    fn validation_names_the_offending_field() {
+        let target_folder = "./target/validated_objects";
+        let _ = fs::remove_dir_all(target_folder);
+        let mut config = Config {
+            source_folder: SourceFolders::Single("./src".to_string()),
+            target_folder: target_folder.to_string(),
+            ..Config::default()
+        };
+        config.validate().unwrap();
+        assert!(Path::new(target_folder).is_dir());
+
+        let field = |config: &Config| match config.validate().unwrap_err().downcast() {
+            Ok(GenericError::InvalidConfigField(field, value, _)) => (field, value),
+            other => panic!("expected an invalid field, got {other:?}"),
+        };
+        config.namespace = "My Pack".to_string();
+        assert_eq!(field(&config), ("namespace", "My Pack".to_string()));
+        config.namespace = "de".to_string();
+        config.easings = serde_json::from_str(
+            r#"{"rush": {"table": [0, 1, 1]}, "snap": {"bezier": [1.5, 0, 0, 1]}}"#,
+        )
+        .unwrap();
+        assert_eq!(field(&config), ("easings", "snap".to_string()));
+        config.easings.remove("snap");
+        // A file is in the way of the tick function's folder.
+        config.tick_function = "./Cargo.toml/tick.mcfunction".to_string();
+        assert_eq!(
+            field(&config),
+            ("tick_function", "./Cargo.toml/tick.mcfunction".to_string())
+        );
+        config.source_folder =
+            SourceFolders::Multiple(vec!["./src".to_string(), "./missing".to_string()]);
+        assert_eq!(field(&config), ("source_folder", "./missing".to_string()));
+    }
+
+    #[test]
+    fn initialize_keeps_an_existing_config_unless_forced() {
+        let path = "./target/initialized_dspa_config.toml";
+        let _ = fs::remove_file(path);
+        initialize(Some(path), false).unwrap();
+        assert_eq!(read(Some(path)).unwrap().namespace, "de");
+        fs::write(path, "namespace = 'kept'").unwrap();
+        let Err(error) = initialize(Some(path), false) else {
+            panic!("expected the existing config to be kept");
+        };
+        assert!(matches!(
+            error.downcast(),
+            Ok(GenericError::ConfigExists(_))
+        ));
+        assert!(fs::read_to_string(path).unwrap().contains("kept"));
+        initialize(Some(path), true).unwrap();
+        assert_eq!(read(Some(path)).unwrap().namespace, "de");
+    }
+
+    #[test]
+    fn missing_config_is_not_created_for_single_files() {
+        let path = "./target/missing_dspa_config.json";
+        let config = read_if_present(Some(path)).unwrap();
+        assert_eq!(config.namespace, Config::default().namespace);
+        assert!(!Path::new(path).exists());
+    }
 }
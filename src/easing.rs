@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How far along an interpolation is for each share of its time, from 0 at the start to 1 at the end.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Curve {
+    /// A CSS-style `cubic-bezier(x1, y1, x2, y2)` between `(0, 0)` and `(1, 1)`.
+    Bezier([f32; 4]),
+    /// The progress at evenly spaced times, the first at the start and the last at the end.
+    /// Progress between two samples is interpolated linearly.
+    Table(Vec<f32>),
+}
+impl Curve {
+    /// How far along the curve is at `time`, which runs from 0 to 1.
+    pub fn at(&self, time: f32) -> f32 {
+        let time = time.clamp(0.0, 1.0);
+        match self {
+            Self::Bezier([x1, y1, x2, y2]) => {
+                let parameter = bezier_parameter(*x1, *x2, time);
+                bezier(*y1, *y2, parameter)
+            }
+            Self::Table(samples) => {
+                let Some(last) = samples.len().checked_sub(1) else {
+                    return time;
+                };
+                #[allow(clippy::cast_precision_loss)]
+                let position = time * last as f32;
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let index = (position.floor() as usize).min(last.saturating_sub(1));
+                let (Some(&from), Some(&to)) = (samples.get(index), samples.get(index + 1)) else {
+                    return samples[0];
+                };
+                #[allow(clippy::cast_precision_loss)]
+                (to - from).mul_add(position - index as f32, from)
+            }
+        }
+    }
+
+    /// Why the curve can't be used, if it can't.
+    pub fn problem(&self) -> Option<&'static str> {
+        match self {
+            Self::Bezier([x1, _, x2, _])
+                if !(0.0..=1.0).contains(x1) || !(0.0..=1.0).contains(x2) =>
+            {
+                Some("The x coordinates of a bezier's control points must be between 0 and 1.")
+            }
+            Self::Bezier(points) if points.iter().any(|point| !point.is_finite()) => {
+                Some("A bezier's control points must be finite numbers.")
+            }
+            Self::Table(samples) if samples.len() < 2 => {
+                Some("A table needs at least a sample for the start and one for the end.")
+            }
+            Self::Table(samples) if samples.iter().any(|sample| !sample.is_finite()) => {
+                Some("A table's samples must be finite numbers.")
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A curve as a statement names it, like the `ease-in` in `move cube 0 1 0 20 ease-in`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Easing {
+    pub name: String,
+    pub curve: Curve,
+}
+
+/// One coordinate of a cubic bezier from 0 to 1 with control points `p1` and `p2`, at `t`.
+fn bezier(p1: f32, p2: f32, t: f32) -> f32 {
+    let inverse = 1.0 - t;
+    (3.0 * inverse * inverse * t).mul_add(p1, (3.0 * inverse * t * t).mul_add(p2, t * t * t))
+}
+
+/// Finds where along the bezier its x coordinate is `x`, by bisection since x only ever grows.
+fn bezier_parameter(x1: f32, x2: f32, x: f32) -> f32 {
+    let (mut low, mut high) = (0.0, 1.0);
+    for _ in 0..32 {
+        let middle = f32::midpoint(low, high);
+        if bezier(x1, x2, middle) < x {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    f32::midpoint(low, high)
+}
+
+/// The curves every animation can use, by the names of their CSS equivalents.
+const BUILT_IN: [(&str, [f32; 4]); 5] = [
+    ("linear", [0.0, 0.0, 1.0, 1.0]),
+    ("ease", [0.25, 0.1, 0.25, 1.0]),
+    ("ease-in", [0.42, 0.0, 1.0, 1.0]),
+    ("ease-out", [0.0, 0.0, 0.58, 1.0]),
+    ("ease-in-out", [0.42, 0.0, 0.58, 1.0]),
+];
+
+/// Looks up the curve called `name`, preferring the config's `easings` over the built-in curves.
+pub fn find(name: &str, configured: &BTreeMap<String, Curve>) -> Option<Curve> {
+    configured.get(name).cloned().or_else(|| {
+        BUILT_IN
+            .iter()
+            .find(|(built_in, _)| *built_in == name)
+            .map(|(_, points)| Curve::Bezier(*points))
+    })
+}
+
+/// The name of every curve, built-in and configured, for the error naming an unknown one.
+pub fn names(configured: &BTreeMap<String, Curve>) -> Vec<&str> {
+    let mut names: Vec<&str> = BUILT_IN
+        .iter()
+        .map(|(name, _)| *name)
+        .chain(configured.keys().map(String::as_str))
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(left: f32, right: f32) -> bool {
+        (left - right).abs() < 1e-3
+    }
+
+    #[test]
+    fn curves_run_from_start_to_end() {
+        for (_, points) in BUILT_IN {
+            let curve = Curve::Bezier(points);
+            assert!(close(curve.at(0.0), 0.0));
+            assert!(close(curve.at(1.0), 1.0));
+        }
+        let linear = find("linear", &BTreeMap::new()).unwrap();
+        assert!(close(linear.at(0.3), 0.3));
+        assert!(find("ease-in", &BTreeMap::new()).unwrap().at(0.5) < 0.5);
+    }
+
+    #[test]
+    fn tables_interpolate_between_samples() {
+        let bounce = Curve::Table(vec![0.0, 1.2, 1.0]);
+        assert!(close(bounce.at(0.25), 0.6));
+        assert!(close(bounce.at(0.5), 1.2));
+        assert!(close(bounce.at(1.0), 1.0));
+    }
+
+    #[test]
+    fn configured_curves_override_built_in_ones() {
+        let configured = BTreeMap::from([("ease".to_string(), Curve::Table(vec![0.0, 1.0]))]);
+        assert_eq!(
+            find("ease", &configured).unwrap(),
+            Curve::Table(vec![0.0, 1.0])
+        );
+        assert_eq!(
+            names(&configured),
+            ["ease", "ease-in", "ease-in-out", "ease-out", "linear"]
+        );
+        assert!(find("bounce", &configured).is_none());
+    }
+}
@@ -3,10 +3,13 @@ use std::{
     num::{ParseFloatError, ParseIntError},
 };
 
+use itertools::Itertools;
 use thiserror::Error;
 
 use crate::{
-    objects::{Entity, Position},
+    compiled::TransformKind,
+    config::TargetVersion,
+    objects::{Entity, Position, TextColor},
     statements::FileInfo,
 };
 
@@ -16,17 +19,31 @@ pub struct CompileError {
     line: usize,
     column: usize,
     error_message: String,
+    /// Whether the statement had the wrong number of arguments, which a left-out entity explains.
+    argument_count: bool,
 }
 #[allow(clippy::needless_pass_by_value)]
 impl CompileError {
+    #[must_use]
     pub fn new(file_info: &FileInfo, position: Position, error_type: CompileErrorType) -> Self {
+        Self::in_file(&file_info.path, position, error_type)
+    }
+    /// Creates an error for a file that has already been parsed, when only its path is left.
+    #[must_use]
+    pub fn in_file(file_path: &str, position: Position, error_type: CompileErrorType) -> Self {
         Self {
-            file_path: file_info.path.clone(),
+            file_path: file_path.to_string(),
             line: position.line,
             column: position.column,
             error_message: format!("{error_type}"),
+            argument_count: matches!(error_type, CompileErrorType::IncorrectArgumentCount(..)),
         }
     }
+
+    #[must_use]
+    pub const fn is_argument_count(&self) -> bool {
+        self.argument_count
+    }
 }
 impl Display for CompileError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -39,6 +56,99 @@ impl Display for CompileError {
 }
 impl std::error::Error for CompileError {}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem worth reporting that doesn't stop the file from compiling, unless warnings are denied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileWarning {
+    file_path: String,
+    line: usize,
+    column: usize,
+    warning_message: String,
+    severity: Severity,
+}
+#[allow(clippy::needless_pass_by_value)]
+impl CompileWarning {
+    #[must_use]
+    pub fn new(file_info: &FileInfo, position: Position, warning_type: CompileWarningType) -> Self {
+        Self::in_file(&file_info.path, position, warning_type)
+    }
+    /// Creates a warning for a file that has already been parsed, when only its path is left.
+    #[must_use]
+    pub fn in_file(file_path: &str, position: Position, warning_type: CompileWarningType) -> Self {
+        Self {
+            file_path: file_path.to_string(),
+            line: position.line,
+            column: position.column,
+            warning_message: format!("{warning_type}"),
+            severity: Severity::Warning,
+        }
+    }
+    /// Reports the warning as an error instead, for when warnings are denied.
+    #[must_use]
+    pub fn with_severity(self, severity: Severity) -> Self {
+        Self { severity, ..self }
+    }
+}
+impl Display for CompileWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self.severity {
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        };
+        write!(
+            f,
+            "Compilation {label}: \n  File: {}\n  Line: {}, Column: {}\n  {label}: {}",
+            self.file_path, self.line, self.column, self.warning_message
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum CompileWarningType<'a> {
+    ZeroDuration(&'a str),
+    /// The kind of transformation and the entity, then the kind and position of the earlier one it overlaps.
+    OverlappingInterpolation(TransformKind, &'a str, TransformKind, Position),
+    /// The same as `OverlappingInterpolation`, for an interpolation that ends before the earlier one starts.
+    OutOfOrderInterpolation(TransformKind, &'a str, TransformKind, Position),
+    /// The length of the command, then the configured maximum.
+    CommandTooLong(usize, usize),
+}
+impl Display for CompileWarningType<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ZeroDuration(statement) => {
+                write!(
+                    f,
+                    "Statement '{statement}' has a duration of 0, which snaps instead of interpolating. Use a duration of at least 1 to animate it."
+                )
+            }
+            Self::OverlappingInterpolation(kind, entity, earlier_kind, earlier) => {
+                write!(
+                    f,
+                    "This {kind} of '{entity}' starts before the {earlier_kind} at {earlier} has finished."
+                )
+            }
+            Self::OutOfOrderInterpolation(kind, entity, earlier_kind, earlier) => {
+                write!(
+                    f,
+                    "This {kind} of '{entity}' plays before the {earlier_kind} at {earlier}, which comes first in the file."
+                )
+            }
+            Self::CommandTooLong(length, max_length) => {
+                write!(
+                    f,
+                    "This statement generates a command of {length} characters, longer than the configured 'max_command_length' of {max_length}. Split it into several statements."
+                )
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum CompileErrorType<'a> {
     InvalidKeyword(&'a str),
@@ -46,15 +156,57 @@ pub enum CompileErrorType<'a> {
     InvalidCharacters(&'a str),
     InvalidInt(&'a str, ParseIntError),
     InvalidFloat(&'a str, ParseFloatError),
-    IncorrectArgumentCount(&'a str, usize, usize),
-    InvalidCoordinate(&'a str, ParseFloatError),
+    IncorrectArgumentCount(&'a str, String, usize),
+    /// The coordinate, why it didn't parse, and a hint for a common mistake, if it looks like one.
+    InvalidCoordinate(&'a str, ParseFloatError, Option<String>),
     InvalidAxis(&'a str),
+    ZeroQuaternion(&'a str),
     InvalidEntityType(&'a str),
     InvalidEntityName(&'a str),
+    EmptyEntityName(&'a str),
+    EntityNameTooLong(&'a str),
+    /// The entity name, which is also the object's.
+    ReservedEntityName(&'a str),
+    InvalidResourceLocation(&'a str),
+    InvalidNamespace(&'a str),
+    InvalidOutputName(&'a str),
+    GroupNotAllowed(&'a str),
+    InvalidPredicate(&'a str),
+    UnknownSelectorKey(&'a str),
+    PredicateNotAllowed(&'a str),
+    MissingEntity(&'a str),
     InvalidState(&'a str),
+    InvalidStateProperty(&'a str),
+    DuplicateStateProperty(&'a str),
+    /// The property and block, then the block's properties.
+    UnknownStateProperty(&'a str, &'a str, String),
+    /// The value and property, then the property's values.
+    InvalidStateValue(&'a str, &'a str, String),
+    NegativeStart(&'a str),
+    InvalidAxisDurations(&'a str),
+    InvalidCondition(String),
+    /// The unknown name, then every name that can be used, comma separated.
+    UnknownEasing(&'a str, String),
+    EasedAxisDurations,
+    UnexpectedArgument(&'a str),
+    InvalidSeconds(&'a str),
+    NonFiniteValue(&'a str, Position),
+    UnknownEntity(&'a str),
+    InvalidTagOperation(&'a str),
+    InvalidEscape(Option<char>),
+    InvalidJson(String),
+    InvalidColor(&'a str),
     NoAnimationName(&'a str),
+    UnterminatedComment,
+    UnsupportedItemSyntax(&'a str, TargetVersion),
+    /// The statement, then the generated line and what is wrong with it.
+    MalformedCommand(&'a str, &'a str, String),
+    UnterminatedRegion(&'a str),
+    UnmatchedEndRegion,
+    DanglingContinuation,
 }
 impl Display for CompileErrorType<'_> {
+    #[allow(clippy::too_many_lines)]
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::InvalidKeyword(keyword) => {
@@ -78,31 +230,234 @@ impl Display for CompileErrorType<'_> {
                     "Incorrect number of arguments in '{statement}': Expected '{expected}', found '{found}'."
                 )
             }
-            Self::InvalidCoordinate(coordinate, error) => {
-                write!(f, "Coordinate '{coordinate}' is invalid: {error}")
+            Self::InvalidCoordinate(coordinate, error, hint) => {
+                write!(f, "Coordinate '{coordinate}' is invalid: {error}")?;
+                if let Some(hint) = hint {
+                    write!(f, " {hint}")?;
+                }
+                Ok(())
             }
             Self::InvalidAxis(axis) => {
                 write!(f, "'{axis}' is not a valid axis.")
             }
+            Self::ZeroQuaternion(statement) => {
+                write!(
+                    f,
+                    "The quaternion in '{statement}' has a length of 0 and cannot be normalized."
+                )
+            }
             Self::InvalidEntityType(argument) => {
                 write!(
                     f,
-                    "Entity type '{argument}' is invalid. Expected one of: [{}]",
-                    Entity::TYPES.map(|s| format!("\"{s}\"")).join(", ")
+                    "Entity type '{argument}' is invalid. Expected one of: [{}], or a type listed in the config's 'entity_types'.",
+                    Entity::TYPES
+                        .iter()
+                        .zip(Entity::TYPE_ALIASES)
+                        .map(|(full, alias)| format!("\"{full}\" (\"{alias}\")"))
+                        .join(", ")
                 )
             }
             Self::InvalidEntityName(name) => {
                 write!(f, "Entity name '{name}' contains invalid characters.")
             }
+            Self::EmptyEntityName(argument) => {
+                write!(
+                    f,
+                    "Entity name '{argument}' is empty, so it would select nothing."
+                )
+            }
+            Self::EntityNameTooLong(name) => {
+                write!(
+                    f,
+                    "Entity name '{name}' is {} characters long. Names can be at most {} characters.",
+                    name.len(),
+                    Entity::MAX_NAME_LENGTH
+                )
+            }
+            Self::ReservedEntityName(name) => {
+                write!(
+                    f,
+                    "Entity name '{name}' is the object's name, which tags every entity of the object. Choose another name."
+                )
+            }
+            Self::InvalidNamespace(namespace) => {
+                write!(
+                    f,
+                    "Namespace '{namespace}' is invalid. Expected only lowercase letters, digits, '_', '-' and '.'."
+                )
+            }
+            Self::InvalidOutputName(name) => {
+                write!(
+                    f,
+                    "Output name '{name}' is invalid. Expected a file name using only lowercase letters, digits, '_', '-' and '.'."
+                )
+            }
+            Self::InvalidResourceLocation(id) => {
+                write!(
+                    f,
+                    "'{id}' is not a valid id. Expected '[namespace:]path' using only lowercase letters, digits, '_', '-', '.' and '/' in the path."
+                )
+            }
+            Self::GroupNotAllowed(name) => {
+                write!(f, "'{name}' must name a single entity, not a group.")
+            }
+            Self::InvalidPredicate(predicate) => {
+                write!(
+                    f,
+                    "Predicate '{predicate}' is invalid. Expected target selector arguments like 'scores={{active=1..}}', with balanced brackets."
+                )
+            }
+            Self::UnknownSelectorKey(key) => {
+                write!(f, "'{key}' is not a target selector argument.")
+            }
+            Self::MissingEntity(statement) => {
+                write!(
+                    f,
+                    "Statement '{statement}' leaves out its entity, but no 'with <entity>' comes before it."
+                )
+            }
+            Self::PredicateNotAllowed(name) => {
+                write!(
+                    f,
+                    "'{name}' declares an entity, so it can't have a predicate."
+                )
+            }
             Self::InvalidState(state) => {
                 write!(f, "State '{state}' is invalid.")
             }
+            Self::InvalidStateProperty(property) => {
+                write!(
+                    f,
+                    "Block state property '{property}' is invalid. Expected 'key=value', with any value that isn't a plain name in double quotes."
+                )
+            }
+            Self::DuplicateStateProperty(key) => {
+                write!(f, "Block state property '{key}' is given more than once.")
+            }
+            Self::UnknownStateProperty(property, block, properties) => {
+                write!(
+                    f,
+                    "Block '{block}' has no state property '{property}'. Expected one of: [{properties}]"
+                )
+            }
+            Self::InvalidStateValue(value, property, values) => {
+                write!(
+                    f,
+                    "'{value}' is not a valid value of block state property '{property}'. Expected one of: [{values}]"
+                )
+            }
+            Self::InvalidTagOperation(operation) => {
+                write!(
+                    f,
+                    "Tag operation '{operation}' is invalid. Expected one of: [\"add\", \"remove\"]"
+                )
+            }
+            Self::InvalidEscape(Some(character)) => {
+                write!(f, "Escape sequence '\\{character}' is invalid.")
+            }
+            Self::InvalidEscape(None) => {
+                write!(f, "Escape sequence '\\' is not followed by a character.")
+            }
+            Self::InvalidJson(error) => {
+                write!(f, "Text component is not valid JSON: {error}")
+            }
+            Self::InvalidColor(color) => {
+                write!(
+                    f,
+                    "Color '{color}' is invalid. Expected a hex code like '#FF8800' or one of: [{}]",
+                    TextColor::NAMES.map(|s| format!("\"{s}\"")).join(", ")
+                )
+            }
+            Self::NegativeStart(start) => {
+                write!(
+                    f,
+                    "Interpolation start '{start}' must not be negative, since a command cannot start interpolating in the past."
+                )
+            }
+            Self::InvalidCondition(condition) => {
+                write!(
+                    f,
+                    "Condition '{condition}' is invalid. Expected '<holder> <objective> matches <range>' or '<holder> <objective> <operation> <holder> <objective>'."
+                )
+            }
+            Self::UnknownEasing(name, names) => {
+                write!(
+                    f,
+                    "Easing '{name}' is unknown. Expected one of {names}, or a curve added under 'easings' in the config."
+                )
+            }
+            Self::EasedAxisDurations => {
+                write!(
+                    f,
+                    "A transformation with a duration for each axis can't have an easing."
+                )
+            }
+            Self::InvalidAxisDurations(durations) => {
+                write!(
+                    f,
+                    "Durations '{durations}' are invalid. Expected one for each axis, like '[20,40,20]'."
+                )
+            }
+            Self::InvalidSeconds(time) => {
+                write!(
+                    f,
+                    "Time '{time}' is not a whole number of ticks once converted, since it is negative or too large."
+                )
+            }
+            Self::NonFiniteValue(value, position) => {
+                write!(
+                    f,
+                    "Value '{value}' at {position} is not a finite number. Infinite and NaN values cannot be written to NBT."
+                )
+            }
+            Self::UnknownEntity(name) => {
+                write!(
+                    f,
+                    "Entity '{name}' is never declared. Declare it with 'entity {name}', or check it for a typo."
+                )
+            }
+            Self::UnexpectedArgument(argument) => {
+                write!(
+                    f,
+                    "Argument '{argument}' is unexpected. Check it for a misspelled modifier, or remove it."
+                )
+            }
             Self::NoAnimationName(argument) => {
                 write!(
                     f,
                     "Argument '{argument}' containes object name, but no animation name."
                 )
             }
+            Self::UnterminatedComment => {
+                write!(f, "Block comment is never closed with '*/'.")
+            }
+            Self::UnsupportedItemSyntax(item, version) if version.item_components() => {
+                write!(
+                    f,
+                    "Item '{item}' is given NBT, which 'target_version' {version} replaces with components like 'stone[custom_name=...]'. Use components, or set 'target_version' to \"1.20\"."
+                )
+            }
+            Self::UnsupportedItemSyntax(item, version) => {
+                write!(
+                    f,
+                    "Item '{item}' is given components, which 'target_version' {version} doesn't have. Use NBT like 'stone{{display:...}}', or set 'target_version' to \"1.20.5\" or later."
+                )
+            }
+            Self::MalformedCommand(statement, line, problem) => {
+                write!(
+                    f,
+                    "Statement '{statement}' generates the malformed command '{line}': {problem}"
+                )
+            }
+            Self::UnterminatedRegion(name) => {
+                write!(f, "Region '{name}' is never closed with '#endregion'.")
+            }
+            Self::UnmatchedEndRegion => {
+                write!(f, "'#endregion' has no '#region' before it to close.")
+            }
+            Self::DanglingContinuation => {
+                write!(f, "Line continuation '\\' is not followed by another line.")
+            }
         }
     }
 }
@@ -113,8 +468,59 @@ pub enum GenericError {
     InvalidPath(String, String),
     #[error("Pattern '{0}' is not a valid regex: {1}")]
     InvalidRegex(&'static str, #[source] regex::Error),
+    #[error("The file '{0}' is not valid UTF-8: found an invalid byte at offset {1}.")]
+    InvalidEncoding(String, usize),
     #[error("The file with path '{0}' does not exist.")]
     FileNotExist(String),
-    #[error("Could to compile one or more files due to errors:\n{0}")]
-    Collection(String),
+    #[error("Config file '{0}' is invalid: {1}")]
+    InvalidConfig(String, String),
+    #[error("Config field '{0}' is invalid ('{1}'): {2}")]
+    InvalidConfigField(&'static str, String, String),
+    #[error("Function tag '{0}' is invalid: {1}")]
+    InvalidFunctionTag(String, String),
+    #[error("Exclude pattern '{0}' is not a valid glob: {1}")]
+    InvalidGlob(String, #[source] globset::Error),
+    #[error("Multiple source files would be compiled to '{0}'.")]
+    OutputCollision(String),
+    #[error("Multiple source files declare the animation '{0}'.")]
+    AnimationCollision(String),
+    #[error("The file '{0}' declares the animation '{1}' in more than one section.")]
+    DuplicateSection(String, String),
+    #[error("{0} generated files differ from the ones on disk.")]
+    OutputsDiffer(usize),
+    #[error("Warnings were denied with '--deny-warnings':\n{0}")]
+    DeniedWarnings(String),
+    #[error("Config file '{0}' already exists. Pass '--force' to overwrite it.")]
+    ConfigExists(String),
+    #[error(
+        "'--jobs' takes how many files to compile at once, which is a number above 0, not '{0}'."
+    )]
+    InvalidJobs(String),
+    #[error(
+        "'--clean' finds generated files by their header, so it can't run while 'header' is empty."
+    )]
+    NoGeneratedMarker,
+    #[error(
+        "The namespace '{0}' can't be used with the target folder '{1}', which has no 'data/{2}' folder to put it in instead."
+    )]
+    UnplacedNamespace(String, String, String),
+    #[error("Missing argument. Usage: {0}")]
+    Usage(&'static str),
+}
+
+/// Every error from a batch of results, each with the index of the result it came from. The errors keep
+/// their types, so they can still be inspected after being collected.
+#[derive(Debug)]
+pub struct ErrorCollection<E> {
+    pub errors: Vec<(usize, E)>,
+}
+impl<E: Display> Display for ErrorCollection<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Could to compile one or more files due to errors:")?;
+        for (index, error) in &self.errors {
+            writeln!(f, "{index}: {error}")?;
+        }
+        Ok(())
+    }
 }
+impl<E: Display + std::fmt::Debug> std::error::Error for ErrorCollection<E> {}
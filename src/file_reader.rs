@@ -1,37 +1,169 @@
-use std::{fs, path::Path};
+use std::{collections::HashSet, fs, path::Path};
+
+use anyhow::ensure;
+use log::{debug, trace};
 
 use crate::{
     compiled::{self, CompiledFile},
+    config::Config,
     errors::GenericError,
-    objects::TrackedChar,
-    statements::{FileInfo, Program},
+    objects::{Position, TrackedChar},
+    statements::{self, FileInfo, Program},
 };
 
-pub fn parse_file(file_path: &str) -> anyhow::Result<CompiledFile> {
-    let contents = fs::read_to_string(file_path)
-        .map_err(|err| GenericError::InvalidPath(file_path.to_string(), err.to_string()))?
-        .replace('\r', "");
+/// Reads and compiles the source at `file_path`.
+///
+/// # Errors
+/// If the file can't be read or isn't UTF-8, this returns a `GenericError`. Otherwise, errors in the
+/// source are returned as a `CompileError`, or an `ErrorCollection` of them.
+pub fn parse_file(file_path: &str, config: &Config) -> anyhow::Result<CompiledFile> {
+    debug!("Compiling {file_path}");
+    let bytes = fs::read(file_path)
+        .map_err(|err| GenericError::InvalidPath(file_path.to_string(), err.to_string()))?;
+    compile_str(&decode(bytes, file_path)?, file_path, config)
+}
+
+/// Compiles only the directives that name the file's animations, for `--list`. Its other statements
+/// aren't parsed, so they aren't checked for errors either.
+///
+/// # Errors
+/// The same as `parse_file`, for the errors the directives can have.
+pub fn parse_header(file_path: &str, config: &Config) -> anyhow::Result<CompiledFile> {
+    let bytes = fs::read(file_path)
+        .map_err(|err| GenericError::InvalidPath(file_path.to_string(), err.to_string()))?;
+    compile_str(
+        &header(&decode(bytes, file_path)?, file_path)?,
+        file_path,
+        config,
+    )
+}
+
+/// Stands in for the statements between directives, so a later `object` line still starts a section.
+const HEADER_PLACEHOLDER: &str = "/say";
+
+/// The lines of `contents` that are blank, comments, or name an animation and its output, with each run
+/// of other statements replaced by a placeholder. Block comments and continued lines are resolved first,
+/// as they are when the file is parsed.
+fn header(contents: &str, file_path: &str) -> anyhow::Result<String> {
+    let contents = contents.strip_prefix('\u{FEFF}').unwrap_or(contents);
+    let chars = to_tracked(&contents.replace('\r', ""));
+    let file_info = FileInfo::new(file_path.to_string(), end_of(&chars));
+    let chars = statements::strip_block_comments(&file_info, &chars)?;
+    let contents: String = statements::join_continuations(&file_info, &chars)?
+        .iter()
+        .map(|tracked| tracked.character)
+        .collect();
+    let mut lines = Vec::new();
+    for line in contents.lines() {
+        let directive = line.split_whitespace().next().is_none_or(|keyword| {
+            keyword.starts_with('#')
+                || matches!(keyword, "object" | "anim" | "namespace" | "output")
+        });
+        if directive {
+            lines.push(line);
+        } else if lines.last() != Some(&HEADER_PLACEHOLDER) {
+            lines.push(HEADER_PLACEHOLDER);
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Reads `bytes` as UTF-8, reporting where the first invalid byte is rather than only that there is one.
+fn decode(bytes: Vec<u8>, file_path: &str) -> anyhow::Result<String> {
+    let contents = String::from_utf8(bytes).map_err(|err| {
+        GenericError::InvalidEncoding(file_path.to_string(), err.utf8_error().valid_up_to())
+    })?;
+    Ok(contents)
+}
+
+/// Compiles `contents` as though it had been read from `file_path`, which names the animation
+/// and is used in error messages.
+///
+/// # Errors
+/// Errors in the source are returned as a `CompileError`, or an `ErrorCollection` of them.
+pub fn compile_str(
+    contents: &str,
+    file_path: &str,
+    config: &Config,
+) -> anyhow::Result<CompiledFile> {
+    // Some editors start UTF-8 files with a byte order mark, which would otherwise end up in the first keyword.
+    let contents = contents.strip_prefix('\u{FEFF}').unwrap_or(contents);
+    let contents = contents.replace('\r', "");
     let chars = to_tracked(&contents);
     let program = Program::parse_from_file(
-        &FileInfo::new(
-            file_path.to_string(),
-            TrackedChar::new(
-                contents.chars().filter(|&c| c == '\n').count(),
-                contents.lines().last().map_or(0, str::len),
-                contents.chars().last().unwrap_or('\n'),
-            ),
-        ),
+        &FileInfo::new(file_path.to_string(), end_of(&chars)),
         &chars,
+        config,
     );
 
-    // println!("{program:#?}");
-    Ok(compiled::program(
-        program?,
-        &get_file_name(file_path)?,
-        file_path,
-    ))
+    let program = program?;
+    trace!("Parsed {file_path}: {program:#?}");
+    let file_name = get_file_name(file_path)?;
+    let (first, sections) = program.sections();
+    if !sections.is_empty() {
+        debug!(
+            "{file_path} is split into {} animations",
+            sections.len() + 1
+        );
+    }
+    let first = compile_section(first, &file_name, file_path, config);
+    let sections: Vec<CompiledFile> = sections
+        .into_iter()
+        .map(|section| compile_section(section, &file_name, file_path, config))
+        .collect();
+    // Each section gets its own timer, so no two can share an animation name.
+    let mut seen = HashSet::new();
+    for section in std::iter::once(&first).chain(&sections) {
+        let key = format!("{}:{}", section.object_name, section.animation_name);
+        ensure!(
+            seen.insert(key.clone()),
+            GenericError::DuplicateSection(file_path.to_string(), key)
+        );
+    }
+    let mut first = CompiledFile { sections, ..first };
+    crate::collect_errors(malformed(&mut first))?;
+    Ok(first)
 }
 
+/// Takes every malformed command `verify` found in the file, its sections and their reversed copies.
+fn malformed(file: &mut CompiledFile) -> Vec<anyhow::Result<()>> {
+    let mut malformed: Vec<anyhow::Result<()>> = std::mem::take(&mut file.malformed)
+        .into_iter()
+        .map(|error| Err(error.into()))
+        .collect();
+    for nested in file
+        .reverse
+        .iter_mut()
+        .map(AsMut::as_mut)
+        .chain(&mut file.sections)
+    {
+        malformed.extend(self::malformed(nested));
+    }
+    malformed
+}
+
+/// Compiles one animation of a file, with its reversed copy if it asks for one.
+fn compile_section(
+    program: Program,
+    file_name: &str,
+    file_path: &str,
+    config: &Config,
+) -> CompiledFile {
+    let reverse = program.has_reverse().then(|| {
+        // The reversed copy repeats the original's warnings, so only the original reports them.
+        let reversed = compiled::program(program.reversed(file_name), file_name, file_path, config);
+        Box::new(CompiledFile {
+            warnings: Vec::new(),
+            ..reversed
+        })
+    });
+    CompiledFile {
+        reverse,
+        ..compiled::program(program, file_name, file_path, config)
+    }
+}
+
+#[must_use]
 pub fn to_tracked(string: &str) -> Vec<TrackedChar> {
     string
         .split_inclusive('\n')
@@ -46,6 +178,19 @@ pub fn to_tracked(string: &str) -> Vec<TrackedChar> {
         .collect()
 }
 
+/// The position just past the last character, on the line after it if it ends the line, so it agrees
+/// with the positions `to_tracked` gives whether or not the file ends in a newline.
+fn end_of(chars: &[TrackedChar]) -> TrackedChar {
+    chars.last().map_or(TrackedChar::new(1, 1, '\n'), |last| {
+        let Position { line, column } = last.position;
+        if last.character == '\n' {
+            TrackedChar::new(line + 1, 1, '\n')
+        } else {
+            TrackedChar::new(line, column + 1, last.character)
+        }
+    })
+}
+
 fn get_file_name(path: &str) -> anyhow::Result<String> {
     let file_name = Path::new(path)
         .file_stem()
@@ -53,3 +198,1072 @@ fn get_file_name(path: &str) -> anyhow::Result<String> {
         .to_string_lossy();
     Ok(file_name.into_owned())
 }
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        iter,
+    };
+
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::{
+        compiled::TransformKind,
+        config::{Retrigger, TargetVersion},
+        easing::Curve,
+    };
+
+    fn compile(contents: &str) -> String {
+        compile_str(contents, "src/anim.dspa", &Config::default())
+            .unwrap()
+            .contents
+    }
+
+    fn at(entity: &str, tick: u32, command: &str) -> String {
+        format!(
+            "execute as @e[tag=obj,tag={entity}] if score $obj-anim timer matches {tick} run {command}"
+        )
+    }
+
+    /// Runs `contents` for `ticks` ticks the way the game would, starting from `scores`, and returns
+    /// the commands other than `scoreboard` ones run on each tick. `scores` maps `holder objective` to
+    /// a value, and `on_tick` can change them before each tick. Like the tick function, the file only
+    /// runs while `$obj-anim flags` is set. Selectors are ignored.
+    fn simulate(
+        contents: &str,
+        ticks: u32,
+        scores: &mut HashMap<String, i32>,
+        on_tick: impl Fn(u32, &mut HashMap<String, i32>),
+    ) -> Vec<(u32, String)> {
+        let matches = |value: Option<&i32>, range: &str| {
+            let Some(&value) = value else { return false };
+            let (min, max) = range.split_once("..").unwrap_or((range, range));
+            min.parse().map_or(true, |min: i32| value >= min)
+                && max.parse().map_or(true, |max: i32| value <= max)
+        };
+        let mut ran = Vec::new();
+        for tick in 0..ticks {
+            on_tick(tick, scores);
+            if !matches(scores.get("$obj-anim flags"), "1..") {
+                continue;
+            }
+            for line in contents.lines().filter(|line| !line.is_empty()) {
+                let mut words = line.split_whitespace();
+                let mut passes = true;
+                if line.starts_with("execute ") {
+                    words.next();
+                    while let Some(word) = words.next() {
+                        match word {
+                            "as" => {
+                                words.next();
+                            }
+                            "if" | "unless" => {
+                                let [_, holder, objective, _, range] =
+                                    [(); 5].map(|()| words.next().unwrap());
+                                let score = scores.get(&format!("{holder} {objective}"));
+                                passes &= matches(score, range) == (word == "if");
+                            }
+                            "run" => break,
+                            _ => panic!("unsupported execute argument '{word}' in '{line}'"),
+                        }
+                    }
+                }
+                let command = words.join(" ");
+                if !passes {
+                    continue;
+                }
+                let arguments = command.split_whitespace().collect_vec();
+                match arguments[..] {
+                    ["scoreboard", "players", "set", holder, objective, value] => {
+                        scores.insert(format!("{holder} {objective}"), value.parse().unwrap());
+                    }
+                    ["scoreboard", "players", "add", holder, objective, value] => {
+                        *scores.entry(format!("{holder} {objective}")).or_default() +=
+                            value.parse::<i32>().unwrap();
+                    }
+                    ["scoreboard", "players", "operation", holder, objective, "=", source, source_objective] =>
+                    {
+                        let value = scores[&format!("{source} {source_objective}")];
+                        scores.insert(format!("{holder} {objective}"), value);
+                    }
+                    _ => ran.push((tick, command)),
+                }
+            }
+        }
+        ran
+    }
+
+    fn parse_line_fails(line: &str) -> bool {
+        compile_str(line, "src/anim.dspa", &Config::default()).is_err()
+    }
+
+    fn tail(length: u32) -> String {
+        format!(
+            "\n\
+            execute if score $obj-anim timer matches {length}.. run scoreboard players set $obj-anim flags 0\n\
+            execute if score $obj-anim timer matches {length}.. run scoreboard players set $obj-anim timer -1\n\
+            \n\
+            scoreboard players add $obj-anim timer 1"
+        )
+    }
+
+    #[test]
+    fn transformations_compile_to_data_merges() {
+        let expected = [
+            at("test", 0, "data merge entity @s {start_interpolation:0,interpolation_duration:20,transformation:{translation: [0f,1f,0f]}}"),
+            at("test", 0, "data merge entity @s {start_interpolation:0,interpolation_duration:20,transformation:{left_rotation: [0f,0.70710677f,0f,0.70710677f]}}"),
+            at("test", 0, "data merge entity @s {start_interpolation:0,interpolation_duration:10,transformation:{scale: [2f,2f,2f]}}"),
+            at("test", 0, "data merge entity @s {start_interpolation:5,interpolation_duration:10,transformation:{translation: [1f,0f,0f]}}"),
+            tail(0),
+        ]
+        .join("\n");
+        assert_eq!(
+            compile("object obj:anim\nmove test 0 1 0 20\nturn test y 90 20\nsize test 2 10\nmove test 1 0 0 10 start 5"),
+            expected
+        );
+    }
+
+    #[test]
+    fn predicates_narrow_the_selector() {
+        let expected = [
+            at("test,scores={active=1..}", 0, "data merge entity @s {start_interpolation:0,interpolation_duration:20,transformation:{translation: [0f,1f,0f]}}"),
+            at("test,scores={active=1..}", 0, "tag @s add moved"),
+            at("test", 0, "data merge entity @s {start_interpolation:0,interpolation_duration:10,transformation:{scale: [2f,2f,2f]}}"),
+            tail(0),
+        ]
+        .join("\n");
+        assert_eq!(
+            compile("object obj:anim\nmove test[scores={active=1..}] 0 1 0 20\ntag test[scores={active=1..}] add moved\nsize test 2 10"),
+            expected
+        );
+    }
+
+    #[test]
+    fn entities_compile_with_their_contents() {
+        let expected = [
+            "execute as @e[tag=obj,tag=test] at @s if score $obj-anim timer matches 0 run summon block_display ~ ~ ~ {Tags:[\"obj\",\"child\"]}".to_string(),
+            "execute as @e[tag=obj,tag=child] at @s if score $obj-anim timer matches 0 run ride @s mount @e[tag=obj,tag=test,limit=1,sort=nearest]".to_string(),
+            at("child", 0, "data merge entity @s {block_state:{Name:\"oak_log\",Properties:{axis:\"y\"}}}"),
+            at("item", 0, "item replace entity @s contents with diamond_sword[damage=5]"),
+            at("label", 0, "data merge entity @s {text:'\"Hello there\"'}"),
+            "execute as @e[tag=obj,tag=label] at @s if score $obj-anim timer matches 0 run tp @s ~0 ~1.5 ~0".to_string(),
+            tail(0),
+        ]
+        .join("\n");
+        assert_eq!(
+            compile(
+                "object obj:anim\n\
+                spawn test block_display child mounted\n\
+                block child oak_log[axis=y]\n\
+                item item diamond_sword[damage=5]\n\
+                text label \"Hello there\"\n\
+                tp label 0 1.5 0"
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn waits_delay_later_statements() {
+        let expected = [
+            at("test", 0, "data merge entity @s {start_interpolation:0,interpolation_duration:20,transformation:{translation: [0f,1f,0f]}}"),
+            at("test", 20, "data merge entity @s {start_interpolation:0,interpolation_duration:20,transformation:{translation: [0f,0f,0f]}}"),
+            "execute if score $obj-anim timer matches 25 run say done".to_string(),
+            "say immediately".to_string(),
+            tail(25),
+        ]
+        .join("\n");
+        assert_eq!(
+            compile(
+                "object obj:anim\n\
+                move test 0 1 0 20\n\
+                wait 20\n\
+                move test 0 0 0 20\n\
+                wait 5\n\
+                /say done\n\
+                //say immediately"
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn file_name_names_the_animation_by_default() {
+        let compiled = compile_str("wait 1", "src/anim.dspa", &Config::default()).unwrap();
+        assert_eq!(compiled.object_name, "anim");
+        assert_eq!(compiled.animation_name, "anim");
+        assert_eq!(
+            compiled::tick_function_line(
+                &Config::default(),
+                &compiled.object_name,
+                &compiled.animation_name,
+                "de:objects/anim"
+            ),
+            "execute if score $anim-anim flags matches 1.. run function de:objects/anim"
+        );
+    }
+
+    #[test]
+    fn consecutive_duplicates_collapse() {
+        let source =
+            "object obj:anim\ntag test add glow\ntag test add glow\nwait 1\ntag test add glow";
+        let expected = [
+            at("test", 0, "tag @s add glow"),
+            at("test", 1, "tag @s add glow"),
+            tail(1),
+        ]
+        .join("\n");
+        assert_eq!(compile(source), expected);
+
+        let config = Config {
+            dedupe: false,
+            ..Config::default()
+        };
+        let compiled = compile_str(source, "src/anim.dspa", &config).unwrap();
+        assert_eq!(compiled.contents.matches("tag @s add glow").count(), 3);
+    }
+
+    #[test]
+    fn byte_order_mark_is_ignored() {
+        assert_eq!(compile("\u{FEFF}wait 1"), compile("wait 1"));
+    }
+
+    #[test]
+    fn invalid_utf8_reports_the_offset() {
+        let error = decode(b"wait 1\n\xFFwait 2".to_vec(), "src/anim.dspa").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The file 'src/anim.dspa' is not valid UTF-8: found an invalid byte at offset 7."
+        );
+        assert_eq!(
+            decode(b"wait 1".to_vec(), "src/anim.dspa").unwrap(),
+            "wait 1"
+        );
+    }
+
+    #[test]
+    fn overlapping_interpolations_of_one_kind_warn() {
+        let source = "move cube 1 0 0 10\n\
+                      turn cube y 90 10\n\
+                      wait 5\n\
+                      move cube 0 1 0 10\n\
+                      move other 0 1 0 10\n\
+                      wait 10\n\
+                      move cube 0 0 1 10";
+        let compiled = compile_str(source, "src/anim.dspa", &Config::default()).unwrap();
+        assert_eq!(compiled.warnings.len(), 1);
+        let warning = compiled.warnings[0].to_string();
+        assert!(warning.contains("Line: 4"), "{warning}");
+        assert!(
+            warning.contains(
+                "This translation of 'cube' starts before the translation at 1:1 has finished"
+            ),
+            "{warning}"
+        );
+
+        let starts = compiled.timelines["cube"]
+            .iter()
+            .map(|interpolation| (interpolation.start, interpolation.kind))
+            .collect_vec();
+        assert_eq!(
+            starts,
+            [
+                (0, TransformKind::Translation),
+                (0, TransformKind::Rotation),
+                (5, TransformKind::Translation),
+                (15, TransformKind::Translation),
+            ]
+        );
+    }
+
+    #[test]
+    fn delayed_start_counts_towards_overlap() {
+        let source = "move cube 1 0 0 10\nmove cube 0 1 0 10 start 10";
+        let compiled = compile_str(source, "src/anim.dspa", &Config::default()).unwrap();
+        assert!(compiled.warnings.is_empty());
+        assert_eq!(compiled.timelines["cube"][1].start, 10);
+    }
+
+    #[test]
+    fn interpolations_played_out_of_file_order_warn() {
+        let source = "move +20 cube 1 0 0 10\nmove cube 0 1 0 10\nsize other 2 5\nturn cube y 90 5";
+        let compiled = compile_str(source, "src/anim.dspa", &Config::default()).unwrap();
+        assert_eq!(compiled.warnings.len(), 1);
+        let warning = compiled.warnings[0].to_string();
+        assert!(warning.contains("Line: 2, Column: 1"), "{warning}");
+        assert!(
+            warning.contains("This translation of 'cube' plays before the translation at 1:1, which comes first in the file."),
+            "{warning}"
+        );
+        let layered = "move +20 cube 1 0 0 10\nmove other 0 1 0 10\nsize cube 2 5";
+        assert!(compile_str(layered, "src/anim.dspa", &Config::default())
+            .unwrap()
+            .warnings
+            .is_empty());
+    }
+
+    #[test]
+    fn long_commands_warn_unless_disabled() {
+        let source = "text cube \"A short line\"\nmove cube 0 1 0 20";
+        let config = Config {
+            max_command_length: Some(150),
+            ..Config::default()
+        };
+        let compiled = compile_str(source, "src/anim.dspa", &config).unwrap();
+        assert_eq!(compiled.warnings.len(), 1);
+        let warning = compiled.warnings[0].to_string();
+        assert!(warning.contains("Line: 2, Column: 1"), "{warning}");
+        assert!(
+            warning.contains("longer than the configured 'max_command_length' of 150"),
+            "{warning}"
+        );
+        let config = Config {
+            max_command_length: None,
+            ..config
+        };
+        assert!(compile_str(source, "src/anim.dspa", &config)
+            .unwrap()
+            .warnings
+            .is_empty());
+    }
+
+    #[test]
+    fn kept_comments_reach_the_output() {
+        let config = Config {
+            single_file: Some("./all.mcfunction".to_string()),
+            ..Config::default()
+        };
+        let source = "  #! Door swings open\nmove cube 0 1 0 20 # dropped\n#!\ntext cube \"#!not a comment\"";
+        let compiled = compile_str(source, "src/anim.dspa", &config).unwrap();
+        let lines: Vec<&str> = compiled.contents.lines().collect();
+        assert_eq!(lines[0], "# Door swings open");
+        assert!(!lines[1].contains("dropped"));
+        assert_eq!(lines[2], "#");
+        assert!(lines[3].contains("#!not a comment"), "{}", lines[3]);
+        assert_eq!(compiled.statement_count, 2);
+    }
+
+    #[test]
+    fn commands_are_counted_by_kind() {
+        let source = "spawn root block cube\nmove cube 0 1 0 20\nturn cube y 720 40\nwait 5\ntag cube add glow";
+        let compiled = compile_str(source, "src/anim.dspa", &Config::default()).unwrap();
+        let counts = &compiled.command_counts;
+        assert_eq!(counts["spawn"], 1);
+        assert_eq!(counts["tag"], 1);
+        assert!(counts["transform"] > 2, "{counts:?}");
+        assert_eq!((counts["reset"], counts["increment"]), (2, 1));
+        let lines = compiled.contents.lines().filter(|line| !line.is_empty());
+        assert_eq!(counts.values().sum::<usize>(), lines.count());
+    }
+
+    #[test]
+    fn timelines_serialize_for_tooling() {
+        let compiled =
+            compile_str("wait 2\nsize cube 2 5", "src/anim.dspa", &Config::default()).unwrap();
+        assert_eq!(
+            serde_json::to_string(&compiled.timelines).unwrap(),
+            r#"{"cube":[{"start":2,"duration":5,"kind":"scale","position":{"line":2,"column":1}}]}"#
+        );
+    }
+
+    #[test]
+    fn report_lists_events_by_tick() {
+        let source =
+            "move cube 0 1 0 20\nwait 5\nturn cube y 90 10 start 2\nhold cube 5\ncmd say hi";
+        let compiled = compile_str(source, "src/anim.dspa", &Config::default()).unwrap();
+        assert_eq!(
+            compiled.report,
+            "tick 0: translate cube -> (0, 1, 0) over 20t\n\
+             tick 5: rotate cube by 90deg about [0, 1, 0] over 10t, starting 2t later\n\
+             tick 5: hold cube for 5t\n\
+             tick 5: command say hi"
+        );
+    }
+
+    #[test]
+    fn offset_delays_one_statement_only() {
+        let compiled = compile_str(
+            "move +5 cube 0 1 0 20\nsize cube 2 5\nturn +1s cube y 90 10",
+            "src/anim.dspa",
+            &Config::default(),
+        )
+        .unwrap();
+        let lines: Vec<&str> = compiled.contents.lines().collect();
+        assert!(
+            lines[0].contains("matches 5 run data merge"),
+            "{}",
+            lines[0]
+        );
+        assert!(
+            lines[1].contains("matches 0 run data merge"),
+            "{}",
+            lines[1]
+        );
+        assert!(
+            lines[2].contains("matches 20 run data merge"),
+            "{}",
+            lines[2]
+        );
+        assert_eq!(compiled.timelines["cube"][0].start, 5);
+        assert!(compiled.report.starts_with("tick 5: translate cube"));
+        assert_eq!(
+            compile_str("move +0 cube 0 1 0 20", "src/anim.dspa", &Config::default())
+                .unwrap()
+                .contents,
+            compile("move cube 0 1 0 20")
+        );
+    }
+
+    #[test]
+    fn offsets_past_the_last_wait_delay_the_reset() {
+        let compiled = compile_str(
+            "object a:b\nmove +30 cube 0 1 0 20",
+            "src/anim.dspa",
+            &Config::default(),
+        )
+        .unwrap();
+        assert_eq!(compiled.length, 30);
+        assert!(compiled.contents.contains("matches 30 run data merge"));
+        assert!(
+            compiled.contents.contains(
+                "if score $a-b timer matches 30.. run scoreboard players set $a-b flags 0"
+            ),
+            "{}",
+            compiled.contents
+        );
+    }
+
+    #[test]
+    fn macros_expand_to_the_same_merges() {
+        let source = "move cube 0 1 0 20\nwait 5\nblock cube stone\nsize cube 2 5\nmove lamp 0 1 0 5\ntag cube add glow";
+        let config = Config {
+            macros: true,
+            ..Config::default()
+        };
+        let plain = compile(source);
+        let macros = compile_str(source, "src/anim.dspa", &config)
+            .unwrap()
+            .contents;
+        let template = compiled::merge_macro();
+        for (plain, invocation) in plain.lines().zip(macros.lines()) {
+            let Some((gate, arguments)) = invocation.split_once(" run function de:tick_merge ")
+            else {
+                assert_eq!(plain, invocation);
+                continue;
+            };
+            let arguments = arguments
+                .strip_prefix("{object:\"anim\",entity:\"cube\",nbt:")
+                .and_then(|rest| rest.strip_suffix('}'))
+                .unwrap();
+            let expanded = template
+                .trim_start_matches('$')
+                .replace("$(object)", "anim")
+                .replace("$(entity)", "cube")
+                .replace("$(nbt)", arguments);
+            let (selector, merge) = expanded.split_once(" run ").unwrap();
+            let condition = gate.strip_prefix("execute ").unwrap();
+            assert_eq!(plain, format!("{selector} {condition} run {merge}"));
+        }
+        assert_eq!(plain.lines().count(), macros.lines().count());
+        // Only the cube repeats its merge often enough to be worth the macro.
+        assert_eq!(macros.matches("run function de:tick_merge").count(), 3);
+        assert!(macros.contains("tag=lamp] if score $anim-anim timer matches 5 run data merge"));
+        assert!(macros.contains("run tag @s add glow"));
+        let gated = compile_str(
+            source,
+            "src/anim.dspa",
+            &Config {
+                single_file: Some("merged.mcfunction".to_string()),
+                macros: true,
+                ..Config::default()
+            },
+        )
+        .unwrap()
+        .contents;
+        assert_eq!(
+            gated
+                .matches("execute if score $anim-anim flags matches 1.. run execute if score $anim-anim timer matches ")
+                .count(),
+            3
+        );
+
+        let quoted = compile_str(
+            &"move cube[name=\"Door\"] 0 1 0 20\nwait 20\n".repeat(3),
+            "src/anim.dspa",
+            &config,
+        )
+        .unwrap()
+        .contents;
+        assert!(
+            quoted.contains(",entity:\"cube,name=\\\"Door\\\"\",nbt:"),
+            "{quoted}"
+        );
+    }
+
+    #[test]
+    fn reverse_plays_the_animation_backwards() {
+        let source = "object door:open\nreverse\nmove cube 0 1 0 20\nwait 10\nsize +2 cube 2 5\ntag cube add open";
+        let compiled = compile_str(source, "src/anim.dspa", &Config::default()).unwrap();
+        assert_eq!(compiled.animation_name, "open");
+        let reverse = compiled.reverse.unwrap();
+        assert_eq!(
+            (
+                reverse.object_name.as_str(),
+                reverse.animation_name.as_str()
+            ),
+            ("door", "open_reverse")
+        );
+        assert_eq!(reverse.length, 20);
+        let expected = "object door:open_reverse\nmove cube 0 0 0 20\nwait 3\nsize cube 1 5\nwait 7\ntag cube remove open\nwait 10";
+        assert_eq!(reverse.contents, compile(expected));
+
+        let source = "object door:open\nreverse\nspawn cube item_display lid\nitem lid stone\nwait 4\nitem lid 2 dirt sand\nwait 6\ntext sign \"Open\"\ncolor sign red";
+        let reverse = compile_str(source, "src/anim.dspa", &Config::default())
+            .unwrap()
+            .reverse
+            .unwrap();
+        let expected = "object door:open_reverse\ncolor sign white\ntext sign \"\"\nwait 4\nitem lid 2 dirt stone\nwait 6\nitem lid air";
+        assert_eq!(reverse.contents, compile(expected));
+        assert!(
+            compile_str("move cube 0 1 0 20", "src/anim.dspa", &Config::default())
+                .unwrap()
+                .reverse
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn per_axis_durations_add_a_keyframe_as_each_axis_finishes() {
+        let expected = [
+            at("cube", 0, "data merge entity @s {start_interpolation:0,interpolation_duration:10,transformation:{translation: [1f,0f,0f]}}"),
+            at("cube", 0, "data merge entity @s {start_interpolation:0,interpolation_duration:10,transformation:{translation: [4f,2f,1f]}}"),
+            at("cube", 10, "data merge entity @s {start_interpolation:0,interpolation_duration:10,transformation:{translation: [4f,4f,2f]}}"),
+            at("cube", 20, "data merge entity @s {start_interpolation:0,interpolation_duration:20,transformation:{translation: [4f,4f,4f]}}"),
+            at("cube", 0, "data merge entity @s {start_interpolation:0,interpolation_duration:20,transformation:{scale: [2f,2f,2f]}}"),
+            at("cube", 20, "data merge entity @s {start_interpolation:0,interpolation_duration:20,transformation:{scale: [2f,3f,2f]}}"),
+            tail(20),
+        ]
+        .join("\n");
+        assert_eq!(
+            compile("object obj:anim\nmove cube 1 0 0 10\nmove cube [10,20,40] 4 4 4\nsize cube [20,40,20] 2 3 2"),
+            expected
+        );
+
+        let delayed = [
+            at("cube", 5, "data merge entity @s {start_interpolation:0,interpolation_duration:10,transformation:{translation: [2f,1f,1f]}}"),
+            at("cube", 15, "data merge entity @s {start_interpolation:0,interpolation_duration:10,transformation:{translation: [2f,2f,2f]}}"),
+            tail(15),
+        ]
+        .join("\n");
+        assert_eq!(
+            compile("object obj:anim\nmove cube [10,20,20] 2 2 2 start 5"),
+            delayed
+        );
+    }
+
+    #[test]
+    fn easings_split_the_transformation_along_their_curve() {
+        let config = Config {
+            easings: BTreeMap::from([("rush".to_string(), Curve::Table(vec![0.0, 1.0, 1.0]))]),
+            ..Config::default()
+        };
+        let keyframe = |y: u32| {
+            format!("data merge entity @s {{start_interpolation:0,interpolation_duration:1,transformation:{{translation: [0f,{y}f,0f]}}}}")
+        };
+        let source = "object obj:anim\nreverse\nmove cube 0 4 0 4 rush";
+        let compiled = compile_str(source, "src/anim.dspa", &config).unwrap();
+        let expected = [
+            at("cube", 0, &keyframe(2)),
+            at("cube", 1, &keyframe(4)),
+            at("cube", 2, &keyframe(4)),
+            at("cube", 3, &keyframe(4)),
+            tail(3),
+        ]
+        .join("\n");
+        assert_eq!(compiled.contents, expected);
+        let delayed = compile_str(
+            "object obj:anim\nmove cube 0 4 0 4 rush start 5",
+            "src/anim.dspa",
+            &config,
+        )
+        .unwrap();
+        let expected = [
+            at("cube", 5, &keyframe(2)),
+            at("cube", 6, &keyframe(4)),
+            at("cube", 7, &keyframe(4)),
+            at("cube", 8, &keyframe(4)),
+            tail(8),
+        ]
+        .join("\n");
+        assert_eq!(delayed.contents, expected);
+
+        let reverse = compiled.reverse.unwrap();
+        assert_eq!(
+            reverse.contents,
+            compile("object obj:anim_reverse\nmove cube 0 0 0 4\nwait 4")
+        );
+    }
+
+    #[test]
+    fn frames_swap_in_turn_without_moving_the_delay() {
+        let expected = [
+            at("test", 0, "item replace entity @s contents with stone"),
+            at("test", 4, "item replace entity @s contents with dirt"),
+            at("test", 8, "item replace entity @s contents with sand"),
+            at(
+                "test",
+                2,
+                "data merge entity @s {block_state:{Name:\"oak_log\",Properties:{axis:\"x\"}}}",
+            ),
+            at(
+                "test",
+                5,
+                "data merge entity @s {block_state:{Name:\"oak_log\",Properties:{axis:\"y\"}}}",
+            ),
+            tail(8),
+        ]
+        .join("\n");
+        assert_eq!(
+            compile("object obj:anim\nitem test 4 stone dirt sand\nwait 2\nblock test 3 oak_log[axis=x] oak_log[axis=y]"),
+            expected
+        );
+    }
+
+    #[test]
+    fn looping_animations_keep_playing_when_they_end() {
+        let move_line = at(
+            "cube",
+            0,
+            "data merge entity @s {start_interpolation:0,interpolation_duration:20,transformation:{translation: [0f,1f,0f]}}",
+        );
+        let looped = [
+            move_line.clone(),
+            "\n\
+            execute if score $obj-anim timer matches 20.. run scoreboard players set $obj-anim timer -1\n\
+            \n\
+            scoreboard players add $obj-anim timer 1"
+                .to_string(),
+        ]
+        .join("\n");
+        assert_eq!(
+            compile("object obj:anim\nloop\nmove cube 0 1 0 20\nwait 20"),
+            looped
+        );
+        let once = [move_line, tail(20)].join("\n");
+        assert_eq!(
+            compile("object obj:anim\nmove cube 0 1 0 20\nwait 20"),
+            once
+        );
+        assert_eq!(
+            compile("object obj:anim\nloop\nmove cube 0 1 0 20\nwait 20\nonce"),
+            once
+        );
+
+        let compiled = compile_str(
+            "object obj:anim\nloop\nreverse\nmove cube 0 1 0 20\nobject obj:other\nmove cube 0 1 0 20\nwait 20",
+            "src/anim.dspa",
+            &Config::default(),
+        )
+        .unwrap();
+        let stops = |file: &CompiledFile| file.contents.contains("flags 0");
+        assert!(!stops(&compiled));
+        assert!(!stops(compiled.reverse.as_deref().unwrap()));
+        assert!(stops(&compiled.sections[0]));
+        assert!(parse_line_fails("loop forever"));
+
+        let unwaited = compile_str(
+            "object obj:anim\nloop\nmove cube 0 1 0 20",
+            "src/anim.dspa",
+            &Config::default(),
+        )
+        .unwrap();
+        assert_eq!(unwaited.length, 20);
+        assert_eq!(unwaited.contents, looped);
+    }
+
+    #[test]
+    fn files_without_a_trailing_newline_end_on_their_last_line() {
+        let error = compile_str("move door 0 1", "src/anim.dspa", &Config::default())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("Line: 1, Column: 5"), "{error}");
+        let end = |contents: &str| end_of(&to_tracked(contents)).position;
+        assert_eq!(end("wait 1\nwait 20"), Position { line: 2, column: 8 });
+        assert_eq!(end("wait 1\nwait 20\n"), Position { line: 3, column: 1 });
+        assert_eq!(end(""), Position { line: 1, column: 1 });
+    }
+
+    #[test]
+    fn dotted_names_are_kept_in_tags_and_scores() {
+        let source = "object robot.arm:wave.up\nmove arm.left 0 1 0 20\nmove arm_left 0 1 0 20\nblock stone stone[axis=y]";
+        assert!(compile_str(source, "src/anim.dspa", &Config::default()).is_err());
+        let config = Config {
+            dotted_names: true,
+            ..Config::default()
+        };
+        let compiled = compile_str(source, "src/anim.dspa", &config)
+            .unwrap()
+            .contents;
+        assert!(compiled.contains(
+            "execute as @e[tag=robot.arm,tag=arm.left] if score $robot.arm-wave.up timer matches 0 run"
+        ));
+        assert!(compiled.contains("execute as @e[tag=robot.arm,tag=arm_left] if score"));
+        let bad_state = compile_str("block stone stone[a.b=y]", "src/anim.dspa", &config);
+        assert!(bad_state.is_err());
+    }
+
+    #[test]
+    fn items_and_text_follow_the_target_version() {
+        let source = "object obj:anim\nitem test stone\nitem test custom:gem{display:{Name:'\"Gem\"'}}\ntext test \"it's\"";
+        let compile_for = |target_version, source: &str| {
+            let config = Config {
+                target_version,
+                ..Config::default()
+            };
+            compile_str(source, "src/anim.dspa", &config)
+        };
+        let old = compile_for(TargetVersion::V1_20, source).unwrap().contents;
+        let expected = [
+            at(
+                "test",
+                0,
+                "data merge entity @s {item:{id:\"minecraft:stone\",Count:1b}}",
+            ),
+            at(
+                "test",
+                0,
+                "data merge entity @s {item:{id:\"custom:gem\",Count:1b,tag:{display:{Name:'\"Gem\"'}}}}",
+            ),
+            at("test", 0, "data merge entity @s {text:'\"it\\'s\"'}"),
+            tail(0),
+        ]
+        .join("\n");
+        assert_eq!(old, expected);
+
+        let source = "object obj:anim\nitem test stone\nitem test custom:gem[custom_model_data={floats:[2f]}]\ntext test \"it's\"";
+        let expected = [
+            at("test", 0, "item replace entity @s contents with stone"),
+            at(
+                "test",
+                0,
+                "item replace entity @s contents with custom:gem[custom_model_data={floats:[2f]}]",
+            ),
+            at("test", 0, "data merge entity @s {text:'\"it\\'s\"'}"),
+            tail(0),
+        ]
+        .join("\n");
+        assert_eq!(
+            compile_for(TargetVersion::V1_20_5, source)
+                .unwrap()
+                .contents,
+            expected
+        );
+        let new = compile_for(TargetVersion::V1_21_5, source)
+            .unwrap()
+            .contents;
+        assert!(
+            new.contains("run data merge entity @s {text:\"it's\"}"),
+            "{new}"
+        );
+
+        let Err(error) = compile_for(TargetVersion::V1_20, "item test stone[damage=1]") else {
+            panic!("1.20 items have no components");
+        };
+        assert!(
+            error
+                .to_string()
+                .contains("is given components, which 'target_version' 1.20 doesn't have"),
+            "{error}"
+        );
+        let Err(error) = compile_for(TargetVersion::V1_21_5, "item test stone{display:{Lore:[]}}")
+        else {
+            panic!("1.21.5 items have no NBT");
+        };
+        assert!(
+            error
+                .to_string()
+                .contains("is given NBT, which 'target_version' 1.21.5 replaces with components"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn waitfor_holds_the_timer_until_its_condition_passes() {
+        let expected = [
+            at("test", 0, "item replace entity @s contents with stone"),
+            "execute as @e[tag=obj,tag=test] if score $obj-anim timer matches 1 if score $door state matches 1 run item replace entity @s contents with dirt".to_string(),
+            "\n\
+            execute if score $obj-anim timer matches 6.. run scoreboard players set $obj-anim flags 0\n\
+            execute if score $obj-anim timer matches 6.. run scoreboard players set $obj-anim timer -1\n\
+            \n\
+            scoreboard players operation $obj-anim.previous timer = $obj-anim timer\n\
+            execute unless score $obj-anim.previous timer matches 1 run scoreboard players add $obj-anim timer 1\n\
+            execute if score $obj-anim.previous timer matches 1 if score $door state matches 1 run scoreboard players add $obj-anim timer 1".to_string(),
+        ]
+        .join("\n");
+        assert_eq!(
+            compile("object obj:anim\nitem test stone\nwaitfor $door state matches 1\nitem test dirt\nwait 5"),
+            expected
+        );
+    }
+
+    #[test]
+    fn waitfor_runs_the_paused_tick_once_its_condition_passes() {
+        let contents =
+            compile("object obj:anim\nwaitfor $door state matches 1\nitem test dirt\nwait 5");
+        let dirt = "item replace entity @s contents with dirt".to_string();
+        let run = |opens_on: u32| {
+            let mut scores = HashMap::from([
+                ("$obj-anim flags".to_string(), 1),
+                ("$obj-anim timer".to_string(), 0),
+                ("$door state".to_string(), 0),
+            ]);
+            let ran = simulate(&contents, 12, &mut scores, |tick, scores| {
+                if tick == opens_on {
+                    scores.insert("$door state".to_string(), 1);
+                }
+            });
+            (ran, scores["$obj-anim flags"])
+        };
+        // Already open when the timer reaches the pause, so it runs on the very next tick.
+        assert_eq!(run(0), (vec![(1, dirt.clone())], 0));
+        assert_eq!(run(4), (vec![(4, dirt)], 0));
+    }
+
+    #[test]
+    fn header_keeps_the_names_of_every_section() {
+        let contents = "# A door\n\nnamespace anims\nobject door:open\nmove cube 0 1 0 20\nwait 20\nobject door:close";
+        assert_eq!(
+            header(contents, "src/anim.dspa").unwrap(),
+            "# A door\n\nnamespace anims\nobject door:open\n/say\nobject door:close"
+        );
+        let names = |contents: &str| {
+            let header = header(contents, "src/anim.dspa").unwrap();
+            let compiled = compile_str(&header, "src/anim.dspa", &Config::default()).unwrap();
+            iter::once(&compiled)
+                .chain(&compiled.sections)
+                .map(|file| format!("{}:{}", file.object_name, file.animation_name))
+                .collect_vec()
+        };
+        assert_eq!(names(contents), ["door:open", "door:close"]);
+        assert_eq!(
+            names("/* A door\n   animation */\nobject door:open\nmove cube 0 1 0 20"),
+            ["door:open"]
+        );
+        assert_eq!(
+            names("object \\\n  lamp:on\nmove cube 0 1 0 20"),
+            ["lamp:on"]
+        );
+    }
+
+    #[test]
+    fn object_lines_before_any_action_name_a_single_animation() {
+        let source = "namespace anims\nobject door:draft\nobject door:open\nmove cube 0 1 0 20";
+        let compiled = compile_str(source, "src/anim.dspa", &Config::default()).unwrap();
+        assert!(compiled.sections.is_empty());
+        assert_eq!(
+            (
+                compiled.object_name.as_str(),
+                compiled.animation_name.as_str()
+            ),
+            ("door", "open")
+        );
+        assert_eq!(compiled.contents, compile(source));
+    }
+
+    #[test]
+    fn later_object_lines_start_new_animations() {
+        let source = "namespace anims\nobject door:open\nmove cube 0 1 0 20\nwait 20\nobject door:close\nwait 5\nmove cube 0 0 0 10\nwait 10";
+        let compiled = compile_str(source, "src/anim.dspa", &Config::default()).unwrap();
+        assert_eq!(compiled.animation_name, "open");
+        assert_eq!(compiled.length, 20);
+        assert_eq!(
+            compiled.contents,
+            compile("object door:open\nmove cube 0 1 0 20\nwait 20")
+        );
+        let [close] = compiled.sections.as_slice() else {
+            panic!("expected one more section, got {}", compiled.sections.len());
+        };
+        assert_eq!(close.animation_name, "close");
+        assert_eq!(close.namespace.as_deref(), Some("anims"));
+        assert_eq!(close.length, 15);
+        assert_eq!(
+            close.contents,
+            compile("object door:close\nwait 5\nmove cube 0 0 0 10\nwait 10")
+        );
+
+        let Err(error) = compile_str(
+            "object door:open\nwait 5\nobject door:open\nwait 5",
+            "src/anim.dspa",
+            &Config::default(),
+        ) else {
+            panic!("repeating an animation should fail");
+        };
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(GenericError::DuplicateSection(_, key)) if key == "door:open"
+        ));
+    }
+
+    #[test]
+    fn hold_extends_the_animation_and_reserves_the_entity() {
+        let held =
+            compile_str("hold cube 40\nwait 10", "src/anim.dspa", &Config::default()).unwrap();
+        assert_eq!(held.length, 40);
+        assert_eq!(held.contents, compile("wait 40"));
+        assert!(held.warnings.is_empty());
+
+        let source = "freeze cube 20\nwait 10\nturn cube y 90 5\nsize other 2 5";
+        let compiled = compile_str(source, "src/anim.dspa", &Config::default()).unwrap();
+        assert_eq!(compiled.warnings.len(), 1);
+        let warning = compiled.warnings[0].to_string();
+        assert!(
+            warning.contains("This rotation of 'cube' starts before the hold at 1:1 has finished."),
+            "{warning}"
+        );
+    }
+
+    #[test]
+    fn region_markers_are_kept_when_asked() {
+        let source = "#region open\nmove cube 0 1 0 20\n#endregion\n#region\n#endregion";
+        let compiled = compile_str(source, "src/anim.dspa", &Config::default()).unwrap();
+        assert!(
+            !compiled.contents.contains("region"),
+            "{}",
+            compiled.contents
+        );
+        assert_eq!(compiled.statement_count, 1);
+
+        let config = Config {
+            keep_regions: true,
+            ..Config::default()
+        };
+        let contents = compile_str(source, "src/anim.dspa", &config)
+            .unwrap()
+            .contents;
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines[0], "#region open");
+        assert_eq!(lines[2..5], ["#endregion", "#region", "#endregion"]);
+    }
+
+    #[test]
+    fn verify_reports_malformed_commands_at_their_statement() {
+        let config = Config {
+            verify: true,
+            ..Config::default()
+        };
+        let source = fs::read_to_string("test/src/test_file.dspa").unwrap();
+        compile_str(&source, "src/anim.dspa", &config).unwrap();
+        let source = "text cube \"it's {here}\"\ncolor cube #FF8800\nreverse\nmove cube 0 1 0 20 ease-in\nwait 1\n/data merge entity @s {Glowing:1b";
+        let Err(error) = compile_str(source, "src/anim.dspa", &config) else {
+            panic!("the unclosed brace is malformed");
+        };
+        let error = error.to_string();
+        assert!(error.contains("Line: 6"), "{error}");
+        assert!(
+            error.contains(
+                "Statement '/data merge entity @s {Glowing:1b' generates the malformed command"
+            ) && error.contains("'{' is never closed."),
+            "{error}"
+        );
+        assert!(compile_str(source, "src/anim.dspa", &Config::default()).is_ok());
+    }
+
+    #[test]
+    fn pretty_output_labels_each_tick() {
+        let config = Config {
+            pretty: true,
+            ..Config::default()
+        };
+        let source = "object obj:anim\ntag cube add a\nmove cube 0 1 0 5\nwait 5\ntag cube add b";
+        let contents = compile_str(source, "src/anim.dspa", &config)
+            .unwrap()
+            .contents;
+        let expected = [
+            "# tick 0".to_string(),
+            at("cube", 0, "tag @s add a"),
+            at("cube", 0, "data merge entity @s {start_interpolation:0,interpolation_duration:5,transformation:{translation: [0f,1f,0f]}}"),
+            String::new(),
+            "# tick 5".to_string(),
+            at("cube", 5, "tag @s add b"),
+            String::new(),
+            "# reset once the animation ends".to_string(),
+            "execute if score $obj-anim timer matches 5.. run scoreboard players set $obj-anim flags 0".to_string(),
+            "execute if score $obj-anim timer matches 5.. run scoreboard players set $obj-anim timer -1".to_string(),
+            String::new(),
+            "# advance the timer".to_string(),
+            "scoreboard players add $obj-anim timer 1".to_string(),
+        ]
+        .join("\n");
+        assert_eq!(contents, expected);
+    }
+
+    #[test]
+    fn objectives_can_be_renamed() {
+        let config = Config {
+            timer_objective: "dspa_timer".to_string(),
+            flags_objective: "dspa_flags".to_string(),
+            ..Config::default()
+        };
+        let compiled =
+            compile_str("move cube 1 0 0 10\n/say hi", "src/anim.dspa", &config).unwrap();
+        let generated = [
+            compiled.contents,
+            compiled::start_function(&config, "anim", "anim"),
+            compiled::stop_function(&config, "anim", "anim"),
+            compiled::tick_function_line(&config, "anim", "anim", "de:objects/anim"),
+        ]
+        .join("\n");
+        assert!(generated.contains(" dspa_timer "), "{generated}");
+        assert!(generated.contains(" dspa_flags "), "{generated}");
+        assert!(!generated.contains(" timer "), "{generated}");
+        assert!(!generated.contains(" flags "), "{generated}");
+    }
+
+    #[test]
+    fn blocked_retrigger_keeps_a_running_timer() {
+        assert_eq!(
+            compiled::start_function(&Config::default(), "obj", "anim"),
+            "scoreboard players set $obj-anim flags 1\n\
+            scoreboard players set $obj-anim timer 0"
+        );
+        let config = Config {
+            retrigger: Retrigger::Block,
+            ..Config::default()
+        };
+        assert_eq!(
+            compiled::start_function(&config, "obj", "anim"),
+            "execute unless score $obj-anim flags matches 1.. run scoreboard players set $obj-anim timer 0\n\
+            scoreboard players set $obj-anim flags 1"
+        );
+    }
+
+    #[test]
+    fn blank_or_star_names_keep_the_file_name() {
+        let name = |source| {
+            let compiled = compile_str(source, "src/walk.dspa", &Config::default()).unwrap();
+            format!("{}:{}", compiled.object_name, compiled.animation_name)
+        };
+        assert_eq!(name("object robot:*"), "robot:walk");
+        assert_eq!(name("object *:wave"), "walk:wave");
+        assert_eq!(name("object :wave"), "walk:wave");
+        assert_eq!(name("object robot:wave\nobject *:*"), "walk:walk");
+        assert!(compile_str("object robot:w*", "src/walk.dspa", &Config::default()).is_err());
+    }
+
+    #[test]
+    fn anchor_pivots_later_rotations() {
+        assert_eq!(
+            compile("anchor cube 0.5 0 0\nturn cube y 90 10\nwait 10\nturn cube y 180 10"),
+            compile("turn cube y 90 10 pivot 0.5 0 0\nwait 10\nturn cube y 180 10 pivot 0.5 0 0")
+        );
+        assert_eq!(
+            compile("anchor cube 0.5 0 0\nturn cube y 90 10 pivot 0 1 0\nturn other y 90 10"),
+            compile("turn cube y 90 10 pivot 0 1 0\nturn other y 90 10")
+        );
+    }
+}
@@ -0,0 +1,1414 @@
+use std::{
+    collections::HashSet,
+    fmt::{Display, Write as _},
+    fs, iter,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use anyhow::ensure;
+use compiled::CompiledFile;
+use config::Config;
+use errors::{CompileError, ErrorCollection, GenericError};
+use file_reader::{parse_file, parse_header};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use itertools::Itertools;
+use log::debug;
+use serde::Serialize;
+use walkdir::WalkDir;
+
+mod block_properties;
+pub mod compiled;
+pub mod config;
+mod easing;
+pub mod errors;
+pub mod file_reader;
+mod objects;
+mod statements;
+mod verify;
+
+fn get_folder_tree(path: &Path, extension: &str, exclude: &GlobSet) -> Vec<String> {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|path| {
+            if let Err(ref err) = path {
+                println!(
+                    "{}",
+                    GenericError::InvalidPath(String::from("<none>"), err.to_string())
+                );
+            }
+            path.ok()
+        })
+        .filter(|path| path.path().extension().is_some_and(|e| e == extension))
+        .filter(|entry| {
+            let relative = entry
+                .path()
+                .strip_prefix(path)
+                .unwrap_or_else(|_| entry.path());
+            !exclude.is_match(relative)
+        })
+        .filter_map(|path| path.into_path().into_os_string().into_string().ok())
+        .collect::<Vec<_>>()
+}
+
+/// Whether the file at `path` starts with `marker`, the first line of the header, marking it as generated.
+#[must_use]
+pub fn is_generated(path: &str, marker: &str) -> bool {
+    fs::read_to_string(path).is_ok_and(|contents| {
+        contents
+            .lines()
+            .next()
+            .is_some_and(|line| line.starts_with(marker))
+    })
+}
+
+/// Every file a run could have generated that starts with the header's marker.
+///
+/// That covers the outputs in the target folder and in its copies for other namespaces, the dispatch
+/// functions and merge macro next to the tick function, and the load function and merged file. The
+/// tick function itself is left out, since it is emptied rather than removed. Files without the
+/// marker were written by hand, and there are none to find without a header.
+#[must_use]
+pub fn generated_outputs(config: &Config) -> Vec<String> {
+    let Some(marker) = compiled::generated_marker(config) else {
+        return Vec::new();
+    };
+    let tick_function = Path::new(&config.tick_function);
+    let tick_stem = tick_function
+        .file_stem()
+        .map(|stem| format!("{}_", stem.to_string_lossy()))
+        .unwrap_or_default();
+    let tick_folder = match tick_function.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    let companions = fs::read_dir(tick_folder)
+        .ok()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_string_lossy();
+            (name.starts_with(&tick_stem) && path.extension()? == config.output_extension.as_str())
+                .then(|| path.to_string_lossy().into_owned())
+        });
+    namespaced_targets(config)
+        .iter()
+        .flat_map(|target| get_folder_tree(target, &config.output_extension, &GlobSet::empty()))
+        .chain(companions)
+        .chain(config.load_function.clone())
+        .chain(config.single_file.clone())
+        .filter(|path| Path::new(path) != tick_function && is_generated(path, &marker))
+        .unique_by(|path| PathBuf::from(path))
+        .sorted()
+        .collect()
+}
+
+/// The target folder, along with the folder each other namespace's outputs would be written to. Those
+/// are the siblings of the target folder's `data/<namespace>` folder, so `./data/de/function` also
+/// finds `./data/mypack/function`.
+fn namespaced_targets(config: &Config) -> Vec<PathBuf> {
+    let target_folder = Path::new(&config.target_folder);
+    let namespace_folder = target_folder.ancestors().find(|folder| {
+        folder.file_name() == Some(config.namespace.as_ref())
+            && folder.parent().and_then(Path::file_name) == Some("data".as_ref())
+    });
+    let siblings = namespace_folder
+        .and_then(Path::parent)
+        .and_then(|parent| fs::read_dir(parent).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let namespace = entry.ok()?.file_name();
+            namespaced_target(
+                &config.target_folder,
+                &config.namespace,
+                &namespace.to_string_lossy(),
+            )
+            .ok()
+        });
+    iter::once(target_folder.to_path_buf())
+        .chain(siblings)
+        .unique()
+        .collect()
+}
+
+/// Builds a single matcher out of the configured exclude patterns.
+///
+/// # Errors
+/// If any pattern is not a valid glob, this returns a `GenericError::InvalidGlob`.
+fn build_exclude_set(patterns: &[String]) -> anyhow::Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(
+            Glob::new(pattern).map_err(|err| GenericError::InvalidGlob(pattern.clone(), err))?,
+        );
+    }
+    Ok(builder.build()?)
+}
+
+/// Collects all the 'Ok' values in the input and flattens the Results into the output.
+///
+/// # Errors
+/// If any of the results in the input are Err, this returns an `ErrorCollection` containing all of the errors.
+pub fn collect_errors<T, E>(input: Vec<Result<T, E>>) -> Result<Vec<T>, ErrorCollection<E>> {
+    let mut values = Vec::new();
+    let mut errors = Vec::new();
+    for (index, result) in input.into_iter().enumerate() {
+        match result {
+            Ok(value) => values.push(value),
+            Err(error) => errors.push((index, error)),
+        }
+    }
+    if errors.is_empty() {
+        Ok(values)
+    } else {
+        Err(ErrorCollection { errors })
+    }
+}
+
+/// Swaps the config's namespace for a file's own in the target folder, so a datapack target like
+/// `./data/de/function` becomes `./data/mypack/function`. Only the folder right after `data` names a
+/// namespace, so any other folder that shares its name is kept.
+///
+/// # Errors
+/// If the target folder has no `data/<namespace>` folder to swap, the file's functions couldn't be
+/// called by its namespace, and this returns a `GenericError::UnplacedNamespace`.
+fn namespaced_target(
+    target_folder: &str,
+    default_namespace: &str,
+    namespace: &str,
+) -> anyhow::Result<PathBuf> {
+    if namespace == default_namespace {
+        return Ok(PathBuf::from(target_folder));
+    }
+    let components = Path::new(target_folder).components().collect_vec();
+    let index = components
+        .windows(2)
+        .rposition(|pair| pair[0].as_os_str() == "data" && pair[1].as_os_str() == default_namespace)
+        .ok_or_else(|| {
+            GenericError::UnplacedNamespace(
+                namespace.to_string(),
+                target_folder.to_string(),
+                default_namespace.to_string(),
+            )
+        })?;
+    Ok(components
+        .iter()
+        .enumerate()
+        .map(|(position, component)| {
+            if position == index + 1 {
+                Path::new(namespace)
+            } else {
+                Path::new(component.as_os_str())
+            }
+        })
+        .collect())
+}
+
+/// Re-roots `source_path` from `source_root` under `target_root` and swaps its extension for `extension`.
+///
+/// # Errors
+/// If `source_path` is not inside `source_root`, this returns a `GenericError::InvalidPath`.
+fn output_path(
+    source_path: &Path,
+    source_root: &Path,
+    target_root: &Path,
+    extension: &str,
+) -> anyhow::Result<PathBuf> {
+    let relative = source_path.strip_prefix(source_root).map_err(|err| {
+        GenericError::InvalidPath(source_path.display().to_string(), err.to_string())
+    })?;
+    let mut path = target_root.join(relative);
+    path.set_extension(extension);
+    Ok(path)
+}
+
+/// Renames the output file to `output_name` when the source declared one with `output`, keeping its folder.
+fn with_output_name(mut path: PathBuf, output_name: Option<&str>, extension: &str) -> PathBuf {
+    if let Some(output_name) = output_name {
+        path.set_file_name(format!("{output_name}.{extension}"));
+    }
+    path
+}
+
+/// Returns the path of a file generated alongside `path`, e.g. `anim.mcfunction` -> `anim_start.mcfunction`.
+fn companion_path(path: &str, suffix: &str, extension: &str) -> String {
+    let extension = format!(".{extension}");
+    let stem = path.strip_suffix(&extension).unwrap_or(path);
+    format!("{stem}_{suffix}{extension}")
+}
+
+/// Ensures no two compiled files would be written to the same output path.
+///
+/// # Errors
+/// If any output path appears more than once, this returns a `GenericError::OutputCollision`.
+fn ensure_unique_outputs<'a>(paths: impl IntoIterator<Item = &'a str>) -> anyhow::Result<()> {
+    ensure_unique(paths, GenericError::OutputCollision)
+}
+
+/// Ensures every `item` is distinct, reporting the first repeated one through `error`.
+fn ensure_unique<'a>(
+    items: impl IntoIterator<Item = &'a str>,
+    error: fn(String) -> GenericError,
+) -> anyhow::Result<()> {
+    let mut seen = HashSet::new();
+    for item in items {
+        ensure!(seen.insert(item), error(item.to_string()));
+    }
+    Ok(())
+}
+
+/// Converts an output path into the resource location used in `function <namespace>:<path>`.
+///
+/// Paths are handled as strings rather than through `Path`, so a Windows path converts the same way on
+/// every platform: either separator is accepted, `.` and empty components are dropped, and so is a
+/// drive prefix like `C:`, which has no meaning in a resource location.
+fn resource_location(namespace: &str, path: &str, extension: &str) -> String {
+    let extension = format!(".{extension}");
+    let mut components = path
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty() && *component != ".")
+        .skip_while(|component| component.ends_with(':'))
+        .collect_vec();
+    if let Some(last) = components.last_mut() {
+        *last = last.strip_suffix(&extension).unwrap_or(last);
+    }
+    format!("{namespace}:{}", components.join("/"))
+}
+
+/// Totals across a run, printed as a single line so it can be found in build logs.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub compiled: usize,
+    pub failed: usize,
+    pub statements: usize,
+    pub ticks: u64,
+}
+impl Summary {
+    fn add(&mut self, file: &CompiledFile) {
+        self.compiled += 1;
+        self.statements += file.statement_count;
+        self.ticks += u64::from(file.length);
+        for section in &file.sections {
+            self.statements += section.statement_count;
+            self.ticks += u64::from(section.length);
+        }
+    }
+}
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Summary: {} compiled, {} failed, {} statements, {} ticks",
+            self.compiled, self.failed, self.statements, self.ticks
+        )
+    }
+}
+
+/// A line of the tick function. Lines sort by resource location, then by `object:animation`,
+/// so the tick function doesn't depend on the order the source folders were walked in.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct TickLine {
+    location: String,
+    object_name: String,
+    animation_name: String,
+}
+impl TickLine {
+    fn new(location: String, file: &CompiledFile) -> Self {
+        Self {
+            location,
+            object_name: file.object_name.clone(),
+            animation_name: file.animation_name.clone(),
+        }
+    }
+    fn compile(&self, config: &Config) -> String {
+        compiled::tick_function_line(
+            config,
+            &self.object_name,
+            &self.animation_name,
+            &self.location,
+        )
+    }
+}
+
+/// Describes how far `file` runs over `max_ticks`, if it does.
+#[must_use]
+pub fn length_warning(file: &CompiledFile, max_ticks: Option<u32>) -> Option<String> {
+    let max_ticks = max_ticks.filter(|&max_ticks| file.length > max_ticks)?;
+    Some(format!(
+        "Warning: '{}' runs for {} ticks, longer than the configured 'max_ticks' of {max_ticks}.",
+        file.path, file.length
+    ))
+}
+
+/// Every file a run generates, with its final contents. Nothing is written until everything has been
+/// generated, so `--diff` can compare the files against the ones on disk instead.
+#[derive(Debug, Default)]
+pub struct Generated {
+    /// Each file's path, paired with its contents.
+    pub files: Vec<(String, String)>,
+    /// A line for each animation generated, printed once the run is sure to succeed.
+    pub messages: Vec<String>,
+}
+impl Generated {
+    /// Adds a generated function, headed by the configured header.
+    pub fn add(&mut self, config: &Config, path: &str, contents: &str) {
+        self.add_raw(path, compiled::with_header(config, contents));
+    }
+    /// Adds a file as it is, for JSON, which has no comments to put a header in.
+    pub fn add_raw(&mut self, path: &str, contents: String) {
+        self.files.push((path.to_string(), contents));
+    }
+}
+
+/// Writes each file's timeline next to its output as JSON.
+fn generate_timelines(
+    config: &Config,
+    outputs: &[(String, CompiledFile)],
+    generated: &mut Generated,
+) -> anyhow::Result<()> {
+    for (path, result) in outputs {
+        let path = Path::new(&companion_path(path, "timeline", &config.output_extension))
+            .with_extension("json");
+        generated.add_raw(
+            &path.to_string_lossy(),
+            serde_json::to_string_pretty(&result.timelines)?,
+        );
+    }
+    Ok(())
+}
+
+/// Where the manifest is written, in the working directory like every other configured path. A config
+/// chosen with `--config` elsewhere doesn't move it.
+const MANIFEST_PATH: &str = "./dispa_manifest.json";
+
+/// An animation as the manifest lists it, for tools that call or time the animations.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct ManifestEntry {
+    object: String,
+    animation: String,
+    namespace: String,
+    /// The function the animation is compiled into, which is the merged function for a `single_file`.
+    function_path: String,
+    length_ticks: u32,
+}
+
+/// Lists every animation, sorted by function and then by name, so the manifest diffs cleanly.
+fn manifest_entries(config: &Config, outputs: &[(String, CompiledFile)]) -> Vec<ManifestEntry> {
+    let mut entries = outputs
+        .iter()
+        .map(|(path, result)| {
+            let namespace = result.namespace.as_ref().unwrap_or(&config.namespace);
+            let function_path = config.single_file.as_ref().map_or_else(
+                || resource_location(namespace, path, &config.output_extension),
+                |single_file| {
+                    resource_location(&config.namespace, single_file, &config.output_extension)
+                },
+            );
+            ManifestEntry {
+                object: result.object_name.clone(),
+                animation: result.animation_name.clone(),
+                namespace: namespace.clone(),
+                function_path,
+                length_ticks: result.length,
+            }
+        })
+        .collect_vec();
+    entries.sort_by(|left, right| {
+        (&left.function_path, &left.object, &left.animation).cmp(&(
+            &right.function_path,
+            &right.object,
+            &right.animation,
+        ))
+    });
+    entries
+}
+
+/// Writes the list of every animation to `dispa_manifest.json`. The compiler never reads it back.
+fn generate_manifest(
+    config: &Config,
+    outputs: &[(String, CompiledFile)],
+    generated: &mut Generated,
+) -> anyhow::Result<()> {
+    let entries = manifest_entries(config, outputs);
+    generated.add_raw(
+        MANIFEST_PATH,
+        format!("{}\n", serde_json::to_string_pretty(&entries)?),
+    );
+    Ok(())
+}
+
+/// Writes each file's report next to its output as plain text.
+fn generate_reports(
+    config: &Config,
+    outputs: &[(String, CompiledFile)],
+    generated: &mut Generated,
+) {
+    for (path, result) in outputs {
+        let extension = format!(".{}", config.output_extension);
+        let stem = path.strip_suffix(&extension).unwrap_or(path);
+        generated.add_raw(
+            &format!("{stem}.timeline.txt"),
+            format!("{}\n", result.report),
+        );
+    }
+}
+
+/// Generates the load function, and adds it to the load tag if there is one.
+fn generate_load_function(
+    config: &Config,
+    load_function: &str,
+    generated: &mut Generated,
+) -> anyhow::Result<()> {
+    generated.add(config, load_function, &compiled::load_function(config));
+    let Some(load_tag) = &config.load_tag else {
+        return Ok(());
+    };
+    let location = resource_location(&config.namespace, load_function, &config.output_extension);
+    let existing = fs::read_to_string(load_tag).ok();
+    let tag = with_tag_value(existing.as_deref(), &location)
+        .map_err(|err| GenericError::InvalidFunctionTag(load_tag.clone(), err.to_string()))?;
+    generated.add_raw(load_tag, tag);
+    Ok(())
+}
+
+/// Adds `value` to a function tag's `values`, keeping what is already there, so the tag can be shared with
+/// other packs' functions and rewritten on every run.
+fn with_tag_value(existing: Option<&str>, value: &str) -> serde_json::Result<String> {
+    let mut tag: serde_json::Value = serde_json::from_str(existing.unwrap_or(r#"{"values":[]}"#))?;
+    let values = tag
+        .get_mut("values")
+        .and_then(serde_json::Value::as_array_mut)
+        .ok_or_else(|| serde::de::Error::custom("expected a 'values' list"))?;
+    if !values.iter().any(|existing| existing == value) {
+        values.push(value.into());
+    }
+    serde_json::to_string_pretty(&tag)
+}
+
+/// Everything a build computes before anything is written, so tools can drive it from Rust and do
+/// their own IO.
+#[derive(Default)]
+pub struct BuildReport {
+    /// Every animation that compiled, with sections and reversed copies as animations of their own,
+    /// in the order they are generated.
+    pub files: Vec<CompiledFile>,
+    /// The path each of `files` is written to, in the same order.
+    pub outputs: Vec<String>,
+    /// The errors of every source that failed to compile.
+    pub errors: Vec<CompileError>,
+    /// The lines of the tick function. Nothing is generated while a source fails, so these are
+    /// empty whenever `errors` isn't.
+    pub tick_lines: Vec<String>,
+    /// Every file the build writes, with its final contents: the animations with their `_start` and
+    /// `_stop` functions, the dispatch and merged functions, the extras the config asks for, and the
+    /// tick function.
+    pub generated: Generated,
+    /// Totals across every source, both those that compiled and those that didn't.
+    pub summary: Summary,
+}
+
+/// Compiles every source folder the way a run does, without writing anything.
+///
+/// # Errors
+/// Sources that fail to compile are reported in the `BuildReport`'s `errors`. This only fails if the
+/// sources can't be found or read, or two generated files would be written to the same place.
+pub fn compile_directory(config: &Config) -> anyhow::Result<BuildReport> {
+    compile_directory_with(config, |_, _| {})
+}
+
+/// Compiles every source folder like `compile_directory`, reporting progress through `on_compiled`.
+///
+/// `on_compiled` is called with each source's path and the number of sources as each one finishes.
+/// Sources can be compiled on several threads, so it can be called from any of them.
+///
+/// # Errors
+/// The same as `compile_directory`.
+pub fn compile_directory_with(
+    config: &Config,
+    on_compiled: impl Fn(&str, usize) + Sync,
+) -> anyhow::Result<BuildReport> {
+    let sources = discover_sources(config)?;
+    let jobs = config.jobs.map_or_else(
+        || thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        NonZeroUsize::get,
+    );
+    let results = parallel_map(&sources, jobs, |&(root, ref path)| {
+        let compiled = parse_file(path, config);
+        on_compiled(path, sources.len());
+        let compiled = compiled?;
+        let output = compiled_output(config, root, &compiled)?;
+        anyhow::Ok((root, output, compiled))
+    });
+    let mut summary = Summary::default();
+    let mut compiled_files = Vec::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(compiled) => {
+                summary.add(&compiled.2);
+                compiled_files.push(compiled);
+            }
+            Err(error) => {
+                summary.failed += 1;
+                errors.extend(compile_errors(error)?);
+            }
+        }
+    }
+    let outputs = file_outputs(config, compiled_files)?;
+    let mut generated = Generated::default();
+    let tick_function = if errors.is_empty() {
+        generate(config, &outputs, &mut generated)?
+    } else {
+        String::new()
+    };
+    let (outputs, files) = outputs.into_iter().unzip();
+    Ok(BuildReport {
+        files,
+        outputs,
+        errors,
+        tick_lines: tick_function.lines().map(str::to_string).collect(),
+        generated,
+        summary,
+    })
+}
+
+/// Generates every file that goes with the compiled animations, ending with the tick function, and
+/// returns the tick function's contents.
+fn generate(
+    config: &Config,
+    outputs: &[(String, CompiledFile)],
+    generated: &mut Generated,
+) -> anyhow::Result<String> {
+    if config.timelines {
+        generate_timelines(config, outputs, generated)?;
+    }
+    if config.report {
+        generate_reports(config, outputs, generated);
+    }
+    if config.manifest {
+        generate_manifest(config, outputs, generated)?;
+    }
+    if config.macros {
+        generated.add(
+            config,
+            &compiled::merge_macro_path(config),
+            &compiled::merge_macro(),
+        );
+    }
+    if let Some(load_function) = &config.load_function {
+        generate_load_function(config, load_function, generated)?;
+    }
+    let tick_function = if let Some(single_file) = &config.single_file {
+        generate_single_file(config, single_file, outputs, generated)?
+    } else {
+        generate_separate_files(config, outputs, generated)?
+    };
+    generated.add(config, &config.tick_function, &tick_function);
+    Ok(tick_function)
+}
+
+/// Adds up the command counts of every generated animation.
+#[must_use]
+pub fn total_command_counts(files: &[CompiledFile]) -> compiled::CommandCounts {
+    let mut totals = compiled::CommandCounts::new();
+    for compiled in files {
+        for (category, count) in &compiled.command_counts {
+            *totals.entry(category).or_default() += count;
+        }
+    }
+    totals
+}
+
+/// Lays out command counts as a table, followed by the length of the tick function.
+#[must_use]
+pub fn stats_table(command_counts: &compiled::CommandCounts, tick_lines: usize) -> String {
+    let total: usize = command_counts.values().sum();
+    let mut table = String::from("Generated commands:\n");
+    for (category, count) in command_counts
+        .iter()
+        .map(|(category, count)| (*category, count))
+        .chain([("total", &total)])
+    {
+        let _ = writeln!(table, "  {category:<12}{count:>8}");
+    }
+    let _ = writeln!(table, "Tick function lines: {tick_lines}");
+    table
+}
+
+/// Finds every source file, paired with the source folder it was found in.
+fn discover_sources(config: &Config) -> anyhow::Result<Vec<(&str, String)>> {
+    let exclude = build_exclude_set(&config.exclude)?;
+    Ok(config
+        .source_folder
+        .roots()
+        .flat_map(|root| {
+            get_folder_tree(Path::new(root), &config.source_extension, &exclude)
+                .into_iter()
+                .map(move |path| (root, path))
+        })
+        .collect())
+}
+
+/// An animation a source declares, as `--list` shows it.
+pub struct ListedAnimation {
+    /// The source it's declared in.
+    pub source: String,
+    /// Its `object:animation` name.
+    pub animation: String,
+    /// The path it compiles to.
+    pub output: String,
+}
+
+/// Every animation the sources declare, including each section, in the order they are found. Only
+/// the headers are read, so even sources whose bodies fail to compile are listed.
+///
+/// # Errors
+/// If a source header can't be read or parsed, or two animations would be written to the same place,
+/// those errors are returned.
+pub fn list_animations(config: &Config) -> anyhow::Result<Vec<ListedAnimation>> {
+    let results = discover_sources(config)?
+        .into_iter()
+        .map(|(root, path)| {
+            let header = parse_header(&path, config)?;
+            let output = compiled_output(config, root, &header)?;
+            let sections = file_outputs(config, vec![(root, output, header)])?;
+            Ok(sections
+                .into_iter()
+                .map(|(output, section)| ListedAnimation {
+                    source: path.clone(),
+                    animation: format!("{}:{}", section.object_name, section.animation_name),
+                    output,
+                })
+                .collect_vec())
+        })
+        .collect::<Vec<anyhow::Result<_>>>();
+    Ok(collect_errors(results)?.into_iter().flatten().collect())
+}
+
+/// The `CompileError`s a source failed with, unpacking the collections of them a file fails with.
+///
+/// # Errors
+/// If the source failed for another reason, like not being readable, that error is returned instead.
+fn compile_errors(error: anyhow::Error) -> anyhow::Result<Vec<CompileError>> {
+    let error = match error.downcast::<CompileError>() {
+        Ok(error) => return Ok(vec![error]),
+        Err(error) => error,
+    };
+    let error = match error.downcast::<ErrorCollection<CompileError>>() {
+        Ok(collection) => return Ok(collection.errors.into_iter().map(|(_, e)| e).collect()),
+        Err(error) => error,
+    };
+    let collection = error.downcast::<ErrorCollection<anyhow::Error>>()?;
+    collection
+        .errors
+        .into_iter()
+        .map(|(_, error)| compile_errors(error))
+        .flatten_ok()
+        .collect()
+}
+
+/// Pairs every compiled file, and each of its sections and reversed copies, with the path it will be
+/// written to.
+///
+/// # Errors
+/// If two animations would be written to the same path, this returns a `GenericError::OutputCollision`.
+fn file_outputs(
+    config: &Config,
+    compiled_files: Vec<(&str, String, CompiledFile)>,
+) -> anyhow::Result<Vec<(String, CompiledFile)>> {
+    let mut outputs = Vec::new();
+    for (root, output, mut compiled) in compiled_files {
+        // Later sections are written next to the file's first animation, named after their own
+        // unless they declare an `output`.
+        let sections = std::mem::take(&mut compiled.sections)
+            .into_iter()
+            .map(|section| {
+                let section_output = compiled_output(config, root, &section)?;
+                let section_output = if section.output_name.is_some() {
+                    section_output
+                } else {
+                    companion_path(
+                        &section_output,
+                        &section.animation_name,
+                        &config.output_extension,
+                    )
+                };
+                Ok((section_output, section))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for (output, mut compiled) in std::iter::once((output, compiled)).chain(sections) {
+            // A reversed copy is written next to its original, as an animation of its own.
+            let reverse = compiled.reverse.take().map(|reverse| {
+                (
+                    companion_path(&output, "reverse", &config.output_extension),
+                    *reverse,
+                )
+            });
+            outputs.push((output, compiled));
+            outputs.extend(reverse);
+        }
+    }
+    for (output, compiled) in &outputs {
+        debug!(
+            "{} ({}:{}) is written to {output}",
+            compiled.path, compiled.object_name, compiled.animation_name
+        );
+    }
+    ensure_unique_outputs(outputs.iter().map(|(path, _)| path.as_str()))?;
+    Ok(outputs)
+}
+
+/// Maps every item on up to `jobs` threads, keeping the results in the order of the items, so how
+/// many jobs run never changes what is generated.
+fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    jobs: usize,
+    map: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    if jobs <= 1 || items.len() <= 1 {
+        return items.iter().map(map).collect();
+    }
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers = (0..jobs.min(items.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut mapped = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            return mapped;
+                        };
+                        mapped.push((index, map(item)));
+                    }
+                })
+            })
+            .collect_vec();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect()
+    });
+    results.sort_unstable_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// The path `compiled` is written to, under the target folder of its namespace.
+///
+/// # Errors
+/// If its namespace can't be put in the target folder, or it isn't inside `root`, this returns a
+/// `GenericError`.
+fn compiled_output(config: &Config, root: &str, compiled: &CompiledFile) -> anyhow::Result<String> {
+    let target_folder = compiled.namespace.as_ref().map_or_else(
+        || Ok(PathBuf::from(&config.target_folder)),
+        |namespace| namespaced_target(&config.target_folder, &config.namespace, namespace),
+    )?;
+    let output = output_path(
+        Path::new(&compiled.path),
+        Path::new(root),
+        &target_folder,
+        &config.output_extension,
+    )?;
+    Ok(with_output_name(
+        output,
+        compiled.output_name.as_deref(),
+        &config.output_extension,
+    )
+    .to_string_lossy()
+    .into_owned())
+}
+
+/// Merges every animation into `single_file`, which the tick function calls unconditionally, and
+/// generates their `_start` and `_stop` functions as usual.
+/// Returns the contents of the tick function.
+fn generate_single_file(
+    config: &Config,
+    single_file: &str,
+    outputs: &[(String, CompiledFile)],
+    generated: &mut Generated,
+) -> anyhow::Result<String> {
+    // Every animation shares the merged function, so their scoreboard holders must not collide.
+    let keys: Vec<String> = outputs
+        .iter()
+        .map(|(_, result)| format!("{}:{}", result.object_name, result.animation_name))
+        .collect();
+    ensure_unique(
+        keys.iter().map(String::as_str),
+        GenericError::AnimationCollision,
+    )?;
+    let contents = outputs
+        .iter()
+        .map(|(_, result)| &result.contents)
+        .join("\n");
+    generated.add(config, single_file, &contents);
+    let merged_location =
+        resource_location(&config.namespace, single_file, &config.output_extension);
+    for (path, result) in outputs {
+        // The animations still start and stop on their own, from where their files would have been.
+        generate_start_stop(config, path, result, generated);
+        generated
+            .messages
+            .push(format!("Successfully Compiled file: {}", result.path));
+    }
+    generated
+        .messages
+        .push(format!("Merged all animations into: {merged_location}"));
+    Ok(format!(
+        "{}\n",
+        compiled::single_file_tick_line(&merged_location)
+    ))
+}
+
+/// Generates the `_start` and `_stop` functions of the animation compiled to `path`.
+fn generate_start_stop(
+    config: &Config,
+    path: &str,
+    result: &CompiledFile,
+    generated: &mut Generated,
+) {
+    generated.add(
+        config,
+        &companion_path(path, "start", &config.output_extension),
+        &compiled::start_function(config, &result.object_name, &result.animation_name),
+    );
+    generated.add(
+        config,
+        &companion_path(path, "stop", &config.output_extension),
+        &compiled::stop_function(config, &result.object_name, &result.animation_name),
+    );
+}
+
+/// Generates each animation with its `_start` and `_stop` functions, and calls it from the tick function.
+/// Returns the contents of the tick function.
+fn generate_separate_files(
+    config: &Config,
+    outputs: &[(String, CompiledFile)],
+    generated: &mut Generated,
+) -> anyhow::Result<String> {
+    let mut tick_lines = Vec::new();
+    for (path, result) in outputs {
+        generated.add(config, path, &result.contents);
+        generate_start_stop(config, path, result, generated);
+        let namespace = result.namespace.as_ref().unwrap_or(&config.namespace);
+        let location = resource_location(namespace, path, &config.output_extension);
+        generated
+            .messages
+            .push(format!("Successfully Compiled file: {location}"));
+        tick_lines.push(TickLine::new(location, result));
+    }
+    if config.sort_tick_function {
+        tick_lines.sort();
+    }
+    if config.group_tick_function {
+        return group_tick_lines(config, &tick_lines, generated);
+    }
+    Ok(tick_lines.iter().fold(String::new(), |mut acc, line| {
+        let _ = writeln!(acc, "{}", line.compile(config));
+        acc
+    }))
+}
+
+/// Moves each object's tick lines into a dispatch function next to the tick function, keeping the objects
+/// in the order their first line appeared. Returns the contents of the tick function, which calls them.
+///
+/// # Errors
+/// If two objects' names only differ in case, or an object's function would replace the shared
+/// `data merge` macro, their functions would collide, and this returns a `GenericError::OutputCollision`.
+fn group_tick_lines(
+    config: &Config,
+    tick_lines: &[TickLine],
+    generated: &mut Generated,
+) -> anyhow::Result<String> {
+    let mut objects: Vec<(&str, Vec<&TickLine>)> = Vec::new();
+    for line in tick_lines {
+        match objects
+            .iter_mut()
+            .find(|(name, _)| *name == line.object_name)
+        {
+            Some((_, lines)) => lines.push(line),
+            None => objects.push((&line.object_name, vec![line])),
+        }
+    }
+    let paths: Vec<String> = objects
+        .iter()
+        .map(|(object_name, _)| {
+            companion_path(
+                &config.tick_function,
+                &object_name.to_lowercase(),
+                &config.output_extension,
+            )
+        })
+        .collect();
+    // The shared macro is written next to the tick function too, where an object's dispatch could land.
+    let merge_macro = config.macros.then(|| compiled::merge_macro_path(config));
+    ensure_unique_outputs(paths.iter().chain(&merge_macro).map(String::as_str))?;
+    let mut tick_function = String::new();
+    for ((object_name, lines), path) in objects.iter().zip(&paths) {
+        let dispatch = lines.iter().fold(String::new(), |mut acc, line| {
+            let _ = writeln!(acc, "{}", line.compile(config));
+            acc
+        });
+        let end = compiled::object_dispatch_end(
+            config,
+            object_name,
+            lines.iter().map(|line| line.animation_name.as_str()),
+        );
+        generated.add(config, path, &format!("{dispatch}{end}\n"));
+        let location = resource_location(&config.namespace, path, &config.output_extension);
+        let _ = writeln!(
+            tick_function,
+            "{}",
+            compiled::object_tick_line(config, object_name, &location)
+        );
+    }
+    Ok(tick_function)
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(source_path: &str) -> PathBuf {
+        output_path(
+            Path::new(source_path),
+            Path::new("src"),
+            Path::new("objects"),
+            "mcfunction",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn output_path_only_swaps_trailing_extension() {
+        assert_eq!(
+            rewrite("src/dspa_effects/dspa.dspa"),
+            Path::new("objects/dspa_effects/dspa.mcfunction")
+        );
+    }
+
+    #[test]
+    fn output_path_only_replaces_root_prefix() {
+        assert_eq!(
+            rewrite("src/nested/src/anim.dspa"),
+            Path::new("objects/nested/src/anim.mcfunction")
+        );
+    }
+
+    #[test]
+    fn output_path_rejects_paths_outside_root() {
+        assert!(output_path(
+            Path::new("other/anim.dspa"),
+            Path::new("src"),
+            Path::new("objects"),
+            "mcfunction"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn exclude_patterns_match_relative_to_source_root() {
+        let root = "./target/excluded_sources";
+        let _ = fs::remove_dir_all(root);
+        for path in [
+            "drafts/scratch.dspa",
+            "drafts/nested/scratch.dspa",
+            "final/drafts.dspa",
+        ] {
+            let path = Path::new(root).join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "wait 1").unwrap();
+        }
+        let config = Config {
+            source_folder: config::SourceFolders::Single(root.to_string()),
+            exclude: vec!["drafts/**".to_string()],
+            ..Config::default()
+        };
+        let sources = discover_sources(&config).unwrap();
+        assert_eq!(sources.len(), 1, "{sources:?}");
+        let (source_root, path) = &sources[0];
+        assert_eq!(*source_root, root);
+        assert_eq!(Path::new(path), Path::new(root).join("final/drafts.dspa"));
+    }
+
+    #[test]
+    fn colliding_outputs_are_rejected() {
+        assert!(ensure_unique_outputs(["objects/a.mcfunction", "objects/b.mcfunction"]).is_ok());
+        assert!(ensure_unique_outputs(["objects/a.mcfunction", "objects/a.mcfunction"]).is_err());
+    }
+
+    #[test]
+    fn output_path_handles_relative_roots() {
+        assert_eq!(
+            output_path(
+                Path::new("./test/src/test_file.dspa"),
+                Path::new("./test/src"),
+                Path::new("./test/objects"),
+                "mcfunction"
+            )
+            .unwrap(),
+            Path::new("./test/objects/test_file.mcfunction")
+        );
+    }
+
+    #[test]
+    fn output_name_replaces_the_file_name() {
+        let path = PathBuf::from("objects/turret/spin.mcfunction");
+        assert_eq!(
+            with_output_name(path.clone(), Some("rotate.fast"), "mcfunction"),
+            Path::new("objects/turret/rotate.fast.mcfunction")
+        );
+        assert_eq!(with_output_name(path.clone(), None, "mcfunction"), path);
+    }
+
+    fn file(statement_count: usize, length: u32) -> CompiledFile {
+        CompiledFile {
+            path: "src/anim.dspa".to_string(),
+            object_name: String::new(),
+            animation_name: String::new(),
+            contents: String::new(),
+            statement_count,
+            length,
+            warnings: Vec::new(),
+            namespace: None,
+            output_name: None,
+            timelines: compiled::Timelines::new(),
+            report: String::new(),
+            reverse: None,
+            sections: Vec::new(),
+            command_counts: compiled::CommandCounts::new(),
+            malformed: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn summary_totals_compiled_files() {
+        let mut summary = Summary {
+            failed: 1,
+            ..Summary::default()
+        };
+        summary.add(&file(3, 40));
+        summary.add(&file(5, 20));
+        assert_eq!(
+            summary.to_string(),
+            "Summary: 2 compiled, 1 failed, 8 statements, 60 ticks"
+        );
+    }
+
+    #[test]
+    fn tick_lines_sort_by_path_then_animation() {
+        let line = |location: &str, object: &str, animation: &str| TickLine {
+            location: location.to_string(),
+            object_name: object.to_string(),
+            animation_name: animation.to_string(),
+        };
+        let mut lines = vec![
+            line("de:objects/b", "a", "a"),
+            line("de:objects/a", "z", "b"),
+            line("de:objects/a", "z", "a"),
+        ];
+        lines.sort();
+        assert_eq!(
+            lines,
+            [
+                line("de:objects/a", "z", "a"),
+                line("de:objects/a", "z", "b"),
+                line("de:objects/b", "a", "a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn grouped_tick_lines_dispatch_per_object() {
+        let config = Config {
+            group_tick_function: true,
+            ..Config::default()
+        };
+        let line = |location: &str, object: &str, animation: &str| TickLine {
+            location: location.to_string(),
+            object_name: object.to_string(),
+            animation_name: animation.to_string(),
+        };
+        let lines = [
+            line("de:objects/door/open", "door", "open"),
+            line("de:objects/lamp", "lamp", "lamp"),
+            line("de:objects/door/close", "door", "close"),
+        ];
+        let mut generated = Generated::default();
+        let tick_function = group_tick_lines(&config, &lines, &mut generated).unwrap();
+        assert_eq!(
+            tick_function,
+            "execute if score #door flags matches 1.. run function de:tick_door\n\
+             execute if score #lamp flags matches 1.. run function de:tick_lamp\n"
+        );
+        let (path, contents) = &generated.files[0];
+        assert_eq!(path, "./tick_door.mcfunction");
+        assert!(contents.ends_with(
+            "execute if score $door-close flags matches 1.. run function de:objects/door/close\n\
+             execute unless score $door-open flags matches 1.. unless score $door-close flags matches 1.. run scoreboard players set #door flags 0\n"
+        ));
+
+        assert!(compiled::start_function(&config, "door", "open")
+            .ends_with("\nscoreboard players set #door flags 1"));
+
+        let clashing = [line("de:a", "Door", "a"), line("de:b", "door", "b")];
+        assert!(group_tick_lines(&config, &clashing, &mut generated).is_err());
+
+        let macros = Config {
+            macros: true,
+            ..config
+        };
+        let merge = [line("de:objects/merge/open", "merge", "open")];
+        assert!(group_tick_lines(&macros, &merge, &mut generated).is_err());
+    }
+
+    #[test]
+    fn collected_errors_keep_their_types() {
+        let results: Vec<Result<u32, GenericError>> = vec![
+            Ok(1),
+            Err(GenericError::FileNotExist("a".to_string())),
+            Ok(2),
+            Err(GenericError::OutputsDiffer(3)),
+        ];
+        let collection = collect_errors(results).unwrap_err();
+        assert!(matches!(
+            collection.errors.as_slice(),
+            [
+                (1, GenericError::FileNotExist(_)),
+                (3, GenericError::OutputsDiffer(3))
+            ]
+        ));
+        assert!(collection
+            .to_string()
+            .ends_with("1: The file with path 'a' does not exist.\n3: 3 generated files differ from the ones on disk.\n"));
+        assert_eq!(
+            collect_errors(vec![Ok::<_, GenericError>(1), Ok(2)]).unwrap(),
+            [1, 2]
+        );
+    }
+
+    #[test]
+    fn stats_total_every_animation() {
+        let mut first = file(1, 10);
+        first.command_counts = [("transform", 3), ("reset", 2)].into();
+        let mut second = file(1, 10);
+        second.command_counts = [("transform", 1), ("spawn", 1)].into();
+        assert_eq!(
+            stats_table(&total_command_counts(&[first, second]), 2),
+            "Generated commands:\n\
+             \x20 reset              2\n\
+             \x20 spawn              1\n\
+             \x20 transform          4\n\
+             \x20 total              7\n\
+             Tick function lines: 2\n"
+        );
+    }
+
+    #[test]
+    fn long_animations_warn_unless_disabled() {
+        assert_eq!(length_warning(&file(1, 100), Some(100)), None);
+        assert_eq!(
+            length_warning(&file(1, 101), Some(100)).unwrap(),
+            "Warning: 'src/anim.dspa' runs for 101 ticks, longer than the configured 'max_ticks' of 100."
+        );
+        assert_eq!(length_warning(&file(1, u32::MAX), None), None);
+    }
+
+    #[test]
+    fn manifest_lists_animations_in_order() {
+        let animation = |path: &str, object: &str, animation: &str, namespace: Option<&str>| {
+            (
+                path.to_string(),
+                CompiledFile {
+                    object_name: object.to_string(),
+                    animation_name: animation.to_string(),
+                    namespace: namespace.map(str::to_string),
+                    ..file(1, 20)
+                },
+            )
+        };
+        let outputs = [
+            animation("./objects/door.mcfunction", "door", "open", None),
+            animation("./objects/box.mcfunction", "box", "spin", Some("mypack")),
+            animation("./objects/box_shake.mcfunction", "box", "shake", None),
+        ];
+        let entries = manifest_entries(&Config::default(), &outputs);
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| entry.function_path.as_str())
+                .collect_vec(),
+            [
+                "de:objects/box_shake",
+                "de:objects/door",
+                "mypack:objects/box"
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&entries[2]).unwrap(),
+            serde_json::json!({
+                "object": "box",
+                "animation": "spin",
+                "namespace": "mypack",
+                "function_path": "mypack:objects/box",
+                "length_ticks": 20,
+            })
+        );
+
+        let merged = Config {
+            single_file: Some("./all.mcfunction".to_string()),
+            ..Config::default()
+        };
+        assert!(manifest_entries(&merged, &outputs)
+            .iter()
+            .all(|entry| entry.function_path == "de:all"));
+    }
+
+    #[test]
+    fn file_namespace_replaces_config_namespace_in_target() {
+        assert_eq!(
+            namespaced_target("./data/de/function", "de", "mypack").unwrap(),
+            Path::new("./data/mypack/function")
+        );
+        assert_eq!(
+            namespaced_target("./de/data/de/function/de", "de", "mypack").unwrap(),
+            Path::new("./de/data/mypack/function/de")
+        );
+        assert_eq!(
+            namespaced_target("./objects", "de", "de").unwrap(),
+            Path::new("./objects")
+        );
+        assert!(namespaced_target("./objects", "de", "mypack").is_err());
+        assert!(namespaced_target("./de/function", "de", "mypack").is_err());
+    }
+
+    #[test]
+    fn resource_locations_match_across_platforms() {
+        let expected = "de:test/objects/anim";
+        for path in [
+            "test/objects/anim.mcfunction",
+            "./test/objects/anim.mcfunction",
+            ".\\test\\objects\\anim.mcfunction",
+            "./test\\objects/anim.mcfunction",
+            "test//objects/./anim.mcfunction",
+            "C:\\test\\objects\\anim.mcfunction",
+            "C:/test/objects/anim.mcfunction",
+        ] {
+            assert_eq!(
+                resource_location("de", path, "mcfunction"),
+                expected,
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn resource_location_only_strips_trailing_extension() {
+        assert_eq!(
+            resource_location(
+                "de",
+                "objects/mcfunction/anim.mcfunction.mcfunction",
+                "mcfunction"
+            ),
+            "de:objects/mcfunction/anim.mcfunction"
+        );
+    }
+
+    #[test]
+    fn reports_sit_next_to_their_outputs() {
+        let mut compiled = file(1, 20);
+        compiled.report = "tick 0: wait 20t".to_string();
+        let outputs = vec![("data/de/function/cube.mcfunction".to_string(), compiled)];
+        let mut generated = Generated::default();
+        generate_reports(&Config::default(), &outputs, &mut generated);
+        assert_eq!(
+            generated.files,
+            vec![(
+                "data/de/function/cube.timeline.txt".to_string(),
+                "tick 0: wait 20t\n".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn single_files_keep_start_and_stop_functions() {
+        let config = Config {
+            single_file: Some("./all.mcfunction".to_string()),
+            header: None,
+            ..Config::default()
+        };
+        let mut compiled = file(1, 20);
+        compiled.object_name = "cube".to_string();
+        compiled.animation_name = "spin".to_string();
+        let outputs = vec![("./data/de/function/spin.mcfunction".to_string(), compiled)];
+        let mut generated = Generated::default();
+        generate_single_file(&config, "./all.mcfunction", &outputs, &mut generated).unwrap();
+        let paths = generated
+            .files
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect_vec();
+        assert_eq!(
+            paths,
+            [
+                "./all.mcfunction",
+                "./data/de/function/spin_start.mcfunction",
+                "./data/de/function/spin_stop.mcfunction"
+            ]
+        );
+        assert_eq!(
+            generated.files[1].1,
+            compiled::start_function(&config, "cube", "spin")
+        );
+    }
+
+    #[test]
+    fn load_tag_keeps_existing_values() {
+        let tag = with_tag_value(None, "de:load").unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&tag).unwrap(),
+            serde_json::json!({ "values": ["de:load"] })
+        );
+        let tag = with_tag_value(
+            Some(r#"{"replace":false,"values":["other:init"]}"#),
+            "de:load",
+        )
+        .unwrap();
+        let tag = with_tag_value(Some(&tag), "de:load").unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&tag).unwrap(),
+            serde_json::json!({ "replace": false, "values": ["other:init", "de:load"] })
+        );
+        assert!(with_tag_value(Some("{}"), "de:load").is_err());
+    }
+
+    #[test]
+    fn load_function_creates_configured_objectives() {
+        let config = Config {
+            timer_objective: "dspa_timer".to_string(),
+            ..Config::default()
+        };
+        assert_eq!(
+            compiled::load_function(&config),
+            "scoreboard objectives add dspa_timer dummy\nscoreboard objectives add flags dummy"
+        );
+    }
+
+    #[test]
+    fn parallel_maps_keep_the_order_of_their_items() {
+        let items = (0..50).collect_vec();
+        let squares = items.iter().map(|item| item * item).collect_vec();
+        for jobs in [1, 4, 64] {
+            assert_eq!(parallel_map(&items, jobs, |item| item * item), squares);
+        }
+        assert!(parallel_map(&[] as &[u32], 4, |item| *item).is_empty());
+    }
+}
@@ -1,102 +1,705 @@
 use std::{
-    fmt::Display,
+    collections::HashSet,
+    env,
+    fmt::Write as _,
     fs,
-    io::{stdin, Read, Write},
-    path::PathBuf,
-    str::FromStr,
+    io::{stdin, IsTerminal, Read, Write},
+    iter,
+    path::Path,
+    process::ExitCode,
 };
 
 use anyhow::ensure;
-use file_reader::parse_file;
-use walkdir::WalkDir;
-
-use crate::errors::GenericError;
-
-mod compiled;
-mod config;
-mod errors;
-mod file_reader;
-mod objects;
-mod statements;
-
-fn get_folder_tree(path: PathBuf) -> Vec<String> {
-    WalkDir::new(path)
-        .into_iter()
-        .filter_map(|path| {
-            if let Err(ref err) = path {
-                println!(
-                    "{}",
-                    GenericError::InvalidPath(String::from("<none>"), err.to_string())
-                );
-            }
-            path.ok()
-        })
-        .filter(|path| {
-            path.path()
-                .extension()
-                .is_some_and(|e| e == DISPA_EXTENSION)
+use display_animations::{
+    compiled::{self, CompiledFile},
+    config::{self, Config},
+    errors::{ErrorCollection, GenericError, Severity},
+    file_reader::parse_file,
+    length_warning, Generated, ListedAnimation, Summary,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use itertools::Itertools;
+use log::{log_enabled, Level};
+use similar::TextDiff;
+
+mod logger;
+
+/// Skips the "Press Enter" pause, for running from scripts.
+const NO_WAIT_FLAG: &str = "--no-wait";
+/// Suppresses the per-file lines, leaving only the summary.
+const QUIET_FLAG: &str = "--quiet";
+/// Fails the run if there are any warnings, instead of only printing them.
+const DENY_WARNINGS_FLAG: &str = "--deny-warnings";
+/// Prints the version and the commit it was built from, then exits without compiling.
+const VERSION_FLAG: &str = "--version";
+/// Reads the config from the following path instead of the default `dspa_config.json` or `dspa_config.toml`.
+const CONFIG_FLAG: &str = "--config";
+/// Compiles the file given after it to stdout, or to the path after `-o`, without walking the source folders.
+const COMPILE_COMMAND: &str = "compile";
+const COMPILE_USAGE: &str = "compile <file> [-o <output>]";
+const OUTPUT_FLAG: &str = "-o";
+/// Sets up a new project: the config, its folders, an empty tick function and an example animation.
+const INIT_COMMAND: &str = "init";
+/// Lets `init` overwrite an existing config.
+const FORCE_FLAG: &str = "--force";
+/// Shows how the generated files would change instead of writing them, failing if any would.
+const DIFF_FLAG: &str = "--diff";
+/// Checks every generated command for malformed SNBT and `execute` chains, as the config's `verify` does.
+const VERIFY_FLAG: &str = "--verify";
+/// Compiles at most the following number of files at once, overriding the config's `jobs`. `--jobs 1`
+/// compiles them one after another, which keeps `-vv` logs in file order.
+const JOBS_FLAG: &str = "--jobs";
+/// Prints how many commands of each kind were generated, and how long the tick function is.
+const STATS_FLAG: &str = "--stats";
+/// Lists every source file with its animation and output path, then exits without compiling.
+const LIST_FLAG: &str = "--list";
+/// Removes every generated file and empties the tick function before compiling, so the run only
+/// regenerates the animations that still have sources.
+const CLEAN_FLAG: &str = "--clean";
+/// Makes `--clean` list what it would remove instead of removing it, then exit without compiling.
+const DRY_RUN_FLAG: &str = "--dry-run";
+/// Logs how each file is compiled and where it is written to stderr. `-vv` also logs every statement.
+const VERBOSE_FLAG: &str = "-v";
+const VERY_VERBOSE_FLAG: &str = "-vv";
+
+/// How many levels of logging were asked for, counting `-vv` as two.
+fn verbosity() -> usize {
+    env::args()
+        .skip(1)
+        .map(|arg| match arg.as_str() {
+            VERBOSE_FLAG => 1,
+            VERY_VERBOSE_FLAG => 2,
+            _ => 0,
         })
-        .filter_map(|path| path.into_path().into_os_string().into_string().ok())
-        .collect::<Vec<_>>()
+        .sum()
+}
+
+fn has_flag(flag: &str) -> bool {
+    env::args().skip(1).any(|arg| arg == flag)
 }
 
-/// Collects all the 'Ok' values in the input and flattens the Results into the output.
+/// Returns the argument following `flag`, e.g. the path in `--config <path>`.
+fn flag_value(flag: &str) -> Option<String> {
+    env::args().skip_while(|arg| arg != flag).nth(1)
+}
+
+fn main() -> ExitCode {
+    logger::init(logger::level(verbosity()));
+    if has_flag(VERSION_FLAG) {
+        println!("DiSPA {} ({})", compiled::VERSION, compiled::GIT_HASH);
+        return ExitCode::SUCCESS;
+    }
+    if env::args().nth(1).as_deref() == Some(COMPILE_COMMAND) {
+        return match compile_single(
+            env::args().nth(2).as_deref(),
+            flag_value(OUTPUT_FLAG).as_deref(),
+        ) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    if env::args().nth(1).as_deref() == Some(INIT_COMMAND) {
+        return match init_project(has_flag(FORCE_FLAG)) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    if has_flag(CLEAN_FLAG) {
+        let dry_run = has_flag(DRY_RUN_FLAG);
+        let result = config::read(flag_value(CONFIG_FLAG).as_deref())
+            .and_then(|config| clean(&config, dry_run));
+        if let Err(err) = result {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+        if dry_run {
+            return ExitCode::SUCCESS;
+        }
+    }
+    if has_flag(LIST_FLAG) {
+        return match list_animations() {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    let mut summary = Summary::default();
+    let result = run(
+        &mut summary,
+        has_flag(QUIET_FLAG),
+        has_flag(DENY_WARNINGS_FLAG),
+        has_flag(DIFF_FLAG),
+    );
+    if let Err(err) = &result {
+        eprintln!("{err}");
+    }
+    println!("{summary}");
+    if stdin().is_terminal() && !has_flag(NO_WAIT_FLAG) {
+        println!("Press Enter to continue...");
+        let _ = std::io::stdout().flush();
+        let _ = stdin().read(&mut [0_u8]);
+    }
+    if result.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// A bar counting compiled files, shown only when writing to a terminal and not running `--quiet`.
+/// Its length is set once the sources are found. It is cleared before anything else is printed, so
+/// it never interleaves with the per-file lines.
+fn progress_bar(quiet: bool) -> anyhow::Result<ProgressBar> {
+    if quiet || !std::io::stdout().is_terminal() {
+        return Ok(ProgressBar::hidden());
+    }
+    let progress = ProgressBar::new(0);
+    progress.set_style(ProgressStyle::with_template(
+        "[{bar:40}] {pos}/{len} {wide_msg}",
+    )?);
+    Ok(progress)
+}
+
+/// Prints every warning from the compiled files, in yellow when writing to a terminal.
 ///
 /// # Errors
-/// If any of the results in the input are Err, this returns a `GenericError::Collection` containing all of the errors.
-pub fn collect_errors<T, E: Display>(input: Vec<Result<T, E>>) -> anyhow::Result<Vec<T>> {
-    let errors = input
+/// If `deny_warnings` is set and there are any warnings, they are returned as a `GenericError::DeniedWarnings` instead.
+fn report_warnings(
+    config: &Config,
+    files: &[CompiledFile],
+    deny_warnings: bool,
+) -> anyhow::Result<()> {
+    let severity = if deny_warnings {
+        Severity::Error
+    } else {
+        Severity::Warning
+    };
+    let warnings: Vec<String> = files
         .iter()
-        .enumerate()
-        .filter_map(|(index, element)| element.as_ref().err().map(|e| (index, e)))
-        .fold(String::new(), |mut acc, err| {
-            acc.push_str(&format!("{}: {}\n", err.0, err.1));
-            acc
-        });
-    ensure!(errors.is_empty(), GenericError::Collection(errors));
-    Ok(input.into_iter().filter_map(Result::ok).collect())
-}
-
-const DISPA_EXTENSION: &str = "dspa";
-const MINECRAFT_EXTENSION: &str = "mcfunction";
-
-fn main() -> anyhow::Result<()> {
-    let config = config::read()?;
-    let files = get_folder_tree(
-        PathBuf::from_str(&config.source_folder).expect("PathBuf::from_str is infallable."),
+        .flat_map(|compiled| {
+            compiled
+                .warnings
+                .iter()
+                .map(|warning| warning.clone().with_severity(severity).to_string())
+                .chain(length_warning(compiled, config.max_ticks))
+        })
+        .collect();
+    ensure!(
+        !deny_warnings || warnings.is_empty(),
+        GenericError::DeniedWarnings(warnings.join("\n"))
     );
-    let results = files.into_iter().map(|path| parse_file(&path)).collect();
-    fs::write(&config.tick_function, "")
-        .map_err(|_| GenericError::FileNotExist(config.tick_function.clone()))?;
-    for result in collect_errors(results)? {
-        let path: String = result
-            .path
-            .replace(&config.source_folder, &config.target_folder)
-            .replace(DISPA_EXTENSION, MINECRAFT_EXTENSION);
-        fs::write(&path, result.contents).map_err(|_| GenericError::FileNotExist(path.clone()))?;
-        let filtered_path = path
-            .replace('\\', "/")
-            .strip_prefix("./")
-            .unwrap_or(&path)
-            .replace(&format!(".{MINECRAFT_EXTENSION}"), "");
-        let mut tick_function = fs::OpenOptions::new()
-            .append(true)
-            .open(&config.tick_function)?;
-        writeln!(
-            tick_function,
-            "{}",
-            compiled::tick_function_line(
-                &result.object_name,
-                &result.animation_name,
-                &config.namespace,
-                &filtered_path
+    let colored = std::io::stderr().is_terminal();
+    for warning in warnings {
+        if colored {
+            eprintln!("\x1b[33m{warning}\x1b[0m");
+        } else {
+            eprintln!("{warning}");
+        }
+    }
+    Ok(())
+}
+
+/// Compiles one file on its own, using the config if there is one but ignoring its folders.
+fn compile_single(path: Option<&str>, output: Option<&str>) -> anyhow::Result<()> {
+    let path = path
+        .filter(|path| *path != OUTPUT_FLAG)
+        .ok_or(GenericError::Usage(COMPILE_USAGE))?;
+    let mut config = config::read_if_present(flag_value(CONFIG_FLAG).as_deref())?;
+    config.verify |= has_flag(VERIFY_FLAG);
+    let compiled = parse_file(path, &config)?;
+    let contents = compiled::with_header(&config, &compiled.contents);
+    match output {
+        Some(output) => {
+            fs::write(output, contents)
+                .map_err(|_| GenericError::FileNotExist(output.to_string()))?;
+        }
+        None => println!("{contents}"),
+    }
+    Ok(())
+}
+
+/// The animation `init` puts in a new project, showing the transformations and spawning.
+const EXAMPLE_ANIMATION: &str = "\
+# An example animation. Summon a display entity tagged 'hello' and 'base', compile the project,
+# then run the generated 'hello_start' function to play it.
+object hello:wave
+spawn base block_display cube
+block cube stone
+move cube 0 1 0 20
+turn cube y 90 20
+wait 20
+size cube 2 10
+wait 10
+";
+
+/// Writes every file, creating the folders they go in, since nested source folders are mirrored
+/// into the target folder.
+fn write(generated: &Generated) -> anyhow::Result<()> {
+    for (path, contents) in &generated.files {
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent).map_err(|_| GenericError::FileNotExist(path.clone()))?;
+        }
+        fs::write(path, contents).map_err(|_| GenericError::FileNotExist(path.clone()))?;
+    }
+    Ok(())
+}
+
+/// Prints a unified diff of every file that would change, and lists the files that would be created,
+/// and the previously generated files that would no longer be generated. Files without the header's
+/// marker were written by hand, so they are never counted as removed.
+///
+/// # Errors
+/// If anything would change, this returns a `GenericError::OutputsDiffer`, so CI can catch stale outputs.
+fn diff(generated: &Generated, config: &Config) -> anyhow::Result<()> {
+    let mut changed = 0;
+    for (path, contents) in &generated.files {
+        match fs::read_to_string(path) {
+            Ok(existing) if existing == *contents => continue,
+            Ok(existing) => print!(
+                "{}",
+                TextDiff::from_lines(&existing, contents)
+                    .unified_diff()
+                    .header(path, path)
             ),
-        )?;
-        println!("Successfully Compiled file: {filtered_path}");
+            Err(_) => println!("New file: {path}"),
+        }
+        changed += 1;
+    }
+    let paths: HashSet<&Path> = generated
+        .files
+        .iter()
+        .map(|(path, _)| Path::new(path))
+        .collect();
+    for path in display_animations::generated_outputs(config) {
+        if !paths.contains(Path::new(&path)) {
+            println!("Removed file: {path}");
+            changed += 1;
+        }
+    }
+    ensure!(changed == 0, GenericError::OutputsDiffer(changed));
+    Ok(())
+}
+
+/// Writes the default config, then sets up the project it describes. An existing config is only
+/// overwritten with `--force`.
+fn init_project(force: bool) -> anyhow::Result<()> {
+    let config = config::initialize(flag_value(CONFIG_FLAG).as_deref(), force)?;
+    for path in scaffold(&config)? {
+        println!("Created '{path}'");
+    }
+    Ok(())
+}
+
+/// Creates the config's folders, an empty tick function and `hello.dspa` in the first source folder,
+/// returning the files it created. Files that already exist are left alone.
+///
+/// # Errors
+/// If a folder or file can't be created, this returns a `GenericError::FileNotExist`.
+fn scaffold(config: &Config) -> anyhow::Result<Vec<String>> {
+    for root in config.source_folder.roots() {
+        fs::create_dir_all(root).map_err(|_| GenericError::FileNotExist(root.to_string()))?;
+    }
+    config.validate()?;
+    let example = config.source_folder.roots().next().map(|root| {
+        (
+            format!("{root}/hello.{}", config.source_extension),
+            EXAMPLE_ANIMATION,
+        )
+    });
+    let mut created = Vec::new();
+    for (path, contents) in iter::once((config.tick_function.clone(), "")).chain(example) {
+        if !Path::new(&path).exists() {
+            fs::write(&path, contents).map_err(|_| GenericError::FileNotExist(path.clone()))?;
+            created.push(path);
+        }
+    }
+    Ok(created)
+}
+
+fn run(summary: &mut Summary, quiet: bool, deny_warnings: bool, diff: bool) -> anyhow::Result<()> {
+    let mut config = config::read(flag_value(CONFIG_FLAG).as_deref())?;
+    config.verify |= has_flag(VERIFY_FLAG);
+    if let Some(jobs) = flag_value(JOBS_FLAG) {
+        config.jobs = Some(
+            jobs.parse()
+                .map_err(|_| GenericError::InvalidJobs(jobs.clone()))?,
+        );
+    }
+    config.validate()?;
+    build(&config, summary, quiet, deny_warnings, diff)
+}
+
+/// Compiles every source and generates the files they make up. Every file is generated before the
+/// first is written, so a run that fails partway, such as on a file that doesn't compile, leaves
+/// the previous outputs and tick function as they were.
+fn build(
+    config: &Config,
+    summary: &mut Summary,
+    quiet: bool,
+    deny_warnings: bool,
+    diff: bool,
+) -> anyhow::Result<()> {
+    // Log records would be drawn over by the bar, so it is hidden while they are shown.
+    let progress = progress_bar(quiet || log_enabled!(Level::Debug))?;
+    let report = display_animations::compile_directory_with(config, |path, total| {
+        progress.set_length(u64::try_from(total).unwrap_or(u64::MAX));
+        progress.set_message(path.to_string());
+        progress.inc(1);
+    });
+    progress.finish_and_clear();
+    let report = report?;
+    *summary = report.summary;
+    if !report.errors.is_empty() {
+        let errors = report.errors.into_iter().enumerate().collect();
+        return Err(ErrorCollection { errors }.into());
+    }
+    report_warnings(config, &report.files, deny_warnings)?;
+    if has_flag(STATS_FLAG) {
+        print!(
+            "{}",
+            display_animations::stats_table(
+                &display_animations::total_command_counts(&report.files),
+                report.tick_lines.len()
+            )
+        );
     }
 
-    println!("Press Enter to continue...");
-    let _ = std::io::stdout().flush();
-    let _ = stdin().read(&mut [0_u8]);
+    if diff {
+        self::diff(&report.generated, config)
+    } else {
+        // Nothing is written in `--diff` mode, so there is nothing to report compiling.
+        if !quiet {
+            for message in &report.generated.messages {
+                println!("{message}");
+            }
+        }
+        write(&report.generated)
+    }
+}
+
+/// Lists every animation each source file declares, one per section, and where it would be written,
+/// reading only each file's directives so nothing is compiled. A missing config isn't created.
+///
+/// # Errors
+/// If any header fails to compile, this returns an `ErrorCollection` of every failure.
+fn list_animations() -> anyhow::Result<()> {
+    let config = config::read_if_present(flag_value(CONFIG_FLAG).as_deref())?;
+    print!(
+        "{}",
+        list_table(&display_animations::list_animations(&config)?)
+    );
     Ok(())
 }
+
+/// Removes every file a run could have generated that starts with the header's marker, and empties the
+/// tick function if it does, printing each path. Files without the marker were written by hand, so they
+/// are always left alone. With `dry_run`, the paths are only listed.
+///
+/// # Errors
+/// If the header is empty, nothing can be told apart, and this returns a `GenericError::NoGeneratedMarker`.
+fn clean(config: &Config, dry_run: bool) -> anyhow::Result<()> {
+    let marker = compiled::generated_marker(config).ok_or(GenericError::NoGeneratedMarker)?;
+    for path in &display_animations::generated_outputs(config) {
+        if dry_run {
+            println!("Would remove: {path}");
+        } else {
+            fs::remove_file(path).map_err(|_| GenericError::FileNotExist(path.clone()))?;
+            println!("Removed: {path}");
+        }
+    }
+    let tick_function = &config.tick_function;
+    if display_animations::is_generated(tick_function, &marker) {
+        if dry_run {
+            println!("Would empty: {tick_function}");
+        } else {
+            fs::write(tick_function, compiled::with_header(config, ""))
+                .map_err(|_| GenericError::FileNotExist(tick_function.clone()))?;
+            println!("Emptied: {tick_function}");
+        }
+    }
+    Ok(())
+}
+
+/// Lays out the listed animations in aligned columns, marking every animation declared more than once.
+fn list_table(animations: &[ListedAnimation]) -> String {
+    let width = |column: fn(&ListedAnimation) -> &str, title: &str| {
+        animations
+            .iter()
+            .map(|animation| column(animation).len())
+            .chain([title.len()])
+            .max()
+            .unwrap_or_default()
+    };
+    let source_width = width(|animation| &animation.source, "Source");
+    let animation_width = width(|animation| &animation.animation, "Animation");
+    let mut table = format!(
+        "{:<source_width$}  {:<animation_width$}  Output\n",
+        "Source", "Animation"
+    );
+    let counts = animations
+        .iter()
+        .map(|animation| animation.animation.as_str())
+        .counts();
+    for animation in animations {
+        let marker = if counts[animation.animation.as_str()] > 1 {
+            "  (duplicate)"
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            table,
+            "{:<source_width$}  {:<animation_width$}  {}{marker}",
+            animation.source, animation.animation, animation.output
+        );
+    }
+    table
+}
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use display_animations::{compile_directory, file_reader::compile_str};
+
+    /// A config for a project under `root`, which is emptied first so earlier runs don't leak in.
+    fn project_config(root: &str) -> Config {
+        let _ = fs::remove_dir_all(root);
+        Config {
+            source_folder: config::SourceFolders::Single(format!("{root}/src")),
+            target_folder: format!("{root}/objects"),
+            tick_function: format!("{root}/functions/tick.mcfunction"),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn denied_warnings_fail_the_run() {
+        let config = Config {
+            max_ticks: Some(10),
+            ..Config::default()
+        };
+        let files = [compile_str("wait 20", "src/anim.dspa", &config).unwrap()];
+        assert!(report_warnings(&config, &files, false).is_ok());
+        assert!(report_warnings(&config, &files, true).is_err());
+        assert!(report_warnings(&Config::default(), &files, true).is_ok());
+    }
+
+    #[test]
+    fn scaffolded_projects_compile() {
+        let root = "./target/scaffolded_project";
+        let config = project_config(root);
+        let example = format!("{root}/src/hello.dspa");
+        assert_eq!(
+            scaffold(&config).unwrap(),
+            [config.tick_function.clone(), example.clone()]
+        );
+        assert!(Path::new(&config.target_folder).is_dir());
+        let compiled = parse_file(&example, &config).unwrap();
+        assert_eq!(compiled.object_name, "hello");
+        assert!(compiled.warnings.is_empty());
+
+        fs::write(&example, "wait 1").unwrap();
+        assert!(scaffold(&config).unwrap().is_empty());
+        assert_eq!(fs::read_to_string(&example).unwrap(), "wait 1");
+    }
+
+    #[test]
+    fn nested_sources_compile_into_an_empty_target() {
+        let root = "./target/nested_sources";
+        let config = project_config(root);
+        fs::create_dir_all(format!("{root}/src/doors/front")).unwrap();
+        fs::write(
+            format!("{root}/src/doors/front/open.dspa"),
+            "move door 0 1 0 20\nwait 20",
+        )
+        .unwrap();
+        build(&config, &mut Summary::default(), true, false, false).unwrap();
+        for path in [
+            "objects/doors/front/open.mcfunction",
+            "objects/doors/front/open_start.mcfunction",
+            "functions/tick.mcfunction",
+        ] {
+            assert!(Path::new(&format!("{root}/{path}")).is_file(), "{path}");
+        }
+    }
+
+    #[test]
+    fn failed_runs_leave_the_tick_function_alone() {
+        let root = "./target/failed_run";
+        let config = project_config(root);
+        fs::create_dir_all(format!("{root}/src")).unwrap();
+        fs::create_dir_all(format!("{root}/objects")).unwrap();
+        fs::create_dir_all(format!("{root}/functions")).unwrap();
+        fs::write(format!("{root}/src/a.dspa"), "move door 0 1 0 20").unwrap();
+        fs::write(format!("{root}/src/b.dspa"), "move door 0 1").unwrap();
+        fs::write(&config.tick_function, "function de:objects/old\n").unwrap();
+
+        assert!(build(&config, &mut Summary::default(), true, false, false).is_err());
+        assert_eq!(
+            fs::read_to_string(&config.tick_function).unwrap(),
+            "function de:objects/old\n"
+        );
+        assert!(!Path::new(&format!("{root}/objects/a.mcfunction")).exists());
+
+        fs::write(format!("{root}/src/b.dspa"), "move door 0 1 0 20").unwrap();
+        build(&config, &mut Summary::default(), true, false, false).unwrap();
+        let tick_function = fs::read_to_string(&config.tick_function).unwrap();
+        assert!(!tick_function.contains("old"), "{tick_function}");
+        assert_eq!(
+            tick_function.matches(" run function ").count(),
+            2,
+            "{tick_function}"
+        );
+    }
+
+    #[test]
+    fn compiling_a_directory_writes_nothing() {
+        let root = "./target/compile_directory";
+        let config = project_config(root);
+        fs::create_dir_all(format!("{root}/src")).unwrap();
+        fs::write(
+            format!("{root}/src/a.dspa"),
+            "move door 0 1 0 20\nwait 20\nobject lamp:close\nmove door 0 -1 0 20",
+        )
+        .unwrap();
+        fs::write(format!("{root}/src/b.dspa"), "move door 0 1").unwrap();
+
+        let report = compile_directory(&config).unwrap();
+        assert_eq!(report.files.len(), 2);
+        assert_eq!(
+            report.outputs,
+            [
+                format!("{root}/objects/a.mcfunction"),
+                format!("{root}/objects/a_close.mcfunction"),
+            ]
+        );
+        assert_eq!(report.errors.len(), 1);
+        assert!(report.tick_lines.is_empty());
+
+        fs::write(format!("{root}/src/b.dspa"), "move door 0 1 0 20").unwrap();
+        let report = compile_directory(&config).unwrap();
+        assert!(report.errors.is_empty());
+        assert_eq!(report.files.len(), 3);
+        assert_eq!(
+            report.tick_lines,
+            [
+                "execute if score $a-a flags matches 1.. run function de:target/compile_directory/objects/a",
+                "execute if score $lamp-close flags matches 1.. run function de:target/compile_directory/objects/a_close",
+                "execute if score $b-b flags matches 1.. run function de:target/compile_directory/objects/b",
+            ]
+        );
+        assert!(!Path::new(&format!("{root}/objects")).exists());
+        assert!(!Path::new(&config.tick_function).exists());
+    }
+
+    #[test]
+    fn clean_only_removes_generated_files() {
+        let root = "./target/clean";
+        let _ = fs::remove_dir_all(root);
+        fs::create_dir_all(format!("{root}/data/de/function/doors")).unwrap();
+        fs::create_dir_all(format!("{root}/data/fan/function")).unwrap();
+        let config = Config {
+            target_folder: format!("{root}/data/de/function/doors"),
+            tick_function: format!("{root}/data/de/function/tick.mcfunction"),
+            load_function: Some(format!("{root}/load.mcfunction")),
+            ..Config::default()
+        };
+        let old = "# File generated using DiSPA v0.0.1\nsay old";
+        let generated = [
+            format!("{root}/data/de/function/doors/gone.mcfunction"),
+            format!("{root}/data/fan/function/doors/spin.mcfunction"),
+            format!("{root}/data/de/function/tick_door.mcfunction"),
+            format!("{root}/data/de/function/tick_merge.mcfunction"),
+            format!("{root}/load.mcfunction"),
+        ];
+        for path in &generated {
+            fs::create_dir_all(Path::new(path).parent().unwrap()).unwrap();
+            fs::write(path, old).unwrap();
+        }
+        let hand_written = [
+            format!("{root}/data/de/function/doors/helper.mcfunction"),
+            format!("{root}/data/de/function/tick_helper.mcfunction"),
+        ];
+        for path in &hand_written {
+            fs::write(path, format!("say hi\n{old}")).unwrap();
+        }
+        fs::write(&config.tick_function, old).unwrap();
+
+        clean(&config, true).unwrap();
+        assert!(generated.iter().all(|path| Path::new(path).exists()));
+        assert_eq!(fs::read_to_string(&config.tick_function).unwrap(), old);
+
+        clean(&config, false).unwrap();
+        for path in &generated {
+            assert!(!Path::new(path).exists(), "{path}");
+        }
+        assert!(hand_written.iter().all(|path| Path::new(path).exists()));
+        assert_eq!(
+            fs::read_to_string(&config.tick_function).unwrap(),
+            compiled::with_header(&config, "")
+        );
+
+        let headerless = Config {
+            header: None,
+            ..config
+        };
+        assert!(clean(&headerless, false).is_err());
+    }
+
+    #[test]
+    fn diffs_leave_hand_written_files_out() {
+        let root = "./target/diff";
+        let _ = fs::remove_dir_all(root);
+        fs::create_dir_all(format!("{root}/objects")).unwrap();
+        let config = Config {
+            target_folder: format!("{root}/objects"),
+            ..Config::default()
+        };
+        let path = format!("{root}/objects/a.mcfunction");
+        let mut generated = Generated::default();
+        generated.add(&config, &path, "say a");
+        write(&generated).unwrap();
+        fs::write(format!("{root}/objects/helper.mcfunction"), "say hi").unwrap();
+        diff(&generated, &config).unwrap();
+
+        fs::write(
+            format!("{root}/objects/gone.mcfunction"),
+            compiled::with_header(&config, "say gone"),
+        )
+        .unwrap();
+        assert!(diff(&generated, &config).is_err());
+    }
+
+    #[test]
+    fn list_marks_repeated_animations() {
+        let listed = |source: &str, animation: &str, output: &str| ListedAnimation {
+            source: source.to_string(),
+            animation: animation.to_string(),
+            output: output.to_string(),
+        };
+        let table = list_table(&[
+            listed("./src/open.dspa", "door:open", "./objects/open.mcfunction"),
+            listed(
+                "./src/a/open.dspa",
+                "door:open",
+                "./objects/a/open.mcfunction",
+            ),
+            listed("./src/spin.dspa", "fan:spin", "./objects/spin.mcfunction"),
+        ]);
+        assert_eq!(
+            table,
+            "Source             Animation  Output\n\
+             ./src/open.dspa    door:open  ./objects/open.mcfunction  (duplicate)\n\
+             ./src/a/open.dspa  door:open  ./objects/a/open.mcfunction  (duplicate)\n\
+             ./src/spin.dspa    fan:spin   ./objects/spin.mcfunction\n"
+        );
+    }
+}
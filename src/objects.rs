@@ -5,6 +5,7 @@ use std::{
 
 use itertools::Itertools;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 
 use crate::errors::{CompileErrorType as ErrorType, GenericError};
 
@@ -27,21 +28,108 @@ impl Translation {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AngleUnit {
+    #[default]
+    Degrees,
+    Radians,
+}
+impl AngleUnit {
+    pub const fn to_radians(self, angle: f32) -> f32 {
+        match self {
+            Self::Degrees => angle.to_radians(),
+            Self::Radians => angle,
+        }
+    }
+}
+
+/// How a rotation was written: as an angle about an axis, or as a raw `[x, y, z, w]` quaternion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Orientation {
+    AxisAngle {
+        axis: [f32; 3],
+        angle: f32,
+        unit: AngleUnit,
+    },
+    Quaternion([f32; 4]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Rotation {
-    pub axis: [f32; 3],
-    pub angle: f32,
+    pub orientation: Orientation,
+    pub pivot: Option<Translation>,
 }
 impl Rotation {
-    pub const fn new(axis: [f32; 3], angle: f32) -> Self {
-        Self { axis, angle }
+    pub const fn new(
+        axis: [f32; 3],
+        angle: f32,
+        unit: AngleUnit,
+        pivot: Option<Translation>,
+    ) -> Self {
+        Self {
+            orientation: Orientation::AxisAngle { axis, angle, unit },
+            pivot,
+        }
+    }
+    /// Creates a rotation from `[x, y, z, w]` components, which are normalized when compiled.
+    pub const fn from_quaternion(quaternion: [f32; 4], pivot: Option<Translation>) -> Self {
+        Self {
+            orientation: Orientation::Quaternion(quaternion),
+            pivot,
+        }
+    }
+    /// Splits a rotation of more than a full turn into quarter-turn keyframes.
+    /// A single quaternion cannot tell 720° from 0°, and interpolation takes the shortest path,
+    /// so each keyframe has to stay small enough for the spin to play out in the intended direction.
+    /// Rotations of up to one turn, and quaternion rotations, are returned unchanged as a single keyframe.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn keyframes(&self) -> Vec<Self> {
+        let Orientation::AxisAngle { axis, angle, unit } = self.orientation else {
+            return vec![*self];
+        };
+        let turns = unit.to_radians(angle).abs() / std::f32::consts::TAU;
+        if turns <= 1.0 {
+            return vec![*self];
+        }
+        let steps = (turns * 4.0).ceil() as u32;
+        (1..=steps)
+            .map(|step| Self::new(axis, angle * step as f32 / steps as f32, unit, self.pivot))
+            .collect()
+    }
+    fn quaternion(&self) -> quaternion_core::Quaternion<f32> {
+        match self.orientation {
+            Orientation::AxisAngle { axis, angle, unit } => {
+                quaternion_core::from_axis_angle(axis, unit.to_radians(angle))
+            }
+            Orientation::Quaternion([x, y, z, w]) => quaternion_core::normalize((w, [x, y, z])),
+        }
     }
     pub fn compile(&self) -> String {
-        let quaternion = quaternion_core::from_axis_angle(self.axis, self.angle.to_radians());
-        format!(
+        let quaternion = self.quaternion();
+        let rotation = format!(
             "left_rotation: [{}f,{}f,{}f,{}f]",
             quaternion.1[0], quaternion.1[1], quaternion.1[2], quaternion.0,
-        )
+        );
+        match self.pivot {
+            None => rotation,
+            Some(pivot) => {
+                // Rotating about a pivot `p` is `translate(p) * rotate * translate(-p)`,
+                // which collapses to a single translation of `p - rotate(p)`.
+                let pivot = [pivot.x, pivot.y, pivot.z];
+                let rotated = quaternion_core::point_rotation(quaternion, pivot);
+                let offset = Translation::new((
+                    pivot[0] - rotated[0],
+                    pivot[1] - rotated[1],
+                    pivot[2] - rotated[2],
+                ));
+                format!("{},{rotation}", offset.compile())
+            }
+        }
     }
 }
 
@@ -59,10 +147,48 @@ impl Scale {
             z: coordinates.2,
         }
     }
+    /// The scale of an entity that hasn't been given one.
+    pub const IDENTITY: Self = Self::new((1.0, 1.0, 1.0));
+
+    /// Flips the sign of the scale along `axis`.
+    pub fn mirrored(self, axis: Axis) -> Self {
+        match axis {
+            Axis::X => Self { x: -self.x, ..self },
+            Axis::Y => Self { y: -self.y, ..self },
+            Axis::Z => Self { z: -self.z, ..self },
+        }
+    }
     pub fn compile(&self) -> String {
         format!("scale: [{}f,{}f,{}f]", self.x, self.y, self.z)
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+impl Axis {
+    pub fn new(string: &str) -> Result<Self, ErrorType<'_>> {
+        match string {
+            "x" => Ok(Self::X),
+            "y" => Ok(Self::Y),
+            "z" => Ok(Self::Z),
+            _ => Err(ErrorType::InvalidAxis(string)),
+        }
+    }
+}
+impl Display for Axis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::X => "x",
+            Self::Y => "y",
+            Self::Z => "z",
+        };
+        write!(f, "{name}")
+    }
+}
 // #[derive(Debug, Default, Clone, Copy)]
 // pub struct Transformation {
 //     pub translation: Translation,
@@ -84,6 +210,86 @@ impl Scale {
 //     }
 // }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextComponent {
+    Plain(String),
+    Json(String),
+}
+impl TextComponent {
+    /// Returns the component as JSON; plain text becomes a JSON string.
+    pub fn compile(&self) -> String {
+        match self {
+            Self::Plain(text) => serde_json::Value::String(text.clone()).to_string(),
+            Self::Json(json) => json.clone(),
+        }
+    }
+    /// Returns this component with its root text color set to `color`.
+    pub fn with_color(&self, color: &TextColor) -> Self {
+        let color = serde_json::Value::String(color.compile());
+        let component = match self {
+            Self::Plain(text) => serde_json::json!({ "text": text, "color": color }),
+            Self::Json(json) => match serde_json::from_str(json) {
+                Ok(serde_json::Value::Object(mut object)) => {
+                    object.insert("color".to_string(), color);
+                    serde_json::Value::Object(object)
+                }
+                Ok(other) => serde_json::json!({ "text": "", "extra": [other], "color": color }),
+                Err(_) => serde_json::json!({ "text": json, "color": color }),
+            },
+        };
+        Self::Json(component.to_string())
+    }
+}
+
+/// A text component color, either one of Minecraft's named colors or a `#RRGGBB` hex code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextColor {
+    Named(&'static str),
+    Hex(u32),
+}
+impl TextColor {
+    pub const NAMES: [&'static str; 16] = [
+        "black",
+        "dark_blue",
+        "dark_green",
+        "dark_aqua",
+        "dark_red",
+        "dark_purple",
+        "gold",
+        "gray",
+        "dark_gray",
+        "blue",
+        "green",
+        "aqua",
+        "red",
+        "light_purple",
+        "yellow",
+        "white",
+    ];
+
+    pub fn new(string: &str) -> Result<Self, ErrorType<'_>> {
+        if let Some(hex) = string.strip_prefix('#') {
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(ErrorType::InvalidColor(string));
+            }
+            return u32::from_str_radix(hex, 16)
+                .map(Self::Hex)
+                .map_err(|_| ErrorType::InvalidColor(string));
+        }
+        Self::NAMES
+            .iter()
+            .find(|&&name| name == string.to_lowercase())
+            .map(|&name| Self::Named(name))
+            .ok_or(ErrorType::InvalidColor(string))
+    }
+    pub fn compile(&self) -> String {
+        match self {
+            Self::Named(name) => (*name).to_string(),
+            Self::Hex(rgb) => format!("#{rgb:06X}"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BlockState {
     id: String,
@@ -99,30 +305,213 @@ impl BlockState {
         let states = self
             .state
             .iter()
-            .map(|(key, value)| format!("{key}:\"{value}\""))
+            .map(|(key, value)| format!("{key}:\"{}\"", value.replace('\\', "\\\\")))
             .join(",");
         if states.is_empty() {
             format!("Name:\"{id}\"")
         } else {
-            format!("Name:\"id\",Properties:{{{states}}}")
+            format!("Name:\"{id}\",Properties:{{{states}}}")
+        }
+    }
+}
+/// Writes the block state the way it is written in source, e.g. `minecraft:oak_log[axis=y]`.
+impl Display for BlockState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.id)?;
+        if !self.state.is_empty() {
+            let states = self
+                .state
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .join(",");
+            write!(f, "[{states}]")?;
         }
+        Ok(())
     }
 }
 
+/// A target of a statement: either a single named entity, or every entity in a `group:<tag>`.
+/// Both are selected by tag, so a group matches however many entities carry that tag. A trailing
+/// predicate like `door[scores={open=1..}]` narrows the selection to the ones that also match it.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Entity(String);
+pub struct Entity {
+    name: String,
+    group: bool,
+    /// Extra target selector arguments, without their brackets.
+    predicate: Option<String>,
+}
 impl Entity {
     pub const TYPES: [&'static str; 3] = ["block_display", "item_display", "text_display"];
+    /// Short names for `TYPES`, in the same order.
+    pub const TYPE_ALIASES: [&'static str; 3] = ["block", "item", "text"];
+    const GROUP_PREFIX: &'static str = "group:";
+    /// The longest name accepted. This isn't a game limit, as tags can be any length. It keeps the
+    /// selectors readable instead: the name is repeated in every command selecting the entity, next to
+    /// the object's tag and any predicate, and no hand-written name comes near 64 characters, so a
+    /// longer one is almost certainly pasted by mistake.
+    pub const MAX_NAME_LENGTH: usize = 64;
+    /// The target selector arguments a predicate can use.
+    const SELECTOR_KEYS: [&'static str; 21] = [
+        "advancements",
+        "distance",
+        "dx",
+        "dy",
+        "dz",
+        "gamemode",
+        "level",
+        "limit",
+        "name",
+        "nbt",
+        "predicate",
+        "scores",
+        "sort",
+        "tag",
+        "team",
+        "type",
+        "x",
+        "x_rotation",
+        "y",
+        "y_rotation",
+        "z",
+    ];
+
+    /// Resolves an entity type, or one of its aliases, to the id it is summoned with.
+    /// `custom_types` are extra ids, such as modded entities, that are accepted as they are.
+    pub fn resolve_type<'a>(
+        entity_type: &'a str,
+        custom_types: &[String],
+    ) -> Result<String, ErrorType<'a>> {
+        Self::TYPES
+            .iter()
+            .zip(Self::TYPE_ALIASES)
+            .find(|&(&full, alias)| entity_type == full || entity_type == alias)
+            .map(|(&full, _)| full.to_string())
+            .or_else(|| {
+                custom_types
+                    .iter()
+                    .find(|custom| *custom == entity_type)
+                    .cloned()
+            })
+            .ok_or(ErrorType::InvalidEntityType(entity_type))
+    }
 
     pub fn new<'a>(string: &'a str, validator: &Regex) -> Result<Self, ErrorType<'a>> {
-        if validator.is_match(string) {
-            Ok(Self(string.to_string()))
+        let (string, predicate) = match string.split_once('[') {
+            Some((name, predicate)) => {
+                let predicate = predicate
+                    .strip_suffix(']')
+                    .ok_or(ErrorType::InvalidPredicate(string))?;
+                Self::check_predicate(predicate)?;
+                (name, Some(predicate.to_string()))
+            }
+            None => (string, None),
+        };
+        let (name, group) = string
+            .strip_prefix(Self::GROUP_PREFIX)
+            .map_or((string, false), |name| (name, true));
+        if name.is_empty() {
+            return Err(ErrorType::EmptyEntityName(string));
+        }
+        if name.len() > Self::MAX_NAME_LENGTH {
+            return Err(ErrorType::EntityNameTooLong(name));
+        }
+        if validator.is_match(name) {
+            Ok(Self {
+                name: name.to_string(),
+                group,
+                predicate,
+            })
         } else {
             Err(ErrorType::InvalidEntityName(string))
         }
     }
     pub fn name(&self) -> &str {
-        &self.0
+        &self.name
+    }
+    pub const fn is_group(&self) -> bool {
+        self.group
+    }
+    pub const fn has_predicate(&self) -> bool {
+        self.predicate.is_some()
+    }
+    /// The entity as it was written, with its group prefix and predicate.
+    pub fn argument(&self) -> String {
+        let prefix = if self.group { Self::GROUP_PREFIX } else { "" };
+        let predicate = self
+            .predicate
+            .as_ref()
+            .map_or_else(String::new, |predicate| format!("[{predicate}]"));
+        format!("{prefix}{}{predicate}", self.name)
+    }
+    /// What follows `tag=` in a selector for the entity: its name, then any conditions from its predicate.
+    pub fn selector(&self) -> String {
+        self.predicate.as_ref().map_or_else(
+            || self.name.clone(),
+            |predicate| format!("{},{predicate}", self.name),
+        )
+    }
+
+    /// Checks that a predicate is a list of `key=value` selector arguments with known keys, where
+    /// brackets, braces and quotes in the values are balanced.
+    fn check_predicate(predicate: &str) -> Result<(), ErrorType<'_>> {
+        let mut arguments = Vec::new();
+        let mut closers = Vec::new();
+        let mut quote = None;
+        let mut start = 0;
+        for (index, character) in predicate.char_indices() {
+            match (quote, character) {
+                (Some(open), _) if character == open => quote = None,
+                (None, '"' | '\'') => quote = Some(character),
+                (None, '[') => closers.push(']'),
+                (None, '{') => closers.push('}'),
+                (None, ']' | '}') if closers.pop() != Some(character) => {
+                    return Err(ErrorType::InvalidPredicate(predicate));
+                }
+                (None, ',') if closers.is_empty() => {
+                    arguments.push(&predicate[start..index]);
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        if quote.is_some() || !closers.is_empty() {
+            return Err(ErrorType::InvalidPredicate(predicate));
+        }
+        arguments.push(&predicate[start..]);
+        for argument in arguments {
+            let Some((key, value)) = argument.split_once('=') else {
+                return Err(ErrorType::InvalidPredicate(predicate));
+            };
+            let key = key.trim();
+            if !Self::SELECTOR_KEYS.contains(&key) {
+                return Err(ErrorType::UnknownSelectorKey(key));
+            }
+            if value.trim().trim_start_matches('!').is_empty() {
+                return Err(ErrorType::InvalidPredicate(predicate));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagOperation {
+    Add,
+    Remove,
+}
+impl TagOperation {
+    pub fn new(string: &str) -> Result<Self, ErrorType<'_>> {
+        match string {
+            "add" => Ok(Self::Add),
+            "remove" => Ok(Self::Remove),
+            _ => Err(ErrorType::InvalidTagOperation(string)),
+        }
+    }
+    pub const fn compile(self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Remove => "remove",
+        }
     }
 }
 
@@ -145,7 +534,7 @@ impl Display for TrackedChar {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct Position {
     pub line: usize,
     pub column: usize,
@@ -161,7 +550,7 @@ impl Add<usize> for Position {
     fn add(self, rhs: usize) -> Self::Output {
         Self {
             line: self.line,
-            column: self.column + rhs,
+            column: self.column.saturating_add(rhs),
         }
     }
 }
@@ -170,8 +559,8 @@ impl Add<(usize, usize)> for Position {
 
     fn add(self, rhs: (usize, usize)) -> Self::Output {
         Self {
-            line: self.line + rhs.0,
-            column: self.column + rhs.1,
+            line: self.line.saturating_add(rhs.0),
+            column: self.column.saturating_add(rhs.1),
         }
     }
 }
@@ -186,21 +575,140 @@ impl Sub<usize> for Position {
     fn sub(self, rhs: usize) -> Self::Output {
         Self {
             line: self.line,
-            column: self.column - rhs,
+            column: self.column.saturating_sub(rhs),
         }
     }
 }
 
 pub struct Regexes {
     pub name: Regex,
+    /// Block state property keys and values, which never take dots even when names do.
+    pub property: Regex,
+    pub resource_location: Regex,
 }
 impl Regexes {
-    const NAME: &'static str = r"^[A-Za-z0-9_\-]*$";
+    const NAME: &'static str = r"^[A-Za-z0-9_\-]+$";
+    /// Names may also contain dots when `dotted_names` is on. Tags and scoreboard holders both accept
+    /// them, so a dotted name is used as it is, and two different names never share a tag.
+    const DOTTED_NAME: &'static str = r"^[A-Za-z0-9_.\-]+$";
+    /// A `namespace:path` id, where the namespace may be left out to default to `minecraft`.
+    const RESOURCE_LOCATION: &'static str = r"^(?:[a-z0-9_.\-]+:)?[a-z0-9_./\-]+$";
 
-    pub fn new() -> anyhow::Result<Self> {
+    pub fn new(dotted_names: bool) -> anyhow::Result<Self> {
+        let name = if dotted_names {
+            Self::DOTTED_NAME
+        } else {
+            Self::NAME
+        };
         Ok(Self {
-            name: Regex::new(Self::NAME)
+            name: Regex::new(name).map_err(|err| GenericError::InvalidRegex(name, err))?,
+            property: Regex::new(Self::NAME)
                 .map_err(|err| GenericError::InvalidRegex(Self::NAME, err))?,
+            resource_location: Regex::new(Self::RESOURCE_LOCATION)
+                .map_err(|err| GenericError::InvalidRegex(Self::RESOURCE_LOCATION, err))?,
         })
     }
 }
+
+#[allow(clippy::unwrap_used)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{errors::CompileError, statements::FileInfo};
+
+    #[test]
+    fn entity_types_resolve_aliases_and_custom_types() {
+        let custom = ["minecraft:armor_stand".to_string()];
+        assert_eq!(
+            Entity::resolve_type("block", &custom).unwrap(),
+            "block_display"
+        );
+        assert_eq!(
+            Entity::resolve_type("text_display", &custom).unwrap(),
+            "text_display"
+        );
+        assert_eq!(
+            Entity::resolve_type("minecraft:armor_stand", &custom).unwrap(),
+            "minecraft:armor_stand"
+        );
+        assert!(Entity::resolve_type("minecraft:armor_stand", &[]).is_err());
+        assert!(Entity::resolve_type("display", &custom).is_err());
+    }
+
+    #[test]
+    fn entity_names_must_be_present_short_and_valid() {
+        let regex = Regexes::new(false).unwrap().name;
+        assert!(Entity::new("door_2", &regex).is_ok());
+        assert!(matches!(
+            Entity::new("", &regex),
+            Err(ErrorType::EmptyEntityName(""))
+        ));
+        assert!(matches!(
+            Entity::new("group:", &regex),
+            Err(ErrorType::EmptyEntityName("group:"))
+        ));
+        let long = "a".repeat(Entity::MAX_NAME_LENGTH + 1);
+        assert!(Entity::new(&long[1..], &regex).is_ok());
+        assert!(matches!(
+            Entity::new(&long, &regex),
+            Err(ErrorType::EntityNameTooLong(_))
+        ));
+        assert!(matches!(
+            Entity::new("door!", &regex),
+            Err(ErrorType::InvalidEntityName("door!"))
+        ));
+    }
+
+    #[test]
+    fn predicates_add_selector_arguments() {
+        let regex = Regexes::new(false).unwrap().name;
+        assert_eq!(Entity::new("door", &regex).unwrap().selector(), "door");
+        let door = Entity::new("door[scores={open=1..},nbt={Tags:[\"a,b\"]}]", &regex).unwrap();
+        assert_eq!(door.name(), "door");
+        assert_eq!(
+            door.selector(),
+            "door,scores={open=1..},nbt={Tags:[\"a,b\"]}"
+        );
+        let group = Entity::new("group:doors[tag=!locked]", &regex).unwrap();
+        assert!(group.is_group());
+        assert_eq!(group.selector(), "doors,tag=!locked");
+        for invalid in [
+            "door[]",
+            "door[scores={open=1..]",
+            "door[tag=]",
+            "door[tag=a",
+            "door[\"]",
+        ] {
+            assert!(
+                matches!(
+                    Entity::new(invalid, &regex),
+                    Err(ErrorType::InvalidPredicate(_))
+                ),
+                "{invalid}"
+            );
+        }
+        assert!(matches!(
+            Entity::new("door[score=open]", &regex),
+            Err(ErrorType::UnknownSelectorKey("score"))
+        ));
+    }
+
+    #[test]
+    fn position_arithmetic_saturates() {
+        let position = Position::new(3, 2);
+        assert_eq!((position - 5).column, 0);
+        assert_eq!((position + usize::MAX).column, usize::MAX);
+    }
+
+    #[test]
+    fn compile_error_with_oversized_offset_does_not_panic() {
+        let file_info = FileInfo::new("test.dspa".to_string(), TrackedChar::new(1, 1, '\n'));
+        let buffer = "block test stone[";
+        let error = CompileError::new(
+            &file_info,
+            Position::new(1, 1) - (buffer.len() + 10),
+            ErrorType::InvalidState(buffer),
+        );
+        assert!(error.to_string().contains("Line: 1, Column: 0"), "{error}");
+    }
+}
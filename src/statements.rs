@@ -1,9 +1,26 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    mem,
+    path::Path,
+};
+
 use crate::{
-    errors::{CompileError, CompileErrorType as ErrorType},
-    objects::{BlockState, Entity, Position, Regexes, Rotation, Scale, TrackedChar, Translation},
+    block_properties,
+    config::{self, Config, TargetVersion},
+    easing::{self, Curve, Easing},
+    errors::{
+        CompileError, CompileErrorType as ErrorType, CompileWarning,
+        CompileWarningType as WarningType,
+    },
+    objects::{
+        AngleUnit, Axis, BlockState, Entity, Position, Regexes, Rotation, Scale, TagOperation,
+        TextColor, TextComponent, TrackedChar, Translation,
+    },
 };
 
 use anyhow::{ensure, Result as AResult};
+use itertools::Itertools;
 use regex::Regex;
 
 macro_rules! arg_count {
@@ -12,7 +29,7 @@ macro_rules! arg_count {
             $data.arguments.len() == $e,
             $data.compile_error(ErrorType::IncorrectArgumentCount(
                 $data.buffer.0,
-                $e,
+                $e.to_string(),
                 $data.arguments.len()
             ))
         )
@@ -22,7 +39,17 @@ macro_rules! arg_count {
             $data.arguments.len() >= $e,
             $data.compile_error(ErrorType::IncorrectArgumentCount(
                 $data.buffer.0,
-                $e,
+                $e.to_string(),
+                $data.arguments.len()
+            ))
+        )
+    };
+    (in [$($e:literal),+], $data:expr) => {
+        ensure!(
+            matches!($data.arguments.len(), $($e)|+),
+            $data.compile_error(ErrorType::IncorrectArgumentCount(
+                $data.buffer.0,
+                [$(stringify!($e)),+].join(" or "),
                 $data.arguments.len()
             ))
         )
@@ -43,20 +70,354 @@ impl FileInfo {
 #[derive(Debug)]
 pub struct Program {
     pub statements: Vec<Statement>,
+    /// The comment-stripped source of each statement, in the same order as `statements`.
+    pub sources: Vec<(Position, String)>,
+    pub warnings: Vec<CompileWarning>,
 }
 impl Program {
-    pub fn parse_from_file(file_info: &FileInfo, contents: &[TrackedChar]) -> AResult<Self> {
-        let regexes = Regexes::new()?;
-        let statements: Vec<AResult<Statement>> = contents
+    pub fn parse_from_file(
+        file_info: &FileInfo,
+        contents: &[TrackedChar],
+        config: &Config,
+    ) -> AResult<Self> {
+        let regexes = Regexes::new(config.dotted_names)?;
+        let contents = strip_block_comments(file_info, contents)?;
+        let contents = join_continuations(file_info, &contents)?;
+        let lines: Vec<&[TrackedChar]> = contents
             .split(|char| char.character == '\n')
             .filter(|line| !line.is_empty())
-            .map(|line| Statement::parse_from_file(file_info, line, &regexes))
+            .collect();
+        let warnings = RefCell::new(Vec::new());
+        // The `with` entity applies from its line on, until the next `with`. Statements starting
+        // with an entity named on an earlier line don't get it.
+        let mut default_entity = None;
+        let mut known_entities = HashSet::new();
+        let statements: Vec<AResult<Statement>> = lines
+            .iter()
+            .map(|line| {
+                let statement = Statement::parse_from_file(
+                    file_info,
+                    line,
+                    &regexes,
+                    config,
+                    &warnings,
+                    default_entity.as_deref(),
+                    &known_entities,
+                );
+                if let Ok(statement) = &statement {
+                    if let Statement::With(entity) = statement {
+                        default_entity = Some(entity.argument());
+                    }
+                    let declared = match statement {
+                        Statement::Entity(entity) | Statement::Spawn(_, _, entity, ..) => {
+                            Some(entity)
+                        }
+                        _ => None,
+                    };
+                    let named = statement.referenced_entities().into_iter().chain(declared);
+                    known_entities.extend(named.map(|entity| entity.name().to_string()));
+                }
+                statement
+            })
+            .collect();
+        let sources: Vec<(Position, String)> = lines
+            .iter()
+            .map(|line| {
+                let (source, position) = get_buffer_string(file_info, line);
+                (position, source)
+            })
             .collect();
 
+        let statements = crate::collect_errors(statements)?;
+        check_object_collisions(file_info, &statements, &sources)?;
+        check_regions(file_info, &statements, &sources)?;
+        if config.strict_entities {
+            check_entities(file_info, &statements, &sources)?;
+        }
         Ok(Self {
-            statements: crate::collect_errors(statements)?,
+            statements,
+            sources,
+            warnings: warnings.into_inner(),
         })
     }
+
+    /// Splits the program into the first animation it defines and any later ones. An `object` line after the first statement
+    /// that does something starts a new animation, with its own timer and tick line, while one
+    /// before it only names the first. Namespaces carry over into later animations, but every other
+    /// directive only applies to the animation it appears in. The program's warnings stay with the
+    /// first animation.
+    pub fn sections(self) -> (Self, Vec<Self>) {
+        let mut sections = Vec::new();
+        let mut current = Self {
+            statements: Vec::new(),
+            sources: Vec::new(),
+            warnings: self.warnings,
+        };
+        let mut namespace = None;
+        for (statement, source) in self.statements.into_iter().zip(self.sources) {
+            if matches!(statement, Statement::ObjectName(..))
+                && current
+                    .statements
+                    .iter()
+                    .any(|statement| !statement.is_directive())
+            {
+                let (statements, sources) = namespace.iter().cloned().unzip();
+                sections.push(std::mem::replace(
+                    &mut current,
+                    Self {
+                        statements,
+                        sources,
+                        warnings: Vec::new(),
+                    },
+                ));
+            }
+            if matches!(statement, Statement::Namespace(_)) {
+                namespace = Some((statement.clone(), source.clone()));
+            }
+            current.statements.push(statement);
+            current.sources.push(source);
+        }
+        sections.push(current);
+        let later = sections.split_off(1);
+        (sections.remove(0), later)
+    }
+
+    /// Whether the file asks for a reversed copy of its animation with `reverse`.
+    pub fn has_reverse(&self) -> bool {
+        self.statements.contains(&Statement::Reverse)
+    }
+
+    /// Builds the program that plays this one backwards, as the `<animation>_reverse` animation.
+    ///
+    /// Every transformation interpolates back to the entity's transformation before it, or to the
+    /// untransformed entity for its first one, finishing when the original one started. Everything else
+    /// runs at its mirrored tick, undone as `undone` describes. Rotations of more than a turn take the
+    /// short way back, and anchors are written into each rotation's pivot.
+    pub fn reversed(&self, file_name: &str) -> Self {
+        let length = program_length(&self.statements);
+        let mut header = Vec::new();
+        let mut events = Vec::new();
+        let mut delay = 0;
+        let mut translations = HashMap::new();
+        let mut rotations = HashMap::new();
+        let mut scales = HashMap::new();
+        let mut anchors = HashMap::new();
+        let mut instants = InstantStates::new();
+        for (statement, source) in self.statements.iter().zip(&self.sources) {
+            // Each axis' own duration and an easing both play out from the entity's last transformation,
+            // which the reversed copy doesn't start from, so it interpolates over the longest duration
+            // at a steady rate.
+            let (offset, statement) = statement.clone().split_offset();
+            let statement = statement.without_axis_durations().split_easing().1;
+            let start = delay + offset;
+            let event = match statement {
+                Statement::ObjectName(object_name, animation_name) => {
+                    let animation_name = animation_name.as_deref().unwrap_or(file_name);
+                    header.push((
+                        Statement::ObjectName(
+                            object_name,
+                            Some(format!("{animation_name}_reverse")),
+                        ),
+                        source.clone(),
+                    ));
+                    continue;
+                }
+                Statement::Namespace(_) | Statement::Entity(_) | Statement::Loop(_) => {
+                    header.push((statement, source.clone()));
+                    continue;
+                }
+                Statement::Wait(duration) => {
+                    delay += duration;
+                    continue;
+                }
+                // The reversed copy plays straight through, keeping the tick each pause takes.
+                Statement::WaitFor(_) => {
+                    delay += 1;
+                    continue;
+                }
+                Statement::Anchor(entity, anchor) => {
+                    anchors.insert(entity.name().to_string(), anchor);
+                    continue;
+                }
+                Statement::Output(_)
+                | Statement::With(_)
+                | Statement::Reverse
+                | Statement::Comment(_)
+                | Statement::Region(_)
+                | Statement::EndRegion
+                | Statement::Empty => continue,
+                Statement::Translate(entity, translation, duration, interpolation_start) => {
+                    let previous = translations
+                        .insert(entity.name().to_string(), translation)
+                        .unwrap_or(Translation::new((0.0, 0.0, 0.0)));
+                    let end = start + interpolation_start + duration;
+                    (end, Statement::Translate(entity, previous, duration, 0))
+                }
+                Statement::Rotate(entity, rotation, duration, interpolation_start) => {
+                    let rotation = Rotation {
+                        pivot: rotation
+                            .pivot
+                            .or_else(|| anchors.get(entity.name()).copied()),
+                        ..rotation
+                    };
+                    let previous = rotations
+                        .insert(entity.name().to_string(), rotation)
+                        .unwrap_or(Rotation::new(
+                            [0.0, 1.0, 0.0],
+                            0.0,
+                            AngleUnit::Degrees,
+                            None,
+                        ));
+                    let end = start + interpolation_start + duration;
+                    (end, Statement::Rotate(entity, previous, duration, 0))
+                }
+                Statement::Scale(entity, scale, duration, interpolation_start) => {
+                    let previous = scales
+                        .insert(entity.name().to_string(), scale)
+                        .unwrap_or(Scale::new((1.0, 1.0, 1.0)));
+                    let end = start + interpolation_start + duration;
+                    (end, Statement::Scale(entity, previous, duration, 0))
+                }
+                Statement::Hold(entity, ticks) => (start + ticks, Statement::Hold(entity, ticks)),
+                Statement::Frames(interval, frames) => {
+                    let end = start + frames_length(interval, &frames);
+                    let Some(frames) = Statement::Frames(interval, frames).undone(&mut instants)
+                    else {
+                        continue;
+                    };
+                    (end, frames)
+                }
+                statement => match statement.undone(&mut instants) {
+                    Some(statement) => (start, statement),
+                    None => continue,
+                },
+            };
+            events.push((length - event.0, event.1, source.clone()));
+        }
+        // Reversing first keeps statements on the same tick in reverse order once they are sorted.
+        events.reverse();
+        events.sort_by_key(|(tick, ..)| *tick);
+        sequence(header, events, length)
+    }
+}
+
+/// The last item, block, text and color each entity was given, by the kind of statement that gave it.
+type InstantStates = HashMap<(String, mem::Discriminant<Statement>), Statement>;
+
+impl Statement {
+    /// The statement that undoes this one in a reversed copy, where it runs at the mirrored tick.
+    ///
+    /// A tag is removed where it was added and the other way around. An item, block, text or color goes
+    /// back to the one its entity had before, found in `states`, or to an empty display for the first.
+    /// Frames each go back to the frame before them, ending on what the entity had before the first.
+    /// A spawn can't be undone, so it is `None` and left out.
+    fn undone(self, states: &mut InstantStates) -> Option<Self> {
+        let (entity, empty) = match &self {
+            Self::Tag(entity, operation, tag) => {
+                let operation = match operation {
+                    TagOperation::Add => TagOperation::Remove,
+                    TagOperation::Remove => TagOperation::Add,
+                };
+                return Some(Self::Tag(entity.clone(), operation, tag.clone()));
+            }
+            Self::Frames(interval, frames) => {
+                let mut frames = frames
+                    .iter()
+                    .map(|frame| frame.clone().undone(states))
+                    .collect::<Option<Vec<_>>>()?;
+                frames.reverse();
+                return Some(Self::Frames(*interval, frames));
+            }
+            Self::Spawn(..) => return None,
+            Self::Item(entity, _) => (entity, Self::Item(entity.clone(), "air".to_string())),
+            Self::Block(entity, _) => (
+                entity,
+                Self::Block(
+                    entity.clone(),
+                    BlockState::new("minecraft:air".to_string(), Vec::new()),
+                ),
+            ),
+            Self::Text(entity, _) => (
+                entity,
+                Self::Text(entity.clone(), TextComponent::Plain(String::new())),
+            ),
+            Self::Color(entity, _) => (
+                entity,
+                Self::Color(entity.clone(), TextColor::Named("white")),
+            ),
+            _ => return Some(self),
+        };
+        let key = (entity.name().to_string(), mem::discriminant(&self));
+        let previous = states.insert(key, self);
+        Some(previous.unwrap_or(empty))
+    }
+}
+
+/// Lays out statements that each run on a tick as a program, with waits between them and a last wait
+/// to `length`, after the `header` statements that run before everything.
+fn sequence(
+    header: Vec<(Statement, (Position, String))>,
+    events: Vec<(u32, Statement, (Position, String))>,
+    length: u32,
+) -> Program {
+    let (mut statements, mut sources): (Vec<_>, Vec<_>) = header.into_iter().unzip();
+    let mut cursor = 0;
+    for (tick, statement, source) in events {
+        if tick > cursor {
+            statements.push(Statement::Wait(tick - cursor));
+            sources.push((source.0, format!("wait {}", tick - cursor)));
+            cursor = tick;
+        }
+        statements.push(statement);
+        sources.push(source);
+    }
+    if length > cursor {
+        let position = sources
+            .last()
+            .map_or(Position { line: 1, column: 1 }, |source| source.0);
+        statements.push(Statement::Wait(length - cursor));
+        sources.push((position, format!("wait {}", length - cursor)));
+    }
+    Program {
+        statements,
+        sources,
+        warnings: Vec::new(),
+    }
+}
+
+/// How long a program runs for: until its last wait, or until its last interpolation or hold ends.
+fn program_length(statements: &[Statement]) -> u32 {
+    let mut delay = 0;
+    let mut length = 0;
+    for statement in statements {
+        let (offset, statement) = statement.clone().split_offset();
+        let end = match statement.without_axis_durations().split_easing().1 {
+            Statement::Wait(duration) => {
+                delay += duration;
+                delay
+            }
+            Statement::WaitFor(_) => {
+                delay += 1;
+                delay
+            }
+            Statement::Translate(_, _, duration, start)
+            | Statement::Rotate(_, _, duration, start)
+            | Statement::Scale(_, _, duration, start) => delay + offset + start + duration,
+            Statement::Hold(_, ticks) => delay + ticks,
+            Statement::Frames(interval, frames) => {
+                delay + offset + frames_length(interval, &frames)
+            }
+            _ => delay,
+        };
+        length = length.max(end);
+    }
+    length
+}
+
+/// The ticks from the first frame of a `Frames` to its last.
+pub fn frames_length(interval: u32, frames: &[Statement]) -> u32 {
+    let steps = u32::try_from(frames.len().saturating_sub(1)).unwrap_or(u32::MAX);
+    interval.saturating_mul(steps)
 }
 
 pub type Vector = (f32, f32, f32);
@@ -67,41 +428,197 @@ struct StatementData<'a> {
     file_info: &'a FileInfo,
     buffer: Buffer<'a>,
     arguments: &'a [&'a str],
+    /// Where each argument starts in the source, in the same order as `arguments`.
+    positions: &'a [Position],
     name_regex: &'a Regex,
+    property_regex: &'a Regex,
+    resource_location_regex: &'a Regex,
+    custom_entity_types: &'a [String],
+    angle_unit: AngleUnit,
+    ticks_per_second: u32,
+    validate_block_states: bool,
+    easings: &'a BTreeMap<String, Curve>,
+    target_version: TargetVersion,
+    warnings: &'a RefCell<Vec<CompileWarning>>,
 }
-impl<'a> StatementData<'a> {
+impl StatementData<'_> {
     fn compile_error(&self, error_type: ErrorType) -> CompileError {
         CompileError::new(self.file_info, self.buffer.1, error_type)
     }
-    fn compile_error_offset(&self, offset: usize, error_type: ErrorType) -> CompileError {
-        CompileError::new(self.file_info, self.buffer.1 + offset, error_type)
+    fn warn(&self, warning_type: WarningType) {
+        self.warnings.borrow_mut().push(CompileWarning::new(
+            self.file_info,
+            self.buffer.1,
+            warning_type,
+        ));
+    }
+    fn compile_error_at(&self, position: Position, error_type: ErrorType) -> CompileError {
+        CompileError::new(self.file_info, position, error_type)
+    }
+    fn argument_position(&self, index: usize) -> Position {
+        self.positions.get(index).copied().unwrap_or(self.buffer.1)
+    }
+    /// Rejects a value that parsed as infinite or NaN, like `inf` or `1e39`, which would otherwise
+    /// compile to broken NBT such as `inff`.
+    fn ensure_finite(&self, index: usize, value: f32) -> AResult<f32> {
+        let position = self.argument_position(index);
+        ensure!(
+            value.is_finite(),
+            self.compile_error_at(
+                position,
+                ErrorType::NonFiniteValue(self.arguments[index], position)
+            )
+        );
+        Ok(value)
+    }
+    /// Parses the three coordinates starting at argument `first`.
+    fn coordinates(&self, first: usize) -> AResult<Vector> {
+        let coordinate = |index: usize| {
+            let value = Statement::parse_coordinate(self.arguments[index])
+                .map_err(|err| self.compile_error_at(self.argument_position(index), err))?;
+            self.ensure_finite(index, value)
+        };
+        Ok((
+            coordinate(first)?,
+            coordinate(first + 1)?,
+            coordinate(first + 2)?,
+        ))
+    }
+    /// Reports the first argument past the `expected` ones, so a misspelled modifier is pointed out
+    /// rather than dropped or blamed on the argument count.
+    fn reject_extra(&self, expected: usize) -> AResult<()> {
+        let Some(extra) = self.arguments.get(expected) else {
+            return Ok(());
+        };
+        Err(self
+            .compile_error_at(
+                self.argument_position(expected),
+                ErrorType::UnexpectedArgument(extra),
+            )
+            .into())
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
-    ObjectName(String, String),
+    /// The object and animation names. `None` falls back to the file name.
+    ObjectName(Option<String>, Option<String>),
+    /// Overrides the config's namespace for this file's tick function line and output folder.
+    Namespace(String),
+    /// Names the output file, without its extension, instead of naming it after the source file.
+    Output(String),
     Wait(u32),
-    Translate(Entity, Translation, u32),
-    Rotate(Entity, Rotation, u32),
-    Scale(Entity, Scale, u32),
-    Spawn(Entity, String, Entity),
+    /// Pauses the animation on the next tick until the `if score` condition passes. The statements on
+    /// that tick run once it does, and the animation carries on from there.
+    WaitFor(String),
+    /// The last two fields are the interpolation duration and the ticks to wait before it starts.
+    Translate(Entity, Translation, u32, u32),
+    Rotate(Entity, Rotation, u32, u32),
+    Scale(Entity, Scale, u32, u32),
+    /// The last field mounts the new entity on its source, so it follows the source's teleports.
+    Spawn(Entity, String, Entity, Option<Vector>, bool),
     Item(Entity, String),
     Block(Entity, BlockState),
-    Text(Entity, String),
+    Text(Entity, TextComponent),
     Teleport(Entity, f32, f32, f32),
+    Tag(Entity, TagOperation, String),
+    Color(Entity, TextColor),
+    /// Display entities have no mirror field, so this negates the entity's scale along the axis.
+    /// A negative scale also flips the model's normals, so its lighting and face culling may look inverted.
+    Mirror(Entity, Axis),
+    /// A command passed through as it is. The flag fires it at the current delay instead of every tick.
     Raw(String, bool),
+    /// Keeps an entity where it is for the given ticks. It emits nothing, but keeps the animation running
+    /// until the hold ends and counts as the entity's on its timeline, so a transformation during it is warned about.
+    Hold(Entity, u32),
+    /// Sets the pivot the entity's later rotations turn about when they don't give their own.
+    /// Like an explicit pivot, a rotation about an anchor sets the entity's translation, so it replaces
+    /// an earlier `translate`, and a later `translate` replaces it in turn until the next rotation.
+    Anchor(Entity, Translation),
+    /// A transformation that runs the given ticks after the current delay, without moving the delay
+    /// for the statements after it like a `wait` would.
+    Offset(u32, Box<Self>),
+    /// A translation or scale whose x, y and z each interpolate over their own duration. The wrapped
+    /// statement holds the longest of them.
+    AxisDurations([u32; 3], Box<Self>),
+    /// A translation or scale that follows a curve, rather than moving at a steady rate.
+    Eased(Easing, Box<Self>),
+    /// Item or block swaps, each the given ticks after the one before. Like a transformation's duration,
+    /// the frames after the first don't move the delay, so a `wait` is needed to line later statements
+    /// up after them.
+    Frames(u32, Vec<Self>),
+    /// Generates a copy of the animation that plays backwards, named `<animation>_reverse`.
+    Reverse,
+    /// Whether the animation starts over when it ends, from `loop`, or stops playing, from `once`,
+    /// which is the default. The last one in the animation applies to all of it.
+    Loop(bool),
+    /// A `#!` comment, which is copied into the output instead of being stripped like a `#` comment.
+    Comment(String),
+    /// A `#region <name>` marker opening a foldable region, which a later `#endregion` closes.
+    Region(String),
+    /// A `#endregion` marker closing the innermost open region.
+    EndRegion,
+    /// Registers an entity name, so `strict_entities` can catch statements that name one by mistake.
+    Entity(Entity),
+    /// Sets the entity the statements after it act on when they leave theirs out.
+    With(Entity),
     Empty,
 }
 impl Statement {
     const RAW_COMMAND_PREFIX: char = '/';
+    const PIVOT_MODIFIER: &'static str = "pivot";
+    const QUATERNION_MODIFIER: &'static str = "quat";
+    const START_MODIFIER: &'static str = "start";
+    const OFFSET_PREFIX: char = '+';
+    const KEPT_COMMENT_PREFIX: &'static str = "#!";
+    const REGION_MARKER: &'static str = "#region";
+    const END_REGION_MARKER: &'static str = "#endregion";
+    const AT_MODIFIER: &'static str = "at";
+    const MOUNTED_MODIFIER: &'static str = "mounted";
+    const DEFAULT_NAME: &'static str = "*";
+    /// Stands in for a left out entity while checking whether a statement only lacks its entity.
+    const PLACEHOLDER_ENTITY: &'static str = "_";
+
+    /// Reads a line that is a `#!` comment. Only a whole line can be one, so a `#!` inside a statement,
+    /// quoted or not, is never kept.
+    fn kept_comment(line: &[TrackedChar]) -> Option<String> {
+        let line: String = line.iter().map(|tracked| tracked.character).collect();
+        line.trim_start()
+            .strip_prefix(Self::KEPT_COMMENT_PREFIX)
+            .map(|comment| comment.trim().to_string())
+    }
+
+    /// Reads a line that is a `#region` or `#endregion` marker. Like a `#!` comment, only a whole line
+    /// can be one, and anything after `#endregion` is ignored, as editors allow.
+    fn region_marker(line: &[TrackedChar]) -> Option<Self> {
+        let line: String = line.iter().map(|tracked| tracked.character).collect();
+        let line = line.trim();
+        let marker = |prefix: &str| {
+            line.strip_prefix(prefix)
+                .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+                .map(str::trim)
+        };
+        marker(Self::REGION_MARKER)
+            .map(|name| Self::Region(name.to_string()))
+            .or_else(|| marker(Self::END_REGION_MARKER).map(|_| Self::EndRegion))
+    }
 
     fn parse_from_file(
         file_info: &FileInfo,
         line: &[TrackedChar],
         regexes: &Regexes,
+        config: &Config,
+        warnings: &RefCell<Vec<CompileWarning>>,
+        default_entity: Option<&str>,
+        known_entities: &HashSet<String>,
     ) -> AResult<Self> {
-        let (buffer_string, buffer_pos) = get_buffer_string(line);
+        if let Some(comment) = Self::kept_comment(line) {
+            return Ok(Self::Comment(comment));
+        }
+        if let Some(marker) = Self::region_marker(line) {
+            return Ok(marker);
+        }
+        let (buffer_string, buffer_pos) = get_buffer_string(file_info, line);
         let buffer: Buffer = (buffer_string.trim(), buffer_pos);
         if buffer.0.is_empty() {
             return Ok(Self::Empty);
@@ -117,37 +634,168 @@ impl Statement {
                 delayed,
             ));
         }
-        let mut words = buffer.0.split(' ');
-        let keyword = words.next().ok_or_else(|| {
+        let mut words = split_arguments(buffer.0).into_iter();
+        let (_, keyword) = words.next().ok_or_else(|| {
             CompileError::new(file_info, buffer.1, ErrorType::LineEmpty(buffer.0))
         })?;
-        let arguments: Vec<_> = words.collect();
+        // The buffer is trimmed, and continued lines put tokens on other physical lines,
+        // so each argument's position is looked up from the character it starts with.
+        let leading = line
+            .iter()
+            .take_while(|tracked| tracked.character.is_whitespace())
+            .count();
+        let position_at = |start: usize| {
+            line.get(leading + buffer.0[..start].chars().count())
+                .map(|tracked| tracked.position)
+        };
+        let (starts, arguments): (Vec<_>, Vec<_>) = words.unzip();
+        let positions: Vec<Position> = starts
+            .iter()
+            .map(|&start| position_at(start).unwrap_or(buffer.1))
+            .collect();
 
-        let buffer: Buffer = (buffer.0, buffer.1 + keyword.len());
+        // Errors about the whole statement point at the whitespace just before its first argument,
+        // however much of it follows the keyword.
+        let keyword_end = starts
+            .first()
+            .and_then(|&start| buffer.0[..start].char_indices().next_back())
+            .map_or(keyword.len(), |(index, _)| index);
+        let buffer: Buffer = (
+            buffer.0,
+            position_at(keyword_end).unwrap_or(buffer.1 + keyword.len()),
+        );
 
         let data = StatementData {
             file_info,
             buffer,
             arguments: &arguments,
+            positions: &positions,
             name_regex: &regexes.name,
+            property_regex: &regexes.property,
+            resource_location_regex: &regexes.resource_location,
+            custom_entity_types: &config.entity_types,
+            angle_unit: config.angle_unit,
+            ticks_per_second: config.ticks_per_second,
+            validate_block_states: config.validate_block_states,
+            easings: &config.easings,
+            target_version: config.target_version,
+            warnings,
         };
 
-        match keyword.try_into().map_err(|err| data.compile_error(err))? {
+        let keyword: Keyword = keyword.try_into().map_err(|err| data.compile_error(err))?;
+        let warning_count = warnings.borrow().len();
+        let parsed = Self::parse_keyword(keyword, data);
+        let miscounted = parsed
+            .as_ref()
+            .err()
+            .and_then(|error| error.downcast_ref::<CompileError>())
+            .is_some_and(CompileError::is_argument_count);
+        if miscounted && keyword.takes_entity() {
+            // The retry warns about the same arguments again, so only the kept attempt's warnings stay.
+            let first_warnings = warnings.borrow_mut().split_off(warning_count);
+            if let Some(retried) =
+                Self::with_default_entity(keyword, data, default_entity, known_entities)
+            {
+                return retried;
+            }
+            let mut warnings = warnings.borrow_mut();
+            warnings.truncate(warning_count);
+            warnings.extend(first_warnings);
+        }
+        parsed
+    }
+
+    /// Parses the arguments of a statement starting with `keyword`.
+    fn parse_keyword(keyword: Keyword, data: StatementData) -> AResult<Self> {
+        match keyword {
             Keyword::Object => Self::parse_object(data),
+            Keyword::Namespace => Self::parse_namespace(data),
+            Keyword::Output => Self::parse_output(data),
             Keyword::Wait => Self::parse_wait(data),
+            Keyword::WaitFor => Self::parse_wait_for(data),
 
-            Keyword::Translate => Self::parse_translation(data),
-            Keyword::Rotate => Self::parse_rotation(data),
-            Keyword::Scale => Self::parse_scale(data),
+            Keyword::Translate => Self::with_offset(data, Self::parse_translation),
+            Keyword::Rotate => Self::with_offset(data, Self::parse_rotation),
+            Keyword::Scale => Self::with_offset(data, Self::parse_scale),
 
             Keyword::Spawn => Self::parse_spawn(data),
             Keyword::Item => Self::parse_item(data),
             Keyword::Block => Self::parse_block(data),
             Keyword::Text => Self::parse_text(data),
             Keyword::Teleport => Self::parse_teleport(data),
+            Keyword::Tag => Self::parse_tag(data),
+            Keyword::Color => Self::parse_color(data),
+            Keyword::Mirror => Self::parse_mirror(data),
+            Keyword::Raw => Self::parse_raw(data),
+            Keyword::Hold => Self::parse_hold(data),
+            Keyword::Anchor => Self::parse_anchor(data),
+            Keyword::Entity => Self::parse_entity(data),
+            Keyword::With => Self::parse_with(data),
+            Keyword::Reverse => {
+                data.reject_extra(0)?;
+                Ok(Self::Reverse)
+            }
+            Keyword::Loop | Keyword::Once => {
+                data.reject_extra(0)?;
+                Ok(Self::Loop(matches!(keyword, Keyword::Loop)))
+            }
         }
     }
 
+    /// Parses a statement that failed to parse as written again with the `with` entity as its
+    /// entity, after any `+<ticks>` offset, for statements that leave it out. Returns `None` when it
+    /// still doesn't parse, or when it already starts with an entity named earlier in the file, so
+    /// the original error is reported. Without a `with` entity, a statement that only parses with
+    /// one is an error saying so.
+    fn with_default_entity(
+        keyword: Keyword,
+        data: StatementData,
+        default_entity: Option<&str>,
+        known_entities: &HashSet<String>,
+    ) -> Option<AResult<Self>> {
+        let index = usize::from(
+            data.arguments
+                .first()
+                .is_some_and(|argument| argument.starts_with(Self::OFFSET_PREFIX)),
+        );
+        let names_entity = data.arguments.get(index).is_some_and(|argument| {
+            Entity::new(argument, data.name_regex)
+                .is_ok_and(|entity| known_entities.contains(entity.name()))
+        });
+        if names_entity {
+            return None;
+        }
+        let mut arguments = data.arguments.to_vec();
+        arguments.insert(index, default_entity.unwrap_or(Self::PLACEHOLDER_ENTITY));
+        let mut positions = data.positions.to_vec();
+        positions.insert(index, data.buffer.1);
+        let parsed = Self::parse_keyword(
+            keyword,
+            StatementData {
+                arguments: &arguments,
+                positions: &positions,
+                ..data
+            },
+        )
+        .ok()?;
+        Some(match default_entity {
+            Some(_) => Ok(parsed),
+            None => Err(data
+                .compile_error(ErrorType::MissingEntity(data.buffer.0))
+                .into()),
+        })
+    }
+
+    /// Parses `with <entity>`, which later statements act on when they leave out their entity.
+    fn parse_with(data: StatementData) -> AResult<Self> {
+        let arguments = data.arguments;
+        data.reject_extra(1)?;
+        arg_count!(== 1, data);
+        let entity =
+            Entity::new(arguments[0], data.name_regex).map_err(|err| data.compile_error(err))?;
+        Ok(Self::With(entity))
+    }
+
     const fn is_raw(char: Option<char>) -> bool {
         match char {
             Some(c) => c == Self::RAW_COMMAND_PREFIX,
@@ -158,104 +806,487 @@ impl Statement {
     fn parse_object(data: StatementData) -> AResult<Self> {
         let arguments = data.arguments;
         let name_regex = data.name_regex;
+        data.reject_extra(1)?;
         arg_count!(== 1, data);
         let argument = arguments[0];
         let (object_name, animation_name) = argument
             .split_once(':')
             .ok_or_else(|| data.compile_error(ErrorType::NoAnimationName(argument)))?;
+        // A blank or `*` name keeps the file name, so it never reaches the scoreboard as it is.
+        let name = |name: &str| -> AResult<Option<String>> {
+            if name.is_empty() || name == Self::DEFAULT_NAME {
+                return Ok(None);
+            }
+            ensure!(
+                name_regex.is_match(name),
+                data.compile_error(ErrorType::InvalidCharacters(name))
+            );
+            Ok(Some(name.to_string()))
+        };
+
+        Ok(Self::ObjectName(name(object_name)?, name(animation_name)?))
+    }
+
+    fn parse_namespace(data: StatementData) -> AResult<Self> {
+        let arguments = data.arguments;
+        data.reject_extra(1)?;
+        arg_count!(== 1, data);
+        let namespace = arguments[0];
         ensure!(
-            name_regex.is_match(object_name),
-            data.compile_error(ErrorType::InvalidCharacters(object_name))
+            config::is_valid_namespace(namespace),
+            data.compile_error(ErrorType::InvalidNamespace(namespace))
         );
+        Ok(Self::Namespace(namespace.to_string()))
+    }
+
+    /// Parses `output <name>`. The name is a single function name, so it can't move the file to
+    /// another folder.
+    fn parse_output(data: StatementData) -> AResult<Self> {
+        let arguments = data.arguments;
+        data.reject_extra(1)?;
+        arg_count!(== 1, data);
+        let name = arguments[0];
         ensure!(
-            name_regex.is_match(animation_name),
-            data.compile_error(ErrorType::InvalidCharacters(animation_name))
+            !name.is_empty()
+                && !name.starts_with('.')
+                && name
+                    .chars()
+                    .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '_' | '-' | '.')),
+            data.compile_error(ErrorType::InvalidOutputName(name))
         );
-
-        Ok(Self::ObjectName(
-            object_name.to_string(),
-            animation_name.to_string(),
-        ))
+        Ok(Self::Output(name.to_string()))
     }
 
     fn parse_wait(data: StatementData) -> AResult<Self> {
         let arguments = data.arguments;
+        data.reject_extra(1)?;
         arg_count!(== 1, data);
-        let wait_duration: u32 = arguments[0]
-            .parse()
-            .map_err(|err| data.compile_error(ErrorType::InvalidInt(arguments[0], err)))?;
+        let wait_duration = Self::parse_ticks(&data, arguments[0])?;
         Ok(Self::Wait(wait_duration))
     }
 
+    /// Parses `waitfor <holder> <objective> matches <range>`, or a comparison with another score like
+    /// `waitfor $door state = $lever state`, keeping it as the condition of an `if score`. A `#` starts a
+    /// comment, so the holder can't be a `#` fake player.
+    fn parse_wait_for(data: StatementData) -> AResult<Self> {
+        let arguments = data.arguments;
+        data.reject_extra(5)?;
+        arg_count!(in [4, 5], data);
+        let valid = match arguments {
+            [_, _, "matches", range] => !range.is_empty(),
+            [_, _, operation, _, _] => matches!(*operation, "<" | "<=" | "=" | ">=" | ">"),
+            _ => false,
+        };
+        let condition = arguments.join(" ");
+        ensure!(
+            valid,
+            data.compile_error(ErrorType::InvalidCondition(condition))
+        );
+        Ok(Self::WaitFor(condition))
+    }
+
     fn parse_translation(data: StatementData) -> AResult<Self> {
+        let (data, start) = Self::split_start(data)?;
+        let (data, easing) = Self::split_easing_name(data)?;
         let arguments = data.arguments;
         let name_regex = data.name_regex;
+        data.reject_extra(5)?;
         arg_count!(== 5, data);
         let entity =
             Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
-        let position: Vector = Self::parse_coordinates(arguments[1], arguments[2], arguments[3])
-            .map_err(|err| data.compile_error(err))?;
-        let duration: u32 = arguments[4]
-            .parse()
-            .map_err(|err| data.compile_error(ErrorType::InvalidInt(arguments[4], err)))?;
+        if let Some(durations) = Self::parse_axis_durations(&data)? {
+            ensure!(
+                easing.is_none(),
+                data.compile_error(ErrorType::EasedAxisDurations)
+            );
+            let translation = Translation::new(data.coordinates(2)?);
+            return Ok(Self::with_axis_durations(durations, |duration| {
+                Self::Translate(entity, translation, duration, start)
+            }));
+        }
+        let position = data.coordinates(1)?;
+        let duration = Self::parse_duration(&data, arguments[4])?;
         let translation = Translation::new(position);
-        Ok(Self::Translate(entity, translation, duration))
+        Ok(Self::with_easing(
+            easing,
+            Self::Translate(entity, translation, duration, start),
+        ))
     }
 
     fn parse_rotation(data: StatementData) -> AResult<Self> {
+        let (data, start) = Self::split_start(data)?;
         let arguments = data.arguments;
         let name_regex = data.name_regex;
-        arg_count!(== 4, data);
+        if arguments.get(1) == Some(&Self::QUATERNION_MODIFIER) {
+            return Self::parse_quaternion_rotation(data, start);
+        }
+        data.reject_extra(Self::with_pivot(arguments, 4))?;
+        arg_count!(in [4, 8], data);
         let entity =
             Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
 
         let axis: [f32; 3] =
             Self::parse_axis(arguments[1]).map_err(|err| data.compile_error(err))?;
-        let angle: f32 = arguments[2]
-            .parse()
-            .map_err(|err| data.compile_error(ErrorType::InvalidFloat(arguments[2], err)))?;
+        for component in axis {
+            data.ensure_finite(1, component)?;
+        }
+        let (angle, unit) = Self::parse_angle(arguments[2], data.angle_unit)
+            .map_err(|err| data.compile_error(err))?;
+        data.ensure_finite(2, angle)?;
 
-        let duration: u32 = arguments[3]
-            .parse()
-            .map_err(|err| data.compile_error(ErrorType::InvalidInt(arguments[3], err)))?;
+        let duration = Self::parse_duration(&data, arguments[3])?;
+
+        let pivot = Self::parse_pivot(&data, 4)?;
+
+        let rotation = Rotation::new(axis, angle, unit, pivot);
+        Ok(Self::Rotate(entity, rotation, duration, start))
+    }
+
+    /// Parses `turn <entity> quat <x> <y> <z> <w> <duration> [pivot <x> <y> <z>]`.
+    fn parse_quaternion_rotation(data: StatementData, start: u32) -> AResult<Self> {
+        let arguments = data.arguments;
+        let name_regex = data.name_regex;
+        data.reject_extra(Self::with_pivot(arguments, 7))?;
+        arg_count!(in [7, 11], data);
+        let entity =
+            Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
+
+        let mut quaternion = [0.0; 4];
+        for (index, component) in (2..6).zip(quaternion.iter_mut()) {
+            let argument = arguments[index];
+            let value = argument
+                .parse()
+                .map_err(|err| data.compile_error(ErrorType::InvalidFloat(argument, err)))?;
+            *component = data.ensure_finite(index, value)?;
+        }
+        ensure!(
+            quaternion.iter().any(|&component| component != 0.0),
+            data.compile_error(ErrorType::ZeroQuaternion(data.buffer.0))
+        );
+
+        let duration = Self::parse_duration(&data, arguments[6])?;
+
+        let pivot = Self::parse_pivot(&data, 7)?;
+
+        let rotation = Rotation::from_quaternion(quaternion, pivot);
+        Ok(Self::Rotate(entity, rotation, duration, start))
+    }
+
+    /// The argument count of a rotation with `count` arguments before its optional pivot.
+    fn with_pivot(arguments: &[&str], count: usize) -> usize {
+        if arguments.get(count) == Some(&Self::PIVOT_MODIFIER) {
+            count + 4
+        } else {
+            count
+        }
+    }
 
-        let rotation = Rotation::new(axis, angle);
-        Ok(Self::Rotate(entity, rotation, duration))
+    /// Parses the optional `pivot <x> <y> <z>` that can trail a rotation, from argument `first` on.
+    fn parse_pivot(data: &StatementData, first: usize) -> AResult<Option<Translation>> {
+        let [modifier, _, _, _] = &data.arguments[first..] else {
+            return Ok(None);
+        };
+        ensure!(
+            *modifier == Self::PIVOT_MODIFIER,
+            data.compile_error(ErrorType::InvalidKeyword(modifier))
+        );
+        let position = data.coordinates(first + 1)?;
+        Ok(Some(Translation::new(position)))
     }
 
     fn parse_scale(data: StatementData) -> AResult<Self> {
+        let (data, start) = Self::split_start(data)?;
+        let (data, easing) = Self::split_easing_name(data)?;
         let arguments = data.arguments;
         let name_regex = data.name_regex;
-        arg_count!(== 5, data);
+        data.reject_extra(5)?;
+        arg_count!(in [3, 5], data);
 
         let entity =
             Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
 
-        let position: Vector = Self::parse_coordinates(arguments[1], arguments[2], arguments[3])
-            .map_err(|err| data.compile_error(err))?;
+        let factor = |index: usize| {
+            let factor = Self::parse_scale_factor(arguments[index])
+                .map_err(|err| data.compile_error(err))?;
+            data.ensure_finite(index, factor)
+        };
+        let factors = |first: usize| -> AResult<Vector> {
+            if arguments.len() == 3 {
+                let uniform = factor(first)?;
+                Ok((uniform, uniform, uniform))
+            } else {
+                Ok((factor(first)?, factor(first + 1)?, factor(first + 2)?))
+            }
+        };
+        if let Some(durations) = Self::parse_axis_durations(&data)? {
+            ensure!(
+                easing.is_none(),
+                data.compile_error(ErrorType::EasedAxisDurations)
+            );
+            let scale = Scale::new(factors(2)?);
+            return Ok(Self::with_axis_durations(durations, |duration| {
+                Self::Scale(entity, scale, duration, start)
+            }));
+        }
+        let position = factors(1)?;
 
-        let duration: u32 = arguments[4]
-            .parse()
-            .map_err(|err| data.compile_error(ErrorType::InvalidInt(arguments[4], err)))?;
+        let duration = Self::parse_duration(&data, arguments[arguments.len() - 1])?;
 
         let scale = Scale::new(position);
-        Ok(Self::Scale(entity, scale, duration))
+        Ok(Self::with_easing(
+            easing,
+            Self::Scale(entity, scale, duration, start),
+        ))
     }
 
-    fn parse_coordinates<'a>(x: &'a str, y: &'a str, z: &'a str) -> Result<Vector, ErrorType<'a>> {
-        let x = x
-            .parse()
-            .map_err(|err| ErrorType::InvalidCoordinate(x, err))?;
-        let y = y
+    /// Parses a scale factor, either plain like `1.5` or as a percentage like `150%`.
+    fn parse_scale_factor(argument: &str) -> Result<f32, ErrorType<'_>> {
+        let (number, divisor) = argument
+            .strip_suffix('%')
+            .map_or((argument, 1.0), |number| (number, 100.0));
+        let factor = Self::parse_coordinate(number).map_err(|err| match err {
+            ErrorType::InvalidCoordinate(_, err, hint) => {
+                ErrorType::InvalidCoordinate(argument, err, hint)
+            }
+            err => err,
+        })?;
+        Ok(factor / divisor)
+    }
+
+    /// Splits a trailing `start <ticks>` off a transformation's arguments, returning the remaining
+    /// arguments and the delay before the interpolation starts, which is 0 when it is left out.
+    fn split_start(data: StatementData) -> AResult<(StatementData, u32)> {
+        let arguments = data.arguments;
+        let [rest @ .., modifier, ticks] = arguments else {
+            return Ok((data, 0));
+        };
+        if *modifier != Self::START_MODIFIER {
+            return Ok((data, 0));
+        }
+        ensure!(
+            !ticks.starts_with('-'),
+            data.compile_error(ErrorType::NegativeStart(ticks))
+        );
+        let start = Self::parse_ticks(&data, ticks)?;
+        Ok((
+            StatementData {
+                arguments: rest,
+                ..data
+            },
+            start,
+        ))
+    }
+
+    /// Splits a trailing easing name off a translation or scale, like the `ease-in` of
+    /// `move cube 0 1 0 20 ease-in`, looking it up in the built-in and configured curves. Coordinates and
+    /// durations start with a digit, a sign or a `.`, so only a name starts with a letter.
+    fn split_easing_name(data: StatementData) -> AResult<(StatementData, Option<Easing>)> {
+        let arguments = data.arguments;
+        let [rest @ .., name] = arguments else {
+            return Ok((data, None));
+        };
+        if rest.len() < 2 || !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return Ok((data, None));
+        }
+        let Some(curve) = easing::find(name, data.easings) else {
+            return Err(data
+                .compile_error(ErrorType::UnknownEasing(
+                    name,
+                    easing::names(data.easings).join(", "),
+                ))
+                .into());
+        };
+        let easing = Easing {
+            name: (*name).to_string(),
+            curve,
+        };
+        Ok((
+            StatementData {
+                arguments: rest,
+                ..data
+            },
+            Some(easing),
+        ))
+    }
+
+    /// Wraps a transformation in its easing, if it has one.
+    fn with_easing(easing: Option<Easing>, statement: Self) -> Self {
+        match easing {
+            Some(easing) => Self::Eased(easing, Box::new(statement)),
+            None => statement,
+        }
+    }
+
+    /// Splits the easing off an eased transformation, which is `None` for any other statement.
+    pub fn split_easing(self) -> (Option<Easing>, Self) {
+        match self {
+            Self::Eased(easing, statement) => (Some(easing), *statement),
+            statement => (None, statement),
+        }
+    }
+
+    /// Parses a transformation with an optional leading `+<ticks>`, like `move +5 cube 0 1 0 20`.
+    /// An entity name can't start with `+`, so the offset is never mistaken for one.
+    fn with_offset<'a>(
+        data: StatementData<'a>,
+        parse: fn(StatementData<'a>) -> AResult<Self>,
+    ) -> AResult<Self> {
+        let Some(ticks) = data
+            .arguments
+            .first()
+            .and_then(|argument| argument.strip_prefix(Self::OFFSET_PREFIX))
+        else {
+            return parse(data);
+        };
+        let offset = Self::parse_ticks(&data, ticks)?;
+        let statement = parse(StatementData {
+            arguments: &data.arguments[1..],
+            positions: data.positions.get(1..).unwrap_or_default(),
+            ..data
+        })?;
+        Ok(if offset == 0 {
+            statement
+        } else {
+            Self::Offset(offset, Box::new(statement))
+        })
+    }
+
+    /// Parses the per-axis durations of a transformation like `size cube [20,40,20] 2 3 2`, which
+    /// replace its duration and come right after the entity. Returns `None` for a single duration.
+    fn parse_axis_durations(data: &StatementData) -> AResult<Option<[u32; 3]>> {
+        let Some(argument) = data.arguments.get(1) else {
+            return Ok(None);
+        };
+        let Some(list) = argument
+            .strip_prefix('[')
+            .and_then(|list| list.strip_suffix(']'))
+        else {
+            return Ok(None);
+        };
+        let durations: Vec<&str> = list.split(',').map(str::trim).collect();
+        let [x, y, z] = durations.as_slice() else {
+            return Err(data
+                .compile_error(ErrorType::InvalidAxisDurations(argument))
+                .into());
+        };
+        Ok(Some([
+            Self::parse_duration(data, x)?,
+            Self::parse_duration(data, y)?,
+            Self::parse_duration(data, z)?,
+        ]))
+    }
+
+    /// Builds a transformation with a duration for each axis. When they are all the same it is an
+    /// ordinary transformation, so it compiles to a single command.
+    fn with_axis_durations(durations: [u32; 3], transformation: impl FnOnce(u32) -> Self) -> Self {
+        let longest = durations.into_iter().max().unwrap_or_default();
+        let statement = transformation(longest);
+        if durations.iter().all(|&duration| duration == longest) {
+            statement
+        } else {
+            Self::AxisDurations(durations, Box::new(statement))
+        }
+    }
+
+    /// Unwraps a transformation with per-axis durations into one over the longest of them.
+    pub fn without_axis_durations(self) -> Self {
+        match self {
+            Self::AxisDurations(_, statement) => *statement,
+            statement => statement,
+        }
+    }
+
+    /// Splits off the offset of a `+<ticks>` statement, which is 0 for any other statement.
+    pub fn split_offset(self) -> (u32, Self) {
+        match self {
+            Self::Offset(offset, statement) => (offset, *statement),
+            statement => (0, statement),
+        }
+    }
+
+    /// Parses a time in ticks, or in seconds with an `s` suffix, which is rounded to the nearest tick.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn parse_ticks<'a>(data: &StatementData<'a>, argument: &'a str) -> AResult<u32> {
+        let Some(seconds) = argument.strip_suffix('s') else {
+            return argument.parse().map_err(|err| {
+                data.compile_error(ErrorType::InvalidInt(argument, err))
+                    .into()
+            });
+        };
+        let seconds: f32 = seconds
             .parse()
-            .map_err(|err| ErrorType::InvalidCoordinate(y, err))?;
-        let z = z
+            .map_err(|err| data.compile_error(ErrorType::InvalidFloat(argument, err)))?;
+        let ticks = (seconds * data.ticks_per_second as f32).round();
+        ensure!(
+            ticks.is_finite() && ticks >= 0.0 && ticks <= u32::MAX as f32,
+            data.compile_error(ErrorType::InvalidSeconds(argument))
+        );
+        Ok(ticks as u32)
+    }
+
+    /// Parses an interpolation duration. A duration of 0 snaps instead of interpolating,
+    /// which is allowed but probably not what was meant, so it is warned about.
+    fn parse_duration<'a>(data: &StatementData<'a>, argument: &'a str) -> AResult<u32> {
+        let duration = Self::parse_ticks(data, argument)?;
+        if duration == 0 {
+            data.warn(WarningType::ZeroDuration(data.buffer.0));
+        }
+        Ok(duration)
+    }
+
+    fn parse_coordinate(coordinate: &str) -> Result<f32, ErrorType<'_>> {
+        coordinate.parse().map_err(|err| {
+            ErrorType::InvalidCoordinate(coordinate, err, Self::coordinate_hint(coordinate))
+        })
+    }
+
+    /// Suggests a fix for a coordinate that doesn't parse because of a common mistake: a comma as
+    /// the decimal point, repeated signs, a trailing unit, or more than one decimal point.
+    fn coordinate_hint(coordinate: &str) -> Option<String> {
+        let suggest = |fixed: String| {
+            fixed
+                .parse::<f32>()
+                .is_ok()
+                .then(|| format!("Did you mean '{fixed}'?"))
+        };
+        if coordinate.matches(',').count() == 1 {
+            return suggest(coordinate.replace(',', "."));
+        }
+        let number = coordinate.trim_start_matches(['+', '-']);
+        let signs = &coordinate[..coordinate.len() - number.len()];
+        if signs.len() > 1 {
+            let sign = if signs.contains('-') { "-" } else { "" };
+            return suggest(format!("{sign}{number}"));
+        }
+        let without_unit = coordinate.trim_end_matches(char::is_alphabetic);
+        if without_unit.len() < coordinate.len() && !without_unit.is_empty() {
+            return suggest(without_unit.to_string())
+                .map(|hint| format!("Coordinates are given without a unit. {hint}"));
+        }
+        (coordinate.matches('.').count() > 1)
+            .then(|| "A number has at most one decimal point.".to_string())
+    }
+
+    /// Parses an angle, honouring an optional `deg`/`rad` suffix over the configured unit.
+    fn parse_angle(
+        angle_string: &str,
+        default: AngleUnit,
+    ) -> Result<(f32, AngleUnit), ErrorType<'_>> {
+        let (number, unit) = [("rad", AngleUnit::Radians), ("deg", AngleUnit::Degrees)]
+            .into_iter()
+            .find_map(|(suffix, unit)| angle_string.strip_suffix(suffix).map(|n| (n, unit)))
+            .unwrap_or((angle_string, default));
+        let angle = number
             .parse()
-            .map_err(|err| ErrorType::InvalidCoordinate(z, err))?;
-        Ok((x, y, z))
+            .map_err(|err| ErrorType::InvalidFloat(angle_string, err))?;
+        Ok((angle, unit))
     }
 
-    fn parse_axis(axis_string: &str) -> Result<[f32; 3], ErrorType> {
+    fn parse_axis(axis_string: &str) -> Result<[f32; 3], ErrorType<'_>> {
         match axis_string {
             "x" => return Ok([1.0, 0.0, 0.0]),
             "y" => return Ok([0.0, 1.0, 0.0]),
@@ -286,109 +1317,451 @@ impl Statement {
     fn parse_spawn(data: StatementData) -> AResult<Self> {
         let arguments = data.arguments;
         let name_regex = data.name_regex;
-        arg_count!(== 3, data);
+        let expected = match arguments.get(3) {
+            Some(&Self::MOUNTED_MODIFIER) => 4,
+            Some(&Self::AT_MODIFIER) => 7,
+            _ => 3,
+        };
+        data.reject_extra(expected)?;
+        arg_count!(== expected, data);
         let source_entity =
             Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
-        let entity_type = arguments[1];
-        ensure!(
-            Entity::TYPES.contains(&entity_type),
-            data.compile_error(ErrorType::InvalidEntityType(entity_type),),
-        );
+        let entity_type = Entity::resolve_type(arguments[1], data.custom_entity_types)
+            .map_err(|err| data.compile_error(err))?;
         let new_entity =
             Entity::new(arguments[2], name_regex).map_err(|err| data.compile_error(err))?;
+        ensure!(
+            !new_entity.is_group(),
+            data.compile_error(ErrorType::GroupNotAllowed(arguments[2]))
+        );
+        ensure!(
+            !new_entity.has_predicate(),
+            data.compile_error(ErrorType::PredicateNotAllowed(arguments[2]))
+        );
+        let mounted = expected == 4;
+        let offset = if expected == 7 {
+            Some(data.coordinates(4)?)
+        } else {
+            None
+        };
         Ok(Self::Spawn(
             source_entity,
-            entity_type.to_string(),
+            entity_type,
             new_entity,
+            offset,
+            mounted,
         ))
     }
 
     fn parse_item(data: StatementData) -> AResult<Self> {
+        if let Some(interval) = Self::frame_interval(&data)? {
+            return Self::parse_frames(&data, interval, Self::parse_item_frame);
+        }
         let arguments = data.arguments;
         let name_regex = data.name_regex;
-        arg_count!(>= 2, data);
+        // Components are bracketed, so they stay in the same argument as the id.
+        data.reject_extra(2)?;
+        arg_count!(== 2, data);
 
         let entity =
             Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
-        let item = arguments[1..].join(" ");
-        Ok(Self::Item(entity, item))
+        Self::parse_item_frame(&data, &entity, 1)
     }
 
-    fn parse_block(data: StatementData) -> AResult<Self> {
-        let arguments = data.arguments;
-        let name_regex = data.name_regex;
-        arg_count!(>= 2, data);
-
-        let entity =
-            Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
-        let block_state = arguments[1..].join(" ");
-        let (id, state): (&str, _) = block_state
-            .split_once('[')
-            .map_or((&block_state, None), |(id, state)| (id, Some(state)));
-        let block = match state {
-            None => BlockState::new(id.to_string(), Vec::new()),
-            Some(state) => {
-                let state = state.strip_suffix(']').ok_or_else(|| {
-                    data.compile_error_offset(
-                        id.len() + state.len(),
-                        ErrorType::InvalidState(state),
-                    )
-                })?;
-                let states: Vec<_> = state
-                    .split(',')
-                    .map(|part| {
-                        part.trim()
-                            .split_once('=')
-                            .map(|(name, value)| (name.to_string(), value.to_string()))
-                            .ok_or_else(|| {
-                                data.compile_error_offset(id.len(), ErrorType::InvalidState(state))
-                            })
-                    })
-                    .collect();
-                let states = crate::collect_errors(states)?;
-                BlockState::new(id.to_string(), states)
-            }
+    /// Parses the item at `index` as a swap of `entity`'s item.
+    fn parse_item_frame(data: &StatementData, entity: &Entity, index: usize) -> AResult<Self> {
+        let item = data.arguments[index].to_string();
+        // Anything from the first `[` or `{` on is the item's components, not part of its id.
+        let components = item.find(['[', '{']);
+        let id = components.map_or(item.as_str(), |index| &item[..index]);
+        Self::validate_resource_location(data, id, data.argument_position(index))?;
+        // Components replaced item NBT, so each version only takes one of them.
+        let expected = if data.target_version.item_components() {
+            '['
+        } else {
+            '{'
         };
-        Ok(Self::Block(entity, block))
+        ensure!(
+            components.is_none_or(|index| item[index..].starts_with(expected)),
+            data.compile_error_at(
+                data.argument_position(index),
+                ErrorType::UnsupportedItemSyntax(data.arguments[index], data.target_version)
+            )
+        );
+        Ok(Self::Item(entity.clone(), item))
     }
 
-    fn parse_text(data: StatementData) -> AResult<Self> {
+    fn parse_block(data: StatementData) -> AResult<Self> {
+        if let Some(interval) = Self::frame_interval(&data)? {
+            return Self::parse_frames(&data, interval, Self::parse_block_frame);
+        }
         let arguments = data.arguments;
         let name_regex = data.name_regex;
-        arg_count!(>= 2, data);
+        data.reject_extra(2)?;
+        arg_count!(== 2, data);
 
         let entity =
             Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
-        let text = arguments[1..].join(" ");
-        Ok(Self::Text(entity, text))
+        Self::parse_block_frame(&data, &entity, 1)
     }
 
-    fn parse_teleport(data: StatementData) -> AResult<Self> {
-        let arguments = data.arguments;
-        let name_regex = data.name_regex;
-        arg_count!(== 4, data);
-
-        let entity =
-            Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
-        let coords = arguments[1..=3]
-            .iter()
-            .map(|coord| {
-                coord
-                    .parse()
-                    .map_err(|err| data.compile_error(ErrorType::InvalidFloat(coord, err)))
-            })
-            .collect::<Result<Vec<f32>, _>>()?;
-        let [x, y, z] = coords.as_slice() else {
-            unreachable!()
-        };
-
-        Ok(Self::Teleport(entity, *x, *y, *z))
+    /// Parses the block state at `index` as a swap of `entity`'s block.
+    fn parse_block_frame(data: &StatementData, entity: &Entity, index: usize) -> AResult<Self> {
+        let block =
+            Self::parse_block_state(data, data.arguments[index], data.argument_position(index))?;
+        Ok(Self::Block(entity.clone(), block))
     }
-}
 
-#[derive(Debug, Clone, Copy)]
+    /// The ticks between frames of an `item` or `block` with a list of frames, like
+    /// `item test 4 stone dirt sand`. Ids don't start with a digit, so a single frame is never
+    /// mistaken for one.
+    fn frame_interval(data: &StatementData) -> AResult<Option<u32>> {
+        match data.arguments {
+            [_, interval, _, ..] if interval.starts_with(|c: char| c.is_ascii_digit()) => {
+                Self::parse_ticks(data, interval).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Parses every argument after the entity and interval as a frame, with `frame`.
+    fn parse_frames(
+        data: &StatementData,
+        interval: u32,
+        frame: fn(&StatementData, &Entity, usize) -> AResult<Self>,
+    ) -> AResult<Self> {
+        let entity = Entity::new(data.arguments[0], data.name_regex)
+            .map_err(|err| data.compile_error(err))?;
+        let frames = (2..data.arguments.len())
+            .map(|index| frame(data, &entity, index))
+            .collect::<AResult<Vec<_>>>()?;
+        Ok(Self::Frames(interval, frames))
+    }
+
+    /// Checks that `id`, found at `position`, is a `namespace:path` resource location.
+    fn validate_resource_location(
+        data: &StatementData,
+        id: &str,
+        position: Position,
+    ) -> AResult<()> {
+        ensure!(
+            data.resource_location_regex.is_match(id),
+            data.compile_error_at(position, ErrorType::InvalidResourceLocation(id))
+        );
+        Ok(())
+    }
+
+    /// Parses `<id>[<key>=<value>,...]`. A value can be quoted to hold commas, brackets or spaces.
+    /// `position` is where `block_state` starts, so errors mark the offending property.
+    fn parse_block_state(
+        data: &StatementData,
+        block_state: &str,
+        position: Position,
+    ) -> AResult<BlockState> {
+        let Some((id, rest)) = block_state.split_once('[') else {
+            Self::validate_resource_location(data, block_state, position)?;
+            return Ok(BlockState::new(block_state.to_string(), Vec::new()));
+        };
+        Self::validate_resource_location(data, id, position)?;
+        let state = rest.strip_suffix(']').ok_or_else(|| {
+            data.compile_error_at(
+                position + block_state.chars().count(),
+                ErrorType::InvalidState(rest),
+            )
+        })?;
+        let mut properties: Vec<(String, String)> = Vec::new();
+        if state.trim().is_empty() {
+            return Ok(BlockState::new(id.to_string(), properties));
+        }
+        for (start, property) in split_properties(state) {
+            let leading = property.len() - property.trim_start().len();
+            let column = position + (id.len() + 1 + start + leading);
+            let property = property.trim();
+            let error = |error_type| data.compile_error_at(column, error_type);
+
+            let (key, value) = property
+                .split_once('=')
+                .ok_or_else(|| error(ErrorType::InvalidStateProperty(property)))?;
+            let (key, value) = (key.trim(), value.trim());
+            ensure!(
+                !key.is_empty() && data.property_regex.is_match(key),
+                error(ErrorType::InvalidStateProperty(property))
+            );
+            let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                Some(quoted) if !quoted.contains('"') => quoted,
+                _ => {
+                    ensure!(
+                        !value.is_empty() && data.property_regex.is_match(value),
+                        error(ErrorType::InvalidStateProperty(property))
+                    );
+                    value
+                }
+            };
+            ensure!(
+                properties.iter().all(|(existing, _)| existing != key),
+                error(ErrorType::DuplicateStateProperty(key))
+            );
+            if data.validate_block_states {
+                Self::validate_block_property(id, key, value).map_err(error)?;
+            }
+            properties.push((key.to_string(), value.to_string()));
+        }
+        Ok(BlockState::new(id.to_string(), properties))
+    }
+
+    /// Checks a property against the vanilla block's, if the block is in the bundled table.
+    fn validate_block_property<'a>(
+        id: &'a str,
+        key: &'a str,
+        value: &'a str,
+    ) -> Result<(), ErrorType<'a>> {
+        let Some(known) = block_properties::properties(id) else {
+            return Ok(());
+        };
+        let quoted = |values: &[&str]| values.iter().map(|value| format!("\"{value}\"")).join(", ");
+        let (_, values) = known.iter().find(|(name, _)| *name == key).ok_or_else(|| {
+            let names: Vec<&str> = known.iter().map(|(name, _)| *name).collect();
+            ErrorType::UnknownStateProperty(key, id, quoted(&names))
+        })?;
+        if values.contains(&value) {
+            Ok(())
+        } else {
+            Err(ErrorType::InvalidStateValue(value, key, quoted(values)))
+        }
+    }
+
+    fn parse_text(data: StatementData) -> AResult<Self> {
+        let arguments = data.arguments;
+        let name_regex = data.name_regex;
+        arg_count!(>= 2, data);
+        // Unquoted text runs to the end of the line, but quoted text and JSON end with their argument.
+        if arguments[1].starts_with(['"', '{', '[']) {
+            data.reject_extra(2)?;
+        }
+
+        let entity =
+            Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
+        let text = arguments[1..].join(" ");
+        let error = |error_type| data.compile_error_at(data.argument_position(1), error_type);
+        if text.starts_with(['{', '[']) {
+            serde_json::from_str::<serde_json::Value>(&text)
+                .map_err(|err| error(ErrorType::InvalidJson(err.to_string())))?;
+            return Ok(Self::Text(entity, TextComponent::Json(text)));
+        }
+        let text = text
+            .strip_prefix('"')
+            .and_then(|text| text.strip_suffix('"'))
+            .unwrap_or(&text);
+        let text = Self::unescape(text).map_err(error)?;
+        Ok(Self::Text(entity, TextComponent::Plain(text)))
+    }
+
+    /// Interprets the `\\n`, `\\t`, `\\'`, `\\"` and `\\\\` escape sequences in `text`.
+    fn unescape(text: &str) -> Result<String, ErrorType<'static>> {
+        let mut unescaped = String::with_capacity(text.len());
+        let mut chars = text.chars();
+        while let Some(character) = chars.next() {
+            if character != '\\' {
+                unescaped.push(character);
+                continue;
+            }
+            let escaped = match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(c @ ('\'' | '"' | '\\')) => c,
+                other => return Err(ErrorType::InvalidEscape(other)),
+            };
+            unescaped.push(escaped);
+        }
+        Ok(unescaped)
+    }
+
+    fn parse_teleport(data: StatementData) -> AResult<Self> {
+        let arguments = data.arguments;
+        let name_regex = data.name_regex;
+        data.reject_extra(4)?;
+        arg_count!(== 4, data);
+
+        let entity =
+            Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
+        let (x, y, z) = data.coordinates(1)?;
+
+        Ok(Self::Teleport(entity, x, y, z))
+    }
+
+    fn parse_color(data: StatementData) -> AResult<Self> {
+        let arguments = data.arguments;
+        let name_regex = data.name_regex;
+        data.reject_extra(2)?;
+        arg_count!(== 2, data);
+
+        let entity =
+            Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
+        let color = TextColor::new(arguments[1]).map_err(|err| data.compile_error(err))?;
+        Ok(Self::Color(entity, color))
+    }
+
+    fn parse_tag(data: StatementData) -> AResult<Self> {
+        let arguments = data.arguments;
+        let name_regex = data.name_regex;
+        data.reject_extra(3)?;
+        arg_count!(== 3, data);
+
+        let entity =
+            Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
+        let operation = TagOperation::new(arguments[1]).map_err(|err| data.compile_error(err))?;
+        let tag = arguments[2];
+        ensure!(
+            name_regex.is_match(tag),
+            data.compile_error(ErrorType::InvalidCharacters(tag))
+        );
+        Ok(Self::Tag(entity, operation, tag.to_string()))
+    }
+
+    fn parse_mirror(data: StatementData) -> AResult<Self> {
+        let arguments = data.arguments;
+        let name_regex = data.name_regex;
+        data.reject_extra(2)?;
+        arg_count!(== 2, data);
+
+        let entity =
+            Entity::new(arguments[0], name_regex).map_err(|err| data.compile_error(err))?;
+        let axis = Axis::new(arguments[1]).map_err(|err| data.compile_error(err))?;
+        Ok(Self::Mirror(entity, axis))
+    }
+
+    fn parse_hold(data: StatementData) -> AResult<Self> {
+        let arguments = data.arguments;
+        data.reject_extra(2)?;
+        arg_count!(== 2, data);
+
+        let entity =
+            Entity::new(arguments[0], data.name_regex).map_err(|err| data.compile_error(err))?;
+        let ticks = arguments[1]
+            .parse()
+            .map_err(|err| data.compile_error(ErrorType::InvalidInt(arguments[1], err)))?;
+        Ok(Self::Hold(entity, ticks))
+    }
+
+    fn parse_entity(data: StatementData) -> AResult<Self> {
+        let arguments = data.arguments;
+        data.reject_extra(1)?;
+        arg_count!(== 1, data);
+
+        let entity =
+            Entity::new(arguments[0], data.name_regex).map_err(|err| data.compile_error(err))?;
+        ensure!(
+            !entity.has_predicate(),
+            data.compile_error(ErrorType::PredicateNotAllowed(arguments[0]))
+        );
+        Ok(Self::Entity(entity))
+    }
+
+    /// Whether the statement is a kept comment or region marker, which is copied rather than compiled.
+    pub const fn is_comment(&self) -> bool {
+        matches!(self, Self::Comment(_) | Self::Region(_) | Self::EndRegion)
+    }
+
+    /// Whether the statement only configures the animation, rather than doing something in it.
+    const fn is_directive(&self) -> bool {
+        matches!(
+            self,
+            Self::ObjectName(..)
+                | Self::Namespace(_)
+                | Self::Output(_)
+                | Self::Entity(_)
+                | Self::With(_)
+                | Self::Reverse
+                | Self::Loop(_)
+                | Self::Comment(_)
+                | Self::Region(_)
+                | Self::EndRegion
+                | Self::Empty
+        )
+    }
+
+    /// The entities the statement acts on, which must already exist.
+    fn referenced_entities(&self) -> Vec<&Entity> {
+        match self {
+            Self::Translate(entity, ..)
+            | Self::Rotate(entity, ..)
+            | Self::Scale(entity, ..)
+            | Self::Spawn(entity, ..)
+            | Self::Item(entity, _)
+            | Self::Block(entity, _)
+            | Self::Text(entity, _)
+            | Self::Teleport(entity, ..)
+            | Self::Tag(entity, ..)
+            | Self::Color(entity, _)
+            | Self::Mirror(entity, _)
+            | Self::Hold(entity, _)
+            | Self::Anchor(entity, _)
+            | Self::With(entity) => vec![entity],
+            Self::Offset(_, statement)
+            | Self::AxisDurations(_, statement)
+            | Self::Eased(_, statement) => statement.referenced_entities(),
+            Self::Frames(_, frames) => frames
+                .first()
+                .map(Self::referenced_entities)
+                .unwrap_or_default(),
+            Self::ObjectName(..)
+            | Self::Namespace(_)
+            | Self::Output(_)
+            | Self::Wait(_)
+            | Self::WaitFor(_)
+            | Self::Raw(..)
+            | Self::Entity(_)
+            | Self::Reverse
+            | Self::Loop(_)
+            | Self::Comment(_)
+            | Self::Region(_)
+            | Self::EndRegion
+            | Self::Empty => Vec::new(),
+        }
+    }
+
+    /// The name the statement makes valid to refer to. Entities are selected by tag, so a tag added
+    /// to an entity names a group as well.
+    fn declared_name(&self) -> Option<&str> {
+        match self {
+            Self::Entity(entity) | Self::Spawn(_, _, entity, ..) => Some(entity.name()),
+            Self::Tag(_, TagOperation::Add, tag) => Some(tag),
+            _ => None,
+        }
+    }
+
+    fn parse_anchor(data: StatementData) -> AResult<Self> {
+        let arguments = data.arguments;
+        data.reject_extra(4)?;
+        arg_count!(== 4, data);
+
+        let entity =
+            Entity::new(arguments[0], data.name_regex).map_err(|err| data.compile_error(err))?;
+        let position = data.coordinates(1)?;
+        Ok(Self::Anchor(entity, Translation::new(position)))
+    }
+
+    /// Takes everything after the keyword from the line itself rather than from the split arguments,
+    /// so the command's spacing and quoting reach the output untouched.
+    fn parse_raw(data: StatementData) -> AResult<Self> {
+        arg_count!(>= 1, data);
+        let command = data
+            .buffer
+            .0
+            .split_once(char::is_whitespace)
+            .map_or("", |(_, command)| command.trim_start());
+        let command = command
+            .strip_prefix(Self::RAW_COMMAND_PREFIX)
+            .unwrap_or(command);
+        Ok(Self::Raw(command.to_string(), true))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 enum Keyword {
     Object,
+    Namespace,
+    Output,
     Wait,
     Translate,
     Rotate,
@@ -398,6 +1771,39 @@ enum Keyword {
     Block,
     Text,
     Teleport,
+    Tag,
+    Color,
+    Mirror,
+    Raw,
+    Hold,
+    Anchor,
+    Entity,
+    Reverse,
+    Loop,
+    Once,
+    WaitFor,
+    With,
+}
+impl Keyword {
+    /// Whether the statement's first argument is the entity it acts on, which `with` can fill in.
+    const fn takes_entity(self) -> bool {
+        matches!(
+            self,
+            Self::Translate
+                | Self::Rotate
+                | Self::Scale
+                | Self::Spawn
+                | Self::Item
+                | Self::Block
+                | Self::Text
+                | Self::Teleport
+                | Self::Tag
+                | Self::Color
+                | Self::Mirror
+                | Self::Hold
+                | Self::Anchor
+        )
+    }
 }
 impl<'a> TryFrom<&'a str> for Keyword {
     type Error = ErrorType<'a>;
@@ -405,6 +1811,8 @@ impl<'a> TryFrom<&'a str> for Keyword {
     fn try_from(value: &'a str) -> Result<Self, Self::Error> {
         let result = match value.to_lowercase().as_str() {
             "object" | "anim" => Self::Object,
+            "namespace" => Self::Namespace,
+            "output" => Self::Output,
             "wait" | "delay" => Self::Wait,
             "translate" | "move" | "m" => Self::Translate,
             "rotate" | "turn" | "r" => Self::Rotate,
@@ -414,32 +1822,1309 @@ impl<'a> TryFrom<&'a str> for Keyword {
             "block" => Self::Block,
             "text" => Self::Text,
             "teleport" | "tp" => Self::Teleport,
+            "tag" => Self::Tag,
+            "color" | "colour" => Self::Color,
+            "mirror" | "flip" => Self::Mirror,
+            "raw" | "cmd" => Self::Raw,
+            "hold" | "freeze" => Self::Hold,
+            "anchor" => Self::Anchor,
+            "entity" => Self::Entity,
+            "reverse" => Self::Reverse,
+            "loop" => Self::Loop,
+            "once" => Self::Once,
+            "waitfor" => Self::WaitFor,
+            "with" => Self::With,
             _ => return Err(ErrorType::InvalidKeyword(value)),
         };
         Ok(result)
     }
 }
 
-fn get_buffer_string(line: &[TrackedChar]) -> (String, Position) {
+/// Reports every statement naming an entity that is never declared with `entity`, spawned, or added
+/// as a tag anywhere in the file. Declarations can come after their first use.
+///
+/// # Errors
+/// If any name is unknown, this returns an `ErrorCollection` of `UnknownEntity` errors.
+fn check_entities(
+    file_info: &FileInfo,
+    statements: &[Statement],
+    sources: &[(Position, String)],
+) -> AResult<()> {
+    let declared: HashSet<&str> = statements
+        .iter()
+        .filter_map(Statement::declared_name)
+        .collect();
+    let references = statements
+        .iter()
+        .zip(sources)
+        .flat_map(|(statement, (position, _))| {
+            statement
+                .referenced_entities()
+                .into_iter()
+                .map(move |entity| (entity, *position))
+        })
+        .filter(|(entity, _)| !declared.contains(entity.name()))
+        .map(|(entity, position)| {
+            Err::<(), _>(CompileError::new(
+                file_info,
+                position,
+                ErrorType::UnknownEntity(entity.name()),
+            ))
+        })
+        .collect();
+    crate::collect_errors(references)?;
+    Ok(())
+}
+
+/// Rejects entities named after the object they belong to. Every entity of the object is tagged with
+/// its name, so such an entity's selector would match all of them.
+///
+/// # Errors
+/// If any entity is named after its object, this returns an `ErrorCollection` of `ReservedEntityName` errors.
+fn check_object_collisions(
+    file_info: &FileInfo,
+    statements: &[Statement],
+    sources: &[(Position, String)],
+) -> AResult<()> {
+    let file_name = Path::new(&file_info.path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut object_name = file_name.as_str();
+    let mut collisions = Vec::new();
+    for (statement, (position, _)) in statements.iter().zip(sources) {
+        if let Statement::ObjectName(object, _) = statement {
+            object_name = object.as_deref().unwrap_or(&file_name);
+        }
+        let names = statement
+            .referenced_entities()
+            .into_iter()
+            .map(Entity::name)
+            .chain(statement.declared_name());
+        for name in names.filter(|name| *name == object_name) {
+            collisions.push(Err::<(), _>(CompileError::new(
+                file_info,
+                *position,
+                ErrorType::ReservedEntityName(name),
+            )));
+        }
+    }
+    crate::collect_errors(collisions)?;
+    Ok(())
+}
+
+/// Pairs every `#endregion` with the `#region` before it.
+///
+/// # Errors
+/// If an `#endregion` has no region to close, or a region is never closed, this returns an
+/// `ErrorCollection` of `UnmatchedEndRegion` and `UnterminatedRegion` errors, the latter pointing at
+/// the region's `#region` line.
+fn check_regions(
+    file_info: &FileInfo,
+    statements: &[Statement],
+    sources: &[(Position, String)],
+) -> AResult<()> {
+    let mut open = Vec::new();
+    let mut errors = Vec::new();
+    for (statement, (position, _)) in statements.iter().zip(sources) {
+        match statement {
+            Statement::Region(name) => open.push((name.as_str(), *position)),
+            Statement::EndRegion if open.pop().is_none() => {
+                errors.push(Err::<(), _>(CompileError::new(
+                    file_info,
+                    *position,
+                    ErrorType::UnmatchedEndRegion,
+                )));
+            }
+            _ => {}
+        }
+    }
+    for (name, position) in open {
+        errors.push(Err(CompileError::new(
+            file_info,
+            position,
+            ErrorType::UnterminatedRegion(name),
+        )));
+    }
+    crate::collect_errors(errors)?;
+    Ok(())
+}
+
+/// Blanks out every character inside a `/* ... */` comment, keeping newlines so positions stay correct.
+/// Quoted text and `#` line comments are skipped, so neither can open a block comment.
+pub fn strip_block_comments(
+    file_info: &FileInfo,
+    contents: &[TrackedChar],
+) -> Result<Vec<TrackedChar>, CompileError> {
+    let mut stripped = contents.to_vec();
+    let mut quoted = false;
+    let mut line_comment = false;
+    let mut opening: Option<Position> = None;
+    let mut index = 0;
+    while index < stripped.len() {
+        let character = stripped[index].character;
+        let next = stripped.get(index + 1).map(|c| c.character);
+        let opens =
+            opening.is_none() && !quoted && !line_comment && character == '/' && next == Some('*');
+        let closes = opening.is_some() && character == '*' && next == Some('/');
+        if opens || closes {
+            opening = opens.then_some(stripped[index].position);
+            stripped[index].character = ' ';
+            stripped[index + 1].character = ' ';
+            index += 2;
+            continue;
+        }
+        if opening.is_some() {
+            if character != '\n' {
+                stripped[index].character = ' ';
+            }
+        } else {
+            match character {
+                '\n' => {
+                    quoted = false;
+                    line_comment = false;
+                }
+                '"' if !line_comment => quoted = !quoted,
+                '#' if !quoted && is_comment_start(&stripped, index) => line_comment = true,
+                _ => {}
+            }
+        }
+        index += 1;
+    }
+    opening.map_or(Ok(stripped), |position| {
+        Err(CompileError::new(
+            file_info,
+            position,
+            ErrorType::UnterminatedComment,
+        ))
+    })
+}
+
+/// Joins every line ending in an unescaped `\\` (outside quotes and `#` comments) onto the next one.
+/// The joined characters keep their own tracked positions, so errors still point at the physical line.
+///
+/// # Errors
+/// If a continuation is followed by a blank line or the end of the file, this returns a
+/// `DanglingContinuation` error pointing at its backslash.
+pub fn join_continuations(
+    file_info: &FileInfo,
+    contents: &[TrackedChar],
+) -> Result<Vec<TrackedChar>, CompileError> {
+    let mut joined: Vec<TrackedChar> = Vec::with_capacity(contents.len());
+    let mut quoted = false;
+    let mut line_comment = false;
+    let mut pending: Option<Position> = None;
+    for (offset, &tracked) in contents.iter().enumerate() {
+        if pending.is_some() && matches!(tracked.character, ' ' | '\t') {
+            // Indentation of a continued line is collapsed into the single separating space.
+            continue;
+        }
+        match tracked.character {
+            '\n' => {
+                if let Some(position) = pending {
+                    // A blank line ends the statement, so there is nothing left to continue it.
+                    return Err(CompileError::new(
+                        file_info,
+                        position,
+                        ErrorType::DanglingContinuation,
+                    ));
+                }
+                if !quoted && !line_comment {
+                    if let Some(index) = continuation_index(&joined) {
+                        let mut separator = joined[index];
+                        separator.character = ' ';
+                        pending = Some(separator.position);
+                        joined.truncate(index);
+                        while joined
+                            .last()
+                            .is_some_and(|c| matches!(c.character, ' ' | '\t'))
+                        {
+                            joined.pop();
+                        }
+                        joined.push(separator);
+                        continue;
+                    }
+                }
+                quoted = false;
+                line_comment = false;
+            }
+            '"' if !line_comment => quoted = !quoted,
+            '#' if !quoted && is_comment_start(contents, offset) => line_comment = true,
+            _ => {}
+        }
+        if !tracked.character.is_whitespace() {
+            pending = None;
+        }
+        joined.push(tracked);
+    }
+    if !quoted && !line_comment {
+        if let Some(index) = continuation_index(&joined) {
+            pending = Some(joined[index].position);
+        }
+    }
+    pending.map_or(Ok(joined), |position| {
+        Err(CompileError::new(
+            file_info,
+            position,
+            ErrorType::DanglingContinuation,
+        ))
+    })
+}
+
+/// Returns the index of the trailing continuation backslash of the last line, if it has one.
+fn continuation_index(line: &[TrackedChar]) -> Option<usize> {
+    let end = line
+        .iter()
+        .rposition(|c| c.character != ' ' && c.character != '\t')?;
+    let backslashes = line[..=end]
+        .iter()
+        .rev()
+        .take_while(|c| c.character == '\\')
+        .count();
+    (backslashes % 2 == 1).then_some(end)
+}
+
+fn get_buffer_string(file_info: &FileInfo, line: &[TrackedChar]) -> (String, Position) {
     let mut quoted: bool = false;
-    assert_ne!(line.len(), 0);
-    let raw: bool = line[0].character == Statement::RAW_COMMAND_PREFIX;
-    let pos: Position = line[0].position;
+    let Some(first) = line.first() else {
+        return (String::new(), file_info.eof.position);
+    };
+    let raw: bool = first.character == Statement::RAW_COMMAND_PREFIX;
+    let pos: Position = first.position;
     let string: String = line
         .iter()
-        .map(|line| line.character)
-        .take_while(|&char| {
-            if char == '"' {
+        .enumerate()
+        .take_while(|&(index, tracked)| {
+            if tracked.character == '"' {
                 quoted = !quoted;
             }
-            char != '#' || quoted || raw
+            quoted || raw || !is_comment_start(line, index)
         })
+        .map(|(_, tracked)| tracked.character)
         .collect();
     (string.trim().to_string(), pos)
 }
 
+/// Splits a statement on the whitespace that is outside quotes and brackets, so quoted text, block states,
+/// item components and JSON stay in one argument even when they contain spaces. Any run of spaces and
+/// tabs separates two arguments, so no argument is ever empty.
+/// Each argument is paired with the byte index it starts at in `line`.
+fn split_arguments(line: &str) -> Vec<(usize, &str)> {
+    let mut arguments = Vec::new();
+    let mut start = 0;
+    let mut depth = 0_usize;
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, character) in line.char_indices() {
+        match (quote, character) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(open), _) if character == open => quote = None,
+            (None, '"' | '\'') => quote = Some(character),
+            (None, '[' | '{') => depth += 1,
+            (None, ']' | '}') => depth = depth.saturating_sub(1),
+            (None, _) if depth == 0 && character.is_whitespace() => {
+                if start < index {
+                    arguments.push((start, &line[start..index]));
+                }
+                start = index + character.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    if start < line.len() {
+        arguments.push((start, &line[start..]));
+    }
+    arguments
+}
+
+/// Splits block state properties on the commas that are outside quotes and nested brackets,
+/// returning each property along with its byte index in `state`.
+fn split_properties(state: &str) -> Vec<(usize, &str)> {
+    let mut properties = Vec::new();
+    let mut start = 0;
+    let mut depth = 0_usize;
+    let mut quoted = false;
+    for (index, character) in state.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            '[' if !quoted => depth += 1,
+            ']' if !quoted => depth = depth.saturating_sub(1),
+            ',' if !quoted && depth == 0 => {
+                properties.push((start, &state[start..index]));
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    properties.push((start, &state[start..]));
+    properties
+}
+
+/// Whether the character at `index` starts a `#` comment. The color argument of a `color` statement
+/// is a `#RRGGBB` code instead, so `color test #FF8800` keeps its argument, while a comment like
+/// `#facade` anywhere else is still a comment.
+fn is_comment_start(chars: &[TrackedChar], index: usize) -> bool {
+    if chars[index].character != '#' {
+        return false;
+    }
+    let line_start = chars[..index]
+        .iter()
+        .rposition(|c| c.character == '\n')
+        .map_or(0, |newline| newline + 1);
+    let before: String = chars[line_start..index]
+        .iter()
+        .map(|c| c.character)
+        .collect();
+    let is_color_argument = before.ends_with(char::is_whitespace)
+        && matches!(before.split_whitespace().collect_vec()[..], ["color", _]);
+    let rest = &chars[index + 1..];
+    let hex_digits = rest
+        .iter()
+        .take_while(|c| c.character.is_ascii_hexdigit())
+        .count();
+    let is_color = is_color_argument
+        && hex_digits == 6
+        && rest.get(6).is_none_or(|c| c.character.is_whitespace());
+    !is_color
+}
+
 #[allow(unused_imports, clippy::missing_const_for_fn, clippy::unwrap_used)]
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::file_reader::to_tracked;
+
+    fn test_config() -> Config {
+        serde_json::from_str(
+            r#"{"source_folder":"./src","target_folder":"./objects","tick_function":"./tick.mcfunction","namespace":"de"}"#,
+        )
+        .unwrap()
+    }
+
+    fn parse_with(line: &str, config: &Config) -> AResult<Statement> {
+        let file_info = FileInfo::new("test.dspa".to_string(), TrackedChar::new(1, 1, '\n'));
+        Statement::parse_from_file(
+            &file_info,
+            &to_tracked(line),
+            &Regexes::new(false).unwrap(),
+            config,
+            &RefCell::new(Vec::new()),
+            None,
+            &HashSet::new(),
+        )
+    }
+
+    fn parse(line: &str) -> AResult<Statement> {
+        parse_with(line, &test_config())
+    }
+
+    fn parse_program(contents: &str) -> AResult<Program> {
+        let file_info = FileInfo::new("test.dspa".to_string(), TrackedChar::new(1, 1, '\n'));
+        Program::parse_from_file(&file_info, &to_tracked(contents), &test_config())
+    }
+
+    #[test]
+    fn empty_line_uses_eof_position() {
+        let file_info = FileInfo::new("test.dspa".to_string(), TrackedChar::new(4, 7, '\n'));
+        let (buffer, position) = get_buffer_string(&file_info, &[]);
+        assert!(buffer.is_empty());
+        assert_eq!((position.line, position.column), (4, 7));
+        assert_eq!(
+            Statement::parse_from_file(
+                &file_info,
+                &[],
+                &Regexes::new(false).unwrap(),
+                &test_config(),
+                &RefCell::new(Vec::new()),
+                None,
+                &HashSet::new()
+            )
+            .unwrap(),
+            Statement::Empty
+        );
+    }
+
+    #[test]
+    fn whitespace_only_lines_are_empty() {
+        let program = parse_program("wait 1\n    \n\t \nwait 2").unwrap();
+        assert_eq!(
+            program.statements,
+            [
+                Statement::Wait(1),
+                Statement::Empty,
+                Statement::Empty,
+                Statement::Wait(2)
+            ]
+        );
+    }
+
+    #[test]
+    fn file_without_trailing_newline_parses_last_line() {
+        let program = parse_program("wait 1\nwait 2").unwrap();
+        assert_eq!(program.statements.last(), Some(&Statement::Wait(2)));
+    }
+
+    fn compiled_rotation(statement: &Statement) -> String {
+        let Statement::Rotate(_, rotation, ..) = statement else {
+            panic!("expected a rotate statement");
+        };
+        rotation.compile()
+    }
+
+    #[test]
+    fn angle_suffix_overrides_configured_unit() {
+        let degrees = compiled_rotation(&parse("turn test y 90 20").unwrap());
+        assert_eq!(
+            degrees,
+            compiled_rotation(&parse("turn test y 1.5707964rad 20").unwrap())
+        );
+
+        let mut config = test_config();
+        config.angle_unit = AngleUnit::Radians;
+        assert_eq!(
+            degrees,
+            compiled_rotation(&parse_with("turn test y 1.5707964 20", &config).unwrap())
+        );
+        assert_eq!(
+            degrees,
+            compiled_rotation(&parse_with("turn test y 90deg 20", &config).unwrap())
+        );
+    }
+
+    #[test]
+    fn block_comments_span_lines() {
+        let program = parse_program("wait 1\n/* wait 2\nwait 3 */ wait 4\nwait 5").unwrap();
+        assert_eq!(
+            program.statements,
+            [
+                Statement::Wait(1),
+                Statement::Empty,
+                Statement::Wait(4),
+                Statement::Wait(5)
+            ]
+        );
+        assert_eq!(program.sources[2].0.line, 3);
+    }
+
+    #[test]
+    fn block_comments_ignore_quotes_and_line_comments() {
+        let program =
+            parse_program("text t \"/* not a comment */\"\nwait 1 # /* nor this").unwrap();
+        assert_eq!(
+            program.statements[0],
+            Statement::Text(
+                Entity::new("t", &Regexes::new(false).unwrap().name).unwrap(),
+                TextComponent::Plain("/* not a comment */".to_string())
+            )
+        );
+        assert_eq!(program.statements[1], Statement::Wait(1));
+    }
+
+    #[test]
+    fn region_markers_must_pair_up() {
+        let program = parse_program(
+            "#region door\nmove door 0 1 0 20\n  #region  lid  \n#endregion lid\n#endregion\n# regional",
+        )
+        .unwrap();
+        assert_eq!(program.statements[0], Statement::Region("door".to_string()));
+        assert_eq!(program.statements[2], Statement::Region("lid".to_string()));
+        assert_eq!(program.statements[3], Statement::EndRegion);
+        assert_eq!(program.statements[5], Statement::Empty);
+
+        let error = parse_program("wait 1\n#region door\n#region lid\n#endregion")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Line: 2, Column: 1")
+                && error.contains("Region 'door' is never closed with '#endregion'."),
+            "{error}"
+        );
+        let error = parse_program("wait 1\n#endregion").unwrap_err().to_string();
+        assert!(
+            error.contains("Line: 2, Column: 1")
+                && error.contains("'#endregion' has no '#region' before it to close."),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn unterminated_block_comment_points_at_opening() {
+        let error = parse_program("wait 1\n  /* wait 2\nwait 3")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Line: 2, Column: 3"), "{error}");
+    }
+
+    #[test]
+    fn trailing_backslash_continues_statement() {
+        let program = parse_program("move cube \\\n  0 1 0 \\\n  20\nwait 1").unwrap();
+        assert_eq!(program.statements[0], parse("move cube 0 1 0 20").unwrap());
+        assert_eq!(program.statements[1], Statement::Wait(1));
+    }
+
+    #[test]
+    fn continued_tokens_keep_their_physical_position() {
+        let contents = join_continuations(
+            &FileInfo::new("test.dspa".to_string(), TrackedChar::new(1, 1, '\n')),
+            &to_tracked("wait \\\n  1"),
+        )
+        .unwrap();
+        let last = contents.last().unwrap();
+        assert_eq!((last.character, last.position.line), ('1', 2));
+    }
+
+    #[test]
+    fn escaped_and_quoted_backslashes_do_not_continue() {
+        let program = parse_program("text t \"a \\\\\"\nwait 1").unwrap();
+        assert_eq!(program.statements[1], Statement::Wait(1));
+    }
+
+    #[test]
+    fn backslash_at_end_of_file_is_an_error() {
+        let error = parse_program("wait 1\nwait \\").unwrap_err().to_string();
+        assert!(error.contains("Line: 2, Column: 6"), "{error}");
+        assert!(parse_program("wait \\\n").is_err());
+    }
+
+    #[test]
+    fn backslash_before_blank_line_is_an_error() {
+        for contents in ["wait \\\n\nwait 1", "wait \\\n   \nwait 1"] {
+            let error = parse_program(contents).unwrap_err().to_string();
+            assert!(
+                error.contains("Line: 1, Column: 6")
+                    && error.contains("is not followed by another line"),
+                "{error}"
+            );
+        }
+    }
+
+    #[test]
+    fn spawn_accepts_optional_offset() {
+        let Statement::Spawn(_, _, _, offset, _) = parse("spawn test block_display child").unwrap()
+        else {
+            panic!("expected a spawn statement");
+        };
+        assert_eq!(offset, None);
+        let Statement::Spawn(_, _, _, offset, _) =
+            parse("spawn test block_display child at 0 2 -1.5").unwrap()
+        else {
+            panic!("expected a spawn statement");
+        };
+        assert_eq!(offset, Some((0.0, 2.0, -1.5)));
+        assert!(parse("spawn test block_display child by 0 2 0").is_err());
+    }
+
+    #[test]
+    fn group_targets_select_by_tag() {
+        let Statement::Translate(entity, ..) = parse("move group:petals 0 1 0 20").unwrap() else {
+            panic!("expected a translate statement");
+        };
+        assert!(entity.is_group());
+        assert_eq!(entity.name(), "petals");
+        assert!(parse("move group:bad/name 0 1 0 20").is_err());
+        assert!(parse("spawn test block_display group:petals").is_err());
+    }
+
+    #[test]
+    fn transforms_warn_about_zero_duration() {
+        let program =
+            parse_program("move cube 0 1 0 0\nturn cube y 90 0\nsize cube 2 0\nmove cube 0 1 0 1")
+                .unwrap();
+        assert_eq!(program.statements.len(), 4);
+        assert_eq!(program.warnings.len(), 3);
+        for warning in &program.warnings {
+            let warning = warning.to_string();
+            assert!(warning.contains("duration of 0"), "{warning}");
+        }
+        assert!(program.warnings[1].to_string().contains("Line: 2"));
+    }
+
+    #[test]
+    fn tag_statement_validates_operation_and_name() {
+        let Statement::Tag(_, operation, tag) = parse("tag test remove highlighted").unwrap()
+        else {
+            panic!("expected a tag statement");
+        };
+        assert_eq!(
+            (operation, tag.as_str()),
+            (TagOperation::Remove, "highlighted")
+        );
+        assert!(parse("tag test toggle highlighted").is_err());
+        assert!(parse("tag test add high/lighted").is_err());
+    }
+
+    #[test]
+    fn text_interprets_escapes() {
+        let Statement::Text(_, text) = parse(r#"text t "It\'s a\nnew \"line\" \\ end""#).unwrap()
+        else {
+            panic!("expected a text statement");
+        };
+        assert_eq!(
+            text,
+            TextComponent::Plain("It's a\nnew \"line\" \\ end".to_string())
+        );
+        let error = parse(r#"text t "bad \q""#).unwrap_err().to_string();
+        assert!(error.contains("'\\q'"), "{error}");
+    }
+
+    #[test]
+    fn text_detects_json_components() {
+        let Statement::Text(_, text) = parse(r#"text t {"text":"Hi","color":"red"}"#).unwrap()
+        else {
+            panic!("expected a text statement");
+        };
+        assert_eq!(
+            text,
+            TextComponent::Json(r#"{"text":"Hi","color":"red"}"#.to_string())
+        );
+        assert!(parse(r#"text t ["Hi", {"text":"there"}]"#).is_ok());
+        assert!(parse(r#"text t {"text":"Hi""#).is_err());
+    }
+
+    #[test]
+    fn color_accepts_names_and_hex_codes() {
+        let Statement::Color(_, color) = parse("color test Red").unwrap() else {
+            panic!("expected a color statement");
+        };
+        assert_eq!(color, TextColor::Named("red"));
+        let Statement::Color(_, color) = parse("color test #ff8800").unwrap() else {
+            panic!("expected a color statement");
+        };
+        assert_eq!(color.compile(), "#FF8800");
+        assert!(parse("color test crimson").is_err());
+        assert!(parse("color test #FF88").is_err());
+        assert!(parse("color test #GG8800").is_err());
+        assert_eq!(
+            parse("wait 1 #FF88001 comment").unwrap(),
+            Statement::Wait(1)
+        );
+        assert_eq!(parse("wait 1 #facade").unwrap(), Statement::Wait(1));
+        assert_eq!(parse("#decade").unwrap(), Statement::Empty);
+        assert_eq!(
+            parse("move test 0 1 0 20 #decade").unwrap(),
+            parse("move test 0 1 0 20").unwrap()
+        );
+    }
+
+    #[test]
+    fn uniform_scale_matches_three_axis_scale() {
+        assert_eq!(
+            parse("size test 2 20").unwrap(),
+            parse("size test 2 2 2 20").unwrap()
+        );
+    }
+
+    #[test]
+    fn rotation_about_pivot_offsets_translation() {
+        let Statement::Rotate(_, rotation, ..) = parse("turn test y 180 20 pivot 1 0 0").unwrap()
+        else {
+            panic!("expected a rotate statement");
+        };
+        let compiled = rotation.compile();
+        assert!(compiled.starts_with("translation: [2f,0f,"), "{compiled}");
+        assert!(compiled.contains(",left_rotation: ["), "{compiled}");
+    }
+
+    #[test]
+    fn rotation_without_pivot_has_no_translation() {
+        let Statement::Rotate(_, rotation, ..) = parse("turn test y 90 20").unwrap() else {
+            panic!("expected a rotate statement");
+        };
+        assert_eq!(
+            rotation.compile(),
+            "left_rotation: [0f,0.70710677f,0f,0.70710677f]"
+        );
+    }
+
+    #[test]
+    fn scale_rejects_other_argument_counts() {
+        let error = parse("size test 2 2 20").unwrap_err().to_string();
+        assert!(error.contains("Expected '3 or 5', found '4'"), "{error}");
+    }
+
+    #[test]
+    fn quaternion_rotation_is_normalized() {
+        let rotation = compiled_rotation(&parse("turn test quat 0 0 2 0 20").unwrap());
+        assert_eq!(rotation, "left_rotation: [0f,0f,1f,0f]");
+        assert!(parse("turn test quat 0 0 0.707 0.707 20 pivot 0 1 0").is_ok());
+    }
+
+    #[test]
+    fn quaternion_rotation_rejects_invalid_components() {
+        assert!(parse("turn test quat 0 0 0.707 20").is_err());
+        assert!(parse("turn test quat 0 0 0.707 0.707 0 20").is_err());
+        assert!(parse("turn test quat 0 0 a 0.707 20").is_err());
+        assert!(parse("turn test quat 0 0 0 0 20").is_err());
+    }
+
+    fn block_state(line: &str) -> String {
+        let Statement::Block(_, block_state) = parse(line).unwrap() else {
+            panic!("expected a block statement");
+        };
+        block_state.compile()
+    }
+
+    #[test]
+    fn block_state_keeps_simple_cases() {
+        assert_eq!(block_state("block test stone"), "Name:\"stone\"");
+        assert_eq!(
+            block_state("block test minecraft:oak_log[axis=y]"),
+            "Name:\"minecraft:oak_log\",Properties:{axis:\"y\"}"
+        );
+        assert_eq!(
+            block_state("block test oak_slab[type=top, waterlogged=false]"),
+            "Name:\"oak_slab\",Properties:{type:\"top\",waterlogged:\"false\"}"
+        );
+    }
+
+    #[test]
+    fn block_state_accepts_quoted_values() {
+        assert_eq!(
+            block_state("block test modded:sign[label=\"a, [b]\",facing=north]"),
+            "Name:\"modded:sign\",Properties:{label:\"a, [b]\",facing:\"north\"}"
+        );
+    }
+
+    #[test]
+    fn block_state_rejects_malformed_properties() {
+        let error = parse("block test oak_log[axis=y,axis=x]").unwrap_err();
+        assert!(error.to_string().contains("Column: 27"), "{error}");
+        let error = parse("block test oak_log[axis=y,junk]").unwrap_err();
+        assert!(error.to_string().contains("Column: 27"), "{error}");
+        assert!(parse("block test oak_log[axis=y").is_err());
+        assert!(parse("block test oak_log[axis=]").is_err());
+        assert!(parse("block test oak_log[=y]").is_err());
+        assert!(parse("block test oak_log[axis=y!]").is_err());
+        assert!(parse("block test oak_log[axis=\"y]").is_err());
+    }
+
+    #[test]
+    fn transformations_accept_a_duration_per_axis() {
+        let Statement::AxisDurations(durations, statement) =
+            parse("size test [20, 40, 20] 2 3 2 start 5").unwrap()
+        else {
+            panic!("expected per-axis durations");
+        };
+        assert_eq!(durations, [20, 40, 20]);
+        assert_eq!(
+            *statement,
+            Statement::Scale(
+                Entity::new("test", &Regexes::new(false).unwrap().name).unwrap(),
+                Scale::new((2.0, 3.0, 2.0)),
+                40,
+                5
+            )
+        );
+        assert!(matches!(
+            parse("move +5 test [1s,10,10] 0 1 0").unwrap(),
+            Statement::Offset(5, statement) if matches!(*statement, Statement::AxisDurations([20, 10, 10], _))
+        ));
+        assert_eq!(
+            parse("size test [10,10,10] 2").unwrap(),
+            parse("size test 2 10").unwrap()
+        );
+        assert!(parse("size test [10,10] 2").is_err());
+        assert!(parse("move test [10,10,x] 0 1 0").is_err());
+        assert!(parse("move test [10,10,10] 0 1").is_err());
+    }
+
+    #[test]
+    fn transformations_accept_an_easing() {
+        let Statement::Eased(easing, statement) =
+            parse("move test 0 1 0 20 ease-in start 5").unwrap()
+        else {
+            panic!("expected an eased translation");
+        };
+        assert_eq!(easing.name, "ease-in");
+        assert_eq!(
+            *statement,
+            Statement::Translate(
+                Entity::new("test", &Regexes::new(false).unwrap().name).unwrap(),
+                Translation::new((0.0, 1.0, 0.0)),
+                20,
+                5
+            )
+        );
+        assert!(matches!(
+            parse("size +5 test 2 10 ease").unwrap(),
+            Statement::Offset(5, statement) if matches!(*statement, Statement::Eased(..))
+        ));
+        let unknown = parse("move test 0 1 0 20 bounce").unwrap_err().to_string();
+        assert!(
+            unknown.contains("Easing 'bounce' is unknown. Expected one of ease, ease-in, ease-in-out, ease-out, linear,"),
+            "{unknown}"
+        );
+        assert!(parse("move test [10,20,10] 0 1 0 ease").is_err());
+        assert!(parse("turn test y 90 20 ease").is_err());
+
+        let config = Config {
+            easings: BTreeMap::from([("bounce".to_string(), Curve::Table(vec![0.0, 1.2, 1.0]))]),
+            ..test_config()
+        };
+        assert!(parse_with("move test 0 1 0 20 bounce", &config).is_ok());
+    }
+
+    #[test]
+    fn only_existing_entities_take_predicates() {
+        assert!(parse("spawn test[tag=ready] block_display child").is_ok());
+        let error = parse("spawn test block_display child[tag=ready]")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("can't have a predicate"), "{error}");
+        assert!(parse("entity door[tag=ready]").is_err());
+        let error = parse("move door[score=open] 0 1 0 20")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("'score' is not a target selector argument."),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn with_fills_in_left_out_entities() {
+        let program = parse_program(
+            "with door\nmove 0 1 0 20\nturn y 90 20 start 5\nsize +5 2 10\ntag add open\nblock stone\nmove lid 0 2 0 20\nwith group:doors[tag=!locked]\nsize 3 10",
+        )
+        .unwrap();
+        let explicit = parse_program(
+            "move door 0 1 0 20\nturn door y 90 20 start 5\nsize +5 door 2 10\ntag door add open\nblock door stone\nmove lid 0 2 0 20\nsize group:doors[tag=!locked] 3 10",
+        )
+        .unwrap();
+        let without_with: Vec<&Statement> = program
+            .statements
+            .iter()
+            .filter(|statement| !matches!(statement, Statement::With(_)))
+            .collect();
+        assert_eq!(without_with, explicit.statements.iter().collect::<Vec<_>>());
+
+        let error = parse_program("move 0 1 0 20").unwrap_err().to_string();
+        assert!(
+            error.contains("leaves out its entity, but no 'with <entity>' comes before it"),
+            "{error}"
+        );
+        let error = parse_program("with door\nmove 0 1 20")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Incorrect number of arguments in 'move 0 1 20'"),
+            "{error}"
+        );
+        let error = parse_program("spawn door block_display lid\nwith door\nblock lid")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Incorrect number of arguments in 'block lid'"),
+            "{error}"
+        );
+
+        let program = parse_program("with door\nmove [0,0,0] 0 1 0").unwrap();
+        let explicit = parse_program("move door [0,0,0] 0 1 0").unwrap();
+        assert_eq!(program.warnings.len(), explicit.warnings.len());
+    }
+
+    #[test]
+    fn waitfor_takes_a_score_condition() {
+        assert_eq!(
+            parse("waitfor $door state matches 1..").unwrap(),
+            Statement::WaitFor("$door state matches 1..".to_string())
+        );
+        assert_eq!(
+            parse("waitfor $door state >= $lever state").unwrap(),
+            Statement::WaitFor("$door state >= $lever state".to_string())
+        );
+        assert!(parse("waitfor $door state 1").is_err());
+        assert!(parse("waitfor $door state is $lever state").is_err());
+        assert!(parse("waitfor $door state matches 1 now").is_err());
+    }
+
+    #[test]
+    fn items_and_blocks_accept_a_list_of_frames() {
+        let Statement::Frames(interval, frames) =
+            parse("item test 1s stone dirt[damage=2]").unwrap()
+        else {
+            panic!("expected frames");
+        };
+        assert_eq!(interval, 20);
+        assert_eq!(
+            frames,
+            [
+                parse("item test stone").unwrap(),
+                parse("item test dirt[damage=2]").unwrap()
+            ]
+        );
+        assert!(matches!(
+            parse("block test 2 stone oak_log[axis=y]").unwrap(),
+            Statement::Frames(2, frames) if frames.len() == 2
+        ));
+        let error = parse("block test 2 stone Oak_Log").unwrap_err();
+        assert!(error.to_string().contains("Column: 20"), "{error}");
+        assert!(parse("item test stone dirt").is_err());
+        assert!(parse("item test 2x stone dirt").is_err());
+    }
+
+    #[test]
+    fn item_and_block_ids_must_be_resource_locations() {
+        assert!(parse("item test diamond_sword").is_ok());
+        assert!(parse("item test minecraft:diamond_sword[minecraft:damage=5]").is_ok());
+        assert!(parse("block test custom.pack:decor/lamp[lit=true]").is_ok());
+        let error = parse("item test Minecraft:Stone").unwrap_err();
+        assert!(error.to_string().contains("Column: 11"), "{error}");
+        assert!(parse("item test minecraft:stone:slab").is_err());
+        assert!(parse("block test oak log").is_err());
+        assert!(parse("block test :stone[axis=y]").is_err());
+    }
+
+    #[test]
+    fn item_components_stay_intact() {
+        let item = r#"minecraft:diamond_sword[enchantments={levels:{"minecraft:sharpness": 5, "minecraft:looting": 3}}, custom_name='"Blade of [Dawn]"', damage=10]"#;
+        assert_eq!(
+            parse(&format!("item test {item}")).unwrap(),
+            Statement::Item(
+                Entity::new("test", &Regexes::new(false).unwrap().name).unwrap(),
+                item.to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn arguments_split_outside_quotes_and_brackets() {
+        assert_eq!(
+            split_arguments(r#"test [1, 0, 0] "a b \" c" 'd e' {"f": [1, 2]}"#),
+            [
+                (0, "test"),
+                (5, "[1, 0, 0]"),
+                (15, r#""a b \" c""#),
+                (26, "'d e'"),
+                (32, r#"{"f": [1, 2]}"#)
+            ]
+        );
+    }
+
+    #[test]
+    fn any_whitespace_separates_arguments() {
+        assert_eq!(
+            split_arguments("move  test\t0 1\t \t0   20"),
+            [
+                (0, "move"),
+                (6, "test"),
+                (11, "0"),
+                (13, "1"),
+                (17, "0"),
+                (21, "20")
+            ]
+        );
+        assert_eq!(
+            parse("move  test 0 1 0 20").unwrap(),
+            parse("move test 0 1 0 20").unwrap()
+        );
+        assert_eq!(
+            parse("text\ttest  \"two  spaces\"").unwrap(),
+            parse("text test \"two  spaces\"").unwrap()
+        );
+        let error = parse_program("move \t  test 0 1 20")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Line: 1, Column: 8"), "{error}");
+    }
+
+    #[test]
+    fn mirror_takes_a_principal_axis() {
+        assert!(matches!(
+            parse("mirror test x").unwrap(),
+            Statement::Mirror(_, Axis::X)
+        ));
+        assert!(matches!(
+            parse("flip test z").unwrap(),
+            Statement::Mirror(_, Axis::Z)
+        ));
+        let error = parse("mirror test w").unwrap_err();
+        assert!(
+            error.to_string().contains("'w' is not a valid axis."),
+            "{error}"
+        );
+        assert!(parse("mirror test").is_err());
+    }
+
+    #[test]
+    fn transformations_accept_start_offset() {
+        let Statement::Translate(_, _, duration, start) =
+            parse("move test 0 1 0 20 start 10").unwrap()
+        else {
+            panic!("expected a translate statement");
+        };
+        assert_eq!((duration, start), (20, 10));
+        assert!(matches!(
+            parse("turn test y 90 20 pivot 0 1 0 start 5").unwrap(),
+            Statement::Rotate(_, _, 20, 5)
+        ));
+        assert!(matches!(
+            parse("turn test quat 0 0 0.707 0.707 20 start 5").unwrap(),
+            Statement::Rotate(_, _, 20, 5)
+        ));
+        assert!(matches!(
+            parse("size test 2 20").unwrap(),
+            Statement::Scale(_, _, 20, 0)
+        ));
+        let error = parse("move test 0 1 0 20 start -10").unwrap_err();
+        assert!(
+            error.to_string().contains("must not be negative"),
+            "{error}"
+        );
+        assert!(parse("size test 2 20 start soon").is_err());
+    }
+
+    #[test]
+    fn spawn_resolves_entity_type_aliases() {
+        assert!(matches!(
+            parse("spawn test item child").unwrap(),
+            Statement::Spawn(_, entity_type, ..) if entity_type == "item_display"
+        ));
+        let mut config = test_config();
+        config.entity_types = vec!["minecraft:interaction".to_string()];
+        assert!(parse_with("spawn test minecraft:interaction child", &config).is_ok());
+        assert!(parse("spawn test minecraft:interaction child").is_err());
+    }
+
+    #[test]
+    fn spawn_accepts_mounted_modifier() {
+        assert!(matches!(
+            parse("spawn test block_display child mounted").unwrap(),
+            Statement::Spawn(_, _, _, None, true)
+        ));
+        assert!(matches!(
+            parse("spawn test block_display child").unwrap(),
+            Statement::Spawn(_, _, _, None, false)
+        ));
+        assert!(parse("spawn test block_display child riding").is_err());
+    }
+
+    #[test]
+    fn namespace_follows_resource_location_rules() {
+        assert_eq!(
+            parse("namespace my_pack.v2").unwrap(),
+            Statement::Namespace("my_pack.v2".to_string())
+        );
+        assert!(parse("namespace MyPack").is_err());
+        assert!(parse("namespace my/pack").is_err());
+        assert!(parse("namespace my:pack").is_err());
+    }
+
+    #[test]
+    fn raw_keeps_the_command_verbatim() {
+        let expected = Statement::Raw(
+            "execute as @e[type=pig,  limit=1] run say \"hi  there\"".to_string(),
+            true,
+        );
+        assert_eq!(
+            parse("raw execute as @e[type=pig,  limit=1] run say \"hi  there\"").unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse("cmd /execute as @e[type=pig,  limit=1] run say \"hi  there\"").unwrap(),
+            expected
+        );
+        assert!(parse("raw").is_err());
+    }
+
+    #[test]
+    fn trailing_arguments_are_reported_where_they_are() {
+        for (line, column) in [
+            ("move entity 1 2 3 10 strat 5", 22),
+            ("turn entity y 90 10 pivto 0 0 0", 21),
+            ("spawn entity block_display new mountd", 32),
+            ("tag entity add moving now", 23),
+            ("text entity \"hello there\" now", 27),
+        ] {
+            let error = parse(line).unwrap_err().to_string();
+            assert!(error.contains("is unexpected"), "{error}");
+            assert!(error.contains(&format!("Column: {column}")), "{error}");
+        }
+        assert!(parse("move entity 1 2 3 10 start 5").is_ok());
+        assert!(parse("turn entity y 90 10 pivot 0 0 0").is_ok());
+        assert!(parse("spawn entity block_display new mounted").is_ok());
+    }
+
+    #[test]
+    fn unquoted_text_takes_the_rest_of_the_line() {
+        assert_eq!(
+            parse("text entity hello there").unwrap(),
+            Statement::Text(
+                Entity::new("entity", &Regexes::new(false).unwrap().name).unwrap(),
+                TextComponent::Plain("hello there".to_string())
+            )
+        );
+    }
+
+    #[test]
+    fn scale_accepts_percentages_per_axis() {
+        let scale = |line| match parse(line).unwrap() {
+            Statement::Scale(_, scale, _, _) => scale,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(
+            scale("size cube 150% 150% 150% 20"),
+            Scale::new((1.5, 1.5, 1.5))
+        );
+        assert_eq!(scale("size cube 200% 1 1 20"), Scale::new((2.0, 1.0, 1.0)));
+        assert_eq!(scale("size cube 50% 20"), Scale::new((0.5, 0.5, 0.5)));
+        assert_eq!(scale("size cube 2 20"), Scale::new((2.0, 2.0, 2.0)));
+        for line in [
+            "size cube %50 1 1 20",
+            "size cube 1.5%. 1 1 20",
+            "size cube 5%% 20",
+        ] {
+            let error = parse(line).unwrap_err().to_string();
+            assert!(error.contains("Coordinate"), "{error}");
+        }
+    }
+
+    #[test]
+    fn errors_point_at_the_source_column_of_their_argument() {
+        let error = parse_program("block cube \\\n    minecraft:stone[facing=]")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Line: 2, Column: 21"), "{error}");
+        let error = parse_program("  text cube \"bad \\q\"")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Line: 1, Column: 13"), "{error}");
+        let error = parse_program("item cube \\\n  Stone")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Line: 2, Column: 3"), "{error}");
+    }
+
+    #[test]
+    fn times_can_be_given_in_seconds() {
+        assert_eq!(parse("wait 2s").unwrap(), Statement::Wait(40));
+        assert_eq!(parse("wait 0.12s").unwrap(), Statement::Wait(2));
+        assert_eq!(
+            parse("move cube 0 1 0 1.5s start 1s").unwrap(),
+            parse("move cube 0 1 0 30 start 20").unwrap()
+        );
+        let config = Config {
+            ticks_per_second: 10,
+            ..test_config()
+        };
+        assert_eq!(parse_with("wait 2s", &config).unwrap(), Statement::Wait(20));
+        assert_eq!(parse_with("wait 2", &config).unwrap(), Statement::Wait(2));
+        assert!(parse("wait -1s").is_err());
+        assert!(parse("wait s").is_err());
+    }
+
+    #[test]
+    fn coordinate_mistakes_get_hints() {
+        let error = |line: &str| parse(line).unwrap_err().to_string();
+        let comma = error("move test 1,5 0 0 20");
+        assert!(comma.contains("Did you mean '1.5'?"), "{comma}");
+        let signs = error("move test ++1 0 0 20");
+        assert!(signs.contains("Did you mean '1'?"), "{signs}");
+        let unit = error("move test 0 2m 0 20");
+        assert!(unit.contains("without a unit. Did you mean '2'?"), "{unit}");
+        let dots = error("move test 1.0.0 0 0 20");
+        assert!(dots.contains("invalid float literal"), "{dots}");
+        assert!(dots.contains("at most one decimal point"), "{dots}");
+        let garbage = error("move test abc 0 0 20");
+        assert!(garbage.ends_with("invalid float literal"), "{garbage}");
+    }
+
+    #[test]
+    fn non_finite_values_are_rejected() {
+        let error = |line: &str| parse(line).unwrap_err().to_string();
+        let translation = error("move test 0 inf 0 20");
+        assert!(
+            translation.contains("Value 'inf' at 1:13 is not a finite number."),
+            "{translation}"
+        );
+        let angle = error("turn test x nan 20");
+        assert!(angle.contains("Value 'nan' at 1:13"), "{angle}");
+        assert!(parse("size test 1e39 20").is_err());
+        assert!(parse("turn test [0,NaN,1] 90 20").is_err());
+        assert!(parse("turn test quat 0 0 inf 1 20").is_err());
+        assert!(parse("turn test y 90 20 pivot 0 -inf 0").is_err());
+    }
+
+    #[test]
+    fn entities_named_after_their_object_are_rejected() {
+        let error = parse_program("object door:open\nmove door 0 1 0 20")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Line: 2, Column: 1") && error.contains("Entity name 'door'"),
+            "{error}"
+        );
+        // Without an `object` line, the object is named after the file.
+        assert!(parse_program("spawn frame block test").is_err());
+        assert!(parse_program("object door:open\nmove test 0 1 0 20").is_ok());
+        assert!(parse_program("object door:open\nmove frame 0 1 0 20\nobject frame:spin").is_ok());
+    }
+
+    #[test]
+    fn strict_entities_reject_undeclared_names() {
+        let config = Config {
+            strict_entities: true,
+            ..test_config()
+        };
+        let file_info = FileInfo::new("test.dspa".to_string(), TrackedChar::new(1, 1, '\n'));
+        let parse_strict =
+            |contents: &str| Program::parse_from_file(&file_info, &to_tracked(contents), &config);
+        let source = "entity cube\nmove cube 0 1 0 20\nspawn cube block child\ntag child add petals\nsize group:petals 2 5";
+        assert!(parse_strict(source).is_ok());
+        let error = parse_strict("entity cube\nmove cbue 0 1 0 20")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("Line: 2, Column: 1")
+                && error.contains("Entity 'cbue' is never declared."),
+            "{error}"
+        );
+        assert!(parse_program("move cbue 0 1 0 20").is_ok());
+    }
+
+    #[test]
+    fn output_names_one_file() {
+        assert_eq!(
+            parse("output rotate").unwrap(),
+            Statement::Output("rotate".to_string())
+        );
+        assert!(parse("output turret/rotate").is_err());
+        assert!(parse("output ../rotate").is_err());
+        assert!(parse("output Rotate").is_err());
+    }
+
+    #[test]
+    fn block_states_can_be_checked_against_vanilla() {
+        let config = Config {
+            validate_block_states: true,
+            ..test_config()
+        };
+        let parse_checked = |line: &str| parse_with(line, &config);
+        assert!(parse_checked("block test minecraft:oak_log[axis=y]").is_ok());
+        assert!(
+            parse_checked("block test oak_stairs[facing=east, half=top, waterlogged=false]")
+                .is_ok()
+        );
+        assert!(parse_checked("block test wheat[age=7]").is_ok());
+        assert!(parse_checked("block test mymod:pipe[axiss=y]").is_ok());
+        let key = parse_checked("block test oak_log[axiss=y]")
+            .unwrap_err()
+            .to_string();
+        assert!(key.contains("Column: 20"), "{key}");
+        assert!(
+            key.contains(
+                "Block 'oak_log' has no state property 'axiss'. Expected one of: [\"axis\"]"
+            ),
+            "{key}"
+        );
+        let value = parse_checked("block test wheat[age=8]")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            value.contains("'8' is not a valid value of block state property 'age'"),
+            "{value}"
+        );
+        assert!(parse("block test oak_log[axiss=y]").is_ok());
+    }
 }
@@ -0,0 +1,174 @@
+use crate::{
+    errors::{CompileError, CompileErrorType},
+    objects::Position,
+};
+
+/// Checks every line `source` generated, reporting the first that isn't a well-formed command at the
+/// statement's position. This only catches the compiler's own mistakes, like an unbalanced bracket or
+/// an unescaped quote in SNBT, not commands the game would reject for other reasons.
+pub fn check(
+    file_path: &str,
+    position: Position,
+    source: &str,
+    command: &str,
+) -> Option<CompileError> {
+    command.lines().find_map(|line| {
+        let problem = problem(line)?;
+        Some(CompileError::in_file(
+            file_path,
+            position,
+            CompileErrorType::MalformedCommand(source, line.trim(), problem),
+        ))
+    })
+}
+
+/// Why a generated line isn't a well-formed command, if it isn't. Comments and blank lines are always
+/// fine, and a macro line is checked as the command it runs.
+pub fn problem(line: &str) -> Option<String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let command = line.strip_prefix('$').unwrap_or(line);
+    bracket_problem(command).or_else(|| execute_problem(&words(command)))
+}
+
+/// Checks that brackets and braces pair up, and that every quote inside them is closed. Quotes
+/// outside them are left alone, since plain text like a `say` can hold an apostrophe.
+fn bracket_problem(command: &str) -> Option<String> {
+    let mut open = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for character in command.chars() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if character == '\\' => escaped = true,
+            Some(opening) if character == opening => quote = None,
+            Some(_) => {}
+            None => match character {
+                '"' | '\'' if !open.is_empty() => quote = Some(character),
+                '{' | '[' => open.push(character),
+                '}' | ']' => {
+                    let opening = if character == '}' { '{' } else { '[' };
+                    match open.pop() {
+                        Some(last) if last == opening => {}
+                        Some(last) => return Some(format!("'{character}' closes a '{last}'.")),
+                        None => return Some(format!("'{character}' has nothing to close.")),
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+    if let Some(quote) = quote {
+        return Some(format!("A {quote} quote is never closed."));
+    }
+    open.last().map(|last| format!("'{last}' is never closed."))
+}
+
+/// The words of a command, split on the whitespace outside brackets and quotes.
+fn words(command: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut depth = 0_usize;
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, character) in command.char_indices() {
+        match (quote, character) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(opening), _) if character == opening => quote = None,
+            (None, '"' | '\'') if depth > 0 => quote = Some(character),
+            (None, '{' | '[') => depth += 1,
+            (None, '}' | ']') => depth = depth.saturating_sub(1),
+            (None, _) if depth == 0 && character.is_whitespace() => {
+                if start < index {
+                    words.push(&command[start..index]);
+                }
+                start = index + character.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    if start < command.len() {
+        words.push(&command[start..]);
+    }
+    words
+}
+
+/// Checks that every `execute` has subcommands, and that each `run` is followed by a command.
+fn execute_problem(words: &[&str]) -> Option<String> {
+    let mut index = 0;
+    while words.get(index) == Some(&"execute") {
+        let run = words[index + 1..]
+            .iter()
+            .position(|word| *word == "run")
+            .map(|run| index + 1 + run);
+        match run {
+            None if index + 1 == words.len() => {
+                return Some("'execute' has no subcommands.".to_string())
+            }
+            None => return None,
+            Some(run) if run == index + 1 => {
+                return Some("'execute' has no subcommands before 'run'.".to_string())
+            }
+            Some(run) if run + 1 == words.len() => {
+                return Some("'run' is not followed by a command.".to_string())
+            }
+            Some(run) => index = run + 1,
+        }
+    }
+    let name = words.get(index)?;
+    (!name.chars().all(|c| c.is_ascii_lowercase() || c == '_'))
+        .then(|| format!("'{name}' is not a command."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn well_formed_commands_pass() {
+        for line in [
+            "execute as @e[tag=obj,tag=cube] run data merge entity @s {text:'{\"text\":\"it\\'s\"}'}",
+            "execute if score $obj-anim timer matches 5.. run scoreboard players set $obj-anim timer -1",
+            "$data merge entity @s $(nbt)",
+            "say it's [almost] done",
+            "# a comment [",
+            "",
+        ] {
+            assert_eq!(problem(line), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn malformed_commands_are_caught() {
+        for (line, expected) in [
+            (
+                "data merge entity @s {text:'it's'}",
+                "A ' quote is never closed.",
+            ),
+            (
+                "data merge entity @s {transformation:[0f,1f}",
+                "'}' closes a '['.",
+            ),
+            (
+                "data merge entity @s {Tags:[\"a\"]}]",
+                "']' has nothing to close.",
+            ),
+            (
+                "summon item_display ~ ~ ~ {Tags:[\"a\"]",
+                "'{' is never closed.",
+            ),
+            ("execute as @s run", "'run' is not followed by a command."),
+            (
+                "execute run say hi",
+                "'execute' has no subcommands before 'run'.",
+            ),
+            ("execute", "'execute' has no subcommands."),
+            ("execute as @s run Say hi", "'Say' is not a command."),
+        ] {
+            assert_eq!(problem(line).as_deref(), Some(expected), "{line}");
+        }
+    }
+}
@@ -0,0 +1,3 @@
+# File generated using DiSPA v0.1.0
+scoreboard players set $test_obj-test_anim flags 1
+scoreboard players set $test_obj-test_anim timer 0
\ No newline at end of file
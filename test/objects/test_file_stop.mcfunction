@@ -0,0 +1,4 @@
+# File generated using DiSPA v0.1.0
+# Stops playback only; display entities keep their current transformation.
+scoreboard players set $test_obj-test_anim flags 0
+scoreboard players set $test_obj-test_anim timer -1
\ No newline at end of file
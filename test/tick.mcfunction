@@ -1 +1,2 @@
+# File generated using DiSPA v0.1.0
 execute if score $test_obj-test_anim flags matches 1.. run function de:test/objects/test_file